@@ -0,0 +1,40 @@
+//! Generates Rust structs from Classes
+use crate::Context;
+use atomic_lib::{codegen, errors::AtomicResult};
+
+/// Prints the Rust code for the passed Classes, or for the Classes in a JSON-AD file
+pub fn codegen(context: &Context) -> AtomicResult<()> {
+    let subcommand_matches = context.matches.subcommand_matches("codegen").unwrap();
+    let mut code = String::new();
+    if let Some(path) = subcommand_matches.value_of("file") {
+        let json_ad = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read JSON-AD file {}. {}", path, e))?;
+        code = codegen::json_ad_to_rust(&json_ad, &context.store)?;
+    }
+    if let Some(classes) = subcommand_matches.values_of("classes") {
+        let mut class_urls = Vec::new();
+        for class_input in classes {
+            let class_url = context
+                .mapping
+                .lock()
+                .unwrap()
+                .try_mapping_or_url(class_input)
+                .ok_or(format!("No url found for {}", class_input))?;
+            class_urls.push(class_url);
+        }
+        let generated = codegen::classes_to_rust(&class_urls, &context.store)?;
+        if code.is_empty() {
+            code = generated;
+        } else {
+            // Skip the header comment, which is already present
+            code.push_str(
+                generated
+                    .split_once('\n')
+                    .map(|(_, rest)| rest)
+                    .unwrap_or_default(),
+            );
+        }
+    }
+    print!("{}", code);
+    Ok(())
+}
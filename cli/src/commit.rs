@@ -12,7 +12,7 @@ pub fn set(context: &Context) -> AtomicResult<()> {
         Err(_) => atomic_lib::Resource::new(subject),
     };
     resource.set_propval_shortname(&property, &value, &context.store)?;
-    post(context, resource.get_commit_builder().clone())?;
+    post(context, resource.get_commit_builder_with_base())?;
     Ok(())
 }
 
@@ -35,7 +35,7 @@ pub fn edit(context: &Context) -> AtomicResult<()> {
     // Remove newline - or else I can's save shortnames or numbers using vim;
     let trimmed = edited.trim_end_matches('\n');
     resource.set_propval_shortname(&prop, trimmed, &context.store)?;
-    post(context, resource.get_commit_builder().clone())?;
+    post(context, resource.get_commit_builder_with_base())?;
     Ok(())
 }
 
@@ -45,7 +45,7 @@ pub fn remove(context: &Context) -> AtomicResult<()> {
     let prop = argument_to_string(context, "property")?;
     let mut resource = context.store.get_resource(&subject)?;
     resource.remove_propval_shortname(&prop, &context.store)?;
-    post(context, resource.get_commit_builder().clone())?;
+    post(context, resource.get_commit_builder_with_base())?;
     Ok(())
 }
 
@@ -58,7 +58,8 @@ pub fn destroy(context: &Context) -> AtomicResult<()> {
     Ok(())
 }
 
-/// Signs the Commit, Posts it and applies it to the server
+/// Signs the Commit, Posts it and applies it to the server.
+/// Queues it if the server can't be reached, see `push`.
 fn post(context: &Context, commit_builder: atomic_lib::commit::CommitBuilder) -> AtomicResult<()> {
     context.get_write_context();
     let agent = context
@@ -66,7 +67,41 @@ fn post(context: &Context, commit_builder: atomic_lib::commit::CommitBuilder) ->
         .get_default_agent()
         .expect("No default agent set");
     let commit = commit_builder.sign(&agent, &context.store)?;
-    atomic_lib::client::post_commit(&commit, &context.store)?;
+    if atomic_lib::queue::post_or_queue(&commit, &context.store)? {
+        println!("Server could not be reached, or earlier Commits are still queued. The Commit is queued, run `atomic-cli push` to send it later.");
+    }
+    Ok(())
+}
+
+/// Sends the queued Commits in order, and reports the ones that were rejected
+pub fn push(context: &Context) -> AtomicResult<()> {
+    let queue = context
+        .store
+        .get_commit_queue()
+        .ok_or("No commit queue has been set.")?;
+    if queue.is_empty(&context.store)? {
+        println!("No queued Commits.");
+        return Ok(());
+    }
+    let report = queue.push(&context.store)?;
+    println!("Sent {} Commits.", report.accepted.len());
+    for (commit, reason) in report.rejected.iter() {
+        eprintln!(
+            "Rejected Commit for {}: {}\n{}",
+            commit.get_subject(),
+            reason,
+            commit.clone().into_resource(&context.store)?.to_json_ad()?
+        );
+    }
+    if report.remaining > 0 {
+        println!("{} Commits are still queued.", report.remaining);
+    }
+    if let Some(reason) = report.stopped {
+        println!("Stopped sending: {}", reason);
+    }
+    if !report.rejected.is_empty() {
+        return Err(format!("{} Commits were rejected.", report.rejected.len()).into());
+    }
     Ok(())
 }
 
@@ -8,10 +8,12 @@ use std::{cell::RefCell, path::PathBuf, sync::Mutex};
 
 use crate::print::{SERIALIZE_OPTIONS, print_resource};
 
+mod codegen;
 mod commit;
 mod new;
 mod path;
 mod print;
+mod schema;
 
 #[allow(dead_code)]
 /// The Context contains all the data for executing a single CLI command, such as the passed arguments and the in memory store.
@@ -47,26 +49,72 @@ impl Context<'_> {
 /// Reads config files for writing data, or promps the user if they don't yet exist
 fn set_agent_config() -> AtomicResult<Config> {
     let agent_config_path = atomic_lib::config::default_config_file_path()?;
-    match atomic_lib::config::read_config(&agent_config_path) {
-        Ok(found) => Ok(found),
-        Err(_e) => {
-            println!("No config found. Let's create one!");
-            let server = promptly::prompt("What's the base url of your Atomic Server?")?;
-            let agent = promptly::prompt("What's the URL of your Agent?")?;
-            let private_key = promptly::prompt("What's the private key of this Agent?")?;
-            let config = atomic_lib::config::Config {
-                server,
-                private_key,
-                agent,
-            };
-            atomic_lib::config::write_config(&agent_config_path, config.clone())?;
-            println!(
-                "New config file created at {:?}",
-                agent_config_path.to_str()
-            );
-            Ok(config)
-        }
+    if agent_config_path.exists() {
+        return atomic_lib::config::read_config_with(&agent_config_path, prompt_passphrase);
+    }
+    println!("No config found. Let's create one!");
+    let server = promptly::prompt("What's the base url of your Atomic Server?")?;
+    let agent = promptly::prompt("What's the URL of your Agent?")?;
+    let private_key = prompt_hidden("What's the private key of this Agent?")?;
+    let passphrase =
+        prompt_hidden("Passphrase to encrypt the private key (leave empty to store it as plain text)")?;
+    let passphrase = Some(passphrase).filter(|p| !p.is_empty());
+    let config = atomic_lib::config::Config {
+        server,
+        private_key,
+        agent,
+    };
+    match passphrase {
+        Some(passphrase) => atomic_lib::config::write_config_encrypted(&agent_config_path, config.clone(), &passphrase)?,
+        None => atomic_lib::config::write_config(&agent_config_path, config.clone())?,
+    };
+    println!(
+        "New config file created at {:?}",
+        agent_config_path.to_str()
+    );
+    Ok(config)
+}
+
+/// Asks the user for a secret, without showing it in the terminal
+#[cfg(feature = "native")]
+fn prompt_hidden(message: &str) -> AtomicResult<String> {
+    let secret = rpassword::read_password_from_tty(Some(&format!("{}: ", message)))?;
+    Ok(secret)
+}
+
+/// WASI has no terminal to hide the input in
+#[cfg(not(feature = "native"))]
+fn prompt_hidden(message: &str) -> AtomicResult<String> {
+    let secret: Option<String> = promptly::prompt_opt(message)?;
+    Ok(secret.unwrap_or_default())
+}
+
+/// Asks the user for the passphrase of an encrypted config
+fn prompt_passphrase() -> AtomicResult<String> {
+    prompt_hidden(&format!(
+        "Passphrase for your private key (set {} to skip this prompt)",
+        atomic_lib::config::PASSPHRASE_ENV
+    ))
+}
+
+/// Encrypts the private key of an existing plain text config
+fn encrypt_config() -> AtomicResult<()> {
+    let path = atomic_lib::config::default_config_file_path()?;
+    if atomic_lib::config::config_is_encrypted(&path)? {
+        return Err(format!("The private key in {:?} is already encrypted.", path).into());
+    }
+    let config = atomic_lib::config::read_config(&path)?;
+    let passphrase = prompt_hidden("New passphrase for your private key")?;
+    if passphrase.is_empty() {
+        return Err("The passphrase can't be empty.".into());
     }
+    let repeated = prompt_hidden("Repeat the passphrase")?;
+    if passphrase != repeated {
+        return Err("Passphrases do not match.".into());
+    }
+    atomic_lib::config::write_config_encrypted(&path, config, &passphrase)?;
+    println!("Encrypted the private key in {:?}", path);
+    Ok(())
 }
 
 fn main() -> AtomicResult<()> {
@@ -89,10 +137,14 @@ fn main() -> AtomicResult<()> {
                     .about("Get a Resource or Value by using Atomic Paths.",
                     )
                     .after_help("\
-                    Traverses a Path and prints the resulting Resource or Value. \n\n\
+                    Traverses a Path and prints the resulting Resource or Value. \n\
+                    Use `~property` to follow incoming links, and `*` for all items of an array. \
+                    Paths with multiple results print one result per line. \n\n\
                     Examples: \n\n\
                     $ atomic get class https://atomicdata.dev/properties/description\n\
                     $ atomic get class description\n\
+                    $ atomic get \"class requires * shortname\"\n\
+                    $ atomic get https://example.com ~parent\n\
                     $ atomic get https://example.com \n\n\
                     Visit https://docs.atomicdata.dev/core/paths.html for more info about paths. \
                     ")
@@ -192,7 +244,82 @@ fn main() -> AtomicResult<()> {
                 )
         )
         .subcommand(SubCommand::with_name("list").about("List all bookmarks"))
-        .subcommand(SubCommand::with_name("validate").about("Validates the store").setting(AppSettings::Hidden))
+        .subcommand(
+            SubCommand::with_name("push")
+                .alias("sync")
+                .about("Sends the Commits that were queued while the server could not be reached.")
+                .after_help("\
+                    Commits are sent in the order in which they were made. \
+                    Sending stops when the server can't be reached, is rate limiting or has an error, and the rest stays queued. \
+                    Commits that the server refuses as invalid are printed and removed from the queue. \
+                    Servers refuse Commits that are more than 24 hours old, so push within a day. \
+                    ")
+        )
+        .subcommand(
+            SubCommand::with_name("schema")
+                .about("Converts Classes to JSON Schema, and back.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Prints the JSON Schema of a Class, including the Properties it inherits.")
+                        .arg(Arg::with_name("class")
+                            .help("The URL or shortname of the Class")
+                            .required(true)
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("import")
+                        .about("Creates a draft Class and its Properties from a JSON Schema file, and prints them as JSON-AD.")
+                        .after_help("\
+                            The `title` of the schema is used as the shortname of the Class. \
+                            Properties with an `$id` that is a known Property are re-used. \
+                            Nothing is saved, so you can check the drafts before importing them. \
+                            ")
+                        .arg(Arg::with_name("file")
+                            .help("Path to the JSON Schema file")
+                            .required(true)
+                        )
+                        .arg(Arg::with_name("base")
+                            .long("base")
+                            .help("Base URL of the new Class and Properties. Defaults to the server in your config.")
+                            .takes_value(true)
+                        )
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("codegen")
+                .about("Generates Rust structs from Classes, and prints them.")
+                .after_help("\
+                    Every struct implements `TryFrom<Resource>` and has an `into_resource` method. \
+                    Required Properties become fields, recommended Properties become `Option` fields. \
+                    ")
+                .arg(Arg::with_name("classes")
+                    .help("The URLs or shortnames of the Classes")
+                    .multiple(true)
+                    .required_unless("file")
+                )
+                .arg(Arg::with_name("file")
+                    .long("file")
+                    .help("Path to a JSON-AD file. Generates structs for all the Classes in it.")
+                    .takes_value(true)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("encrypt-config")
+                .about("Encrypts the private key in your existing config.toml using a passphrase.")
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Validates the store, and prints the issues it finds.")
+                .arg(Arg::with_name("json")
+                    .long("json")
+                    .help("Prints the report as JSON")
+                )
+                .arg(Arg::with_name("fetch")
+                    .long("fetch")
+                    .help("Also checks if every Resource can be fetched from its URL")
+                )
+        )
         .get_matches();
 
     let config_folder = home_dir()
@@ -210,6 +337,8 @@ fn main() -> AtomicResult<()> {
 
     // Initialize an in-memory store
     let store = atomic_lib::Store::init()?;
+    // Commits that can't be sent yet are stored here
+    store.set_commit_queue(atomic_lib::queue::CommitQueue::open(&config_folder.join("queue.json")));
     // Add some default data / common properties to speed things up
     store.populate()?;
 
@@ -235,9 +364,15 @@ fn main() -> AtomicResult<()> {
 
 fn exec_command(context: &mut Context) -> AtomicResult<()> {
     match context.matches.subcommand_name() {
+        Some("codegen") => {
+            codegen::codegen(context)?;
+        }
         Some("destroy") => {
             commit::destroy(context)?;
         }
+        Some("encrypt-config") => {
+            encrypt_config()?;
+        }
         Some("edit") => {
             #[cfg(feature = "native")] {
                 commit::edit(context)?;
@@ -255,9 +390,19 @@ fn exec_command(context: &mut Context) -> AtomicResult<()> {
         Some("new") => {
             new::new(context)?;
         }
+        Some("push") => {
+            commit::push(context)?;
+        }
         Some("remove") => {
             commit::remove(context)?;
         }
+        Some("schema") => {
+            match context.matches.subcommand_matches("schema").unwrap().subcommand_name() {
+                Some("export") => schema::export(context)?,
+                Some("import") => schema::import(context)?,
+                _ => return Err("Use schema export or schema import".into()),
+            }
+        }
         Some("set") => {
             commit::set(context)?;
         }
@@ -265,7 +410,7 @@ fn exec_command(context: &mut Context) -> AtomicResult<()> {
             tpf(context)?;
         }
         Some("validate") => {
-            validate(context);
+            validate(context)?;
         }
         Some(cmd) => return Err(format!("{} is not a valid command. Run atomic --help", cmd).into()),
         None => println!("Run atomic --help for available commands"),
@@ -309,10 +454,21 @@ fn tpf_value(string: &str) -> Option<&str> {
     }
 }
 
-/// Validates the store
-fn validate(context: &mut Context) {
-    let reportstring = context.store.validate().to_string();
-    println!("{}", reportstring);
+/// Validates the store, prints the issues as they are found, or the full report as JSON
+fn validate(context: &mut Context) -> AtomicResult<()> {
+    let subcommand_matches = context.matches.subcommand_matches("validate").unwrap();
+    let fetch_items = subcommand_matches.is_present("fetch");
+    if subcommand_matches.is_present("json") {
+        let report = atomic_lib::validate::validate_store(&context.store, fetch_items);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        let report =
+            atomic_lib::validate::validate_store_streaming(&context.store, fetch_items, |issue| {
+                println!("{}", issue)
+            });
+        println!("{}", report);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -331,6 +487,32 @@ mod test {
         cmd.args(&["get","shortname"]).assert().success();
     }
 
+    #[test]
+    fn schema_export_import() {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        let output = cmd.args(["schema", "export", "class"]).assert().success();
+        let schema = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        assert!(schema.contains("\"required\""));
+        let path = std::env::temp_dir().join("atomic-cli-class-schema.json");
+        std::fs::write(&path, schema.replace("\"title\": \"class\"", "\"title\": \"Thing\"")).unwrap();
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        let output = cmd
+            .args(["schema", "import", path.to_str().unwrap(), "--base", "https://example.com"])
+            .assert()
+            .success();
+        let imported = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        assert!(imported.contains("https://example.com/classes/thing"));
+    }
+
+    #[test]
+    fn codegen_class() {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        let output = cmd.args(["codegen", "class"]).assert().success();
+        let code = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        assert!(code.contains("pub struct Class {"));
+        assert!(code.contains("pub fn into_resource(self) -> atomic_lib::Resource {"));
+    }
+
     #[test]
     fn get_url() {
         let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
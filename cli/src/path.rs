@@ -1,5 +1,5 @@
 use crate::{Context, print::{get_serialization, print_resource}};
-use atomic_lib::{errors::AtomicResult, serialize, storelike, Atom, Storelike};
+use atomic_lib::{errors::AtomicResult, serialize, storelike, urls, Atom, Storelike};
 use serialize::Format;
 
 /// Resolves an Atomic Path query
@@ -12,13 +12,32 @@ pub fn get_path(context: &mut Context) -> AtomicResult<()> {
     let path_string: String = path_vec.join(" ");
     let serialization: Format = get_serialization(subcommand_matches)?;
 
-    // Returns a URL or Value
+    // Incoming links are only known by the server, so these paths are resolved by its `/path` endpoint
+    if path_string.split(' ').any(|item| item.starts_with('~')) {
+        return get_remote_path(context, &path_string);
+    }
+
+    // Returns URLs or Values
     let store = &mut context.store;
-    let path = store
-        .get_path(&path_string, Some(&context.mapping.lock().unwrap()))?;
-    let out = match path {
+    let mut results = store
+        .get_path_all(&path_string, Some(&context.mapping.lock().unwrap()))?;
+    if results.len() != 1 {
+        for result in results {
+            match result {
+                storelike::PathReturn::Subject(subject) => println!("{}", subject),
+                storelike::PathReturn::Atom(atom) => match serialization {
+                    Format::NT => {
+                        println!("{}", serialize::atoms_to_ntriples(vec![*atom], store)?.trim_end())
+                    }
+                    _ => println!("{}", atom.value),
+                },
+            }
+        }
+        return Ok(());
+    }
+    let out = match results.remove(0) {
         storelike::PathReturn::Subject(subject) => {
-            let resource = store.get_resource_extended(&subject)?;
+            let resource = store.get_resource_extended(&subject, None)?;
             print_resource(context, &resource, subcommand_matches)?;
             return Ok(())
         },
@@ -35,3 +54,33 @@ pub fn get_path(context: &mut Context) -> AtomicResult<()> {
     println!("{}", out);
     Ok(())
 }
+
+/// Resolves the path using the `/path` endpoint of the server that hosts the first Resource of the path
+fn get_remote_path(context: &Context, path_string: &str) -> AtomicResult<()> {
+    let subcommand_matches = context.matches.subcommand_matches("get").unwrap();
+    let mut items = path_string.splitn(2, ' ');
+    let first = items.next().unwrap_or_default();
+    let subject = context
+        .mapping
+        .lock()
+        .unwrap()
+        .try_mapping_or_url(first)
+        .ok_or(format!("No url found for {}", first))?;
+    let rest = items.next().unwrap_or_default();
+    let server = url::Url::parse(&subject)?.origin().ascii_serialization();
+    let mut endpoint = url::Url::parse(&format!("{}/path", server))?;
+    endpoint
+        .query_pairs_mut()
+        .append_pair("path", &format!("{} {}", subject, rest));
+    let resource = atomic_lib::client::fetch_resource(endpoint.as_str(), &context.store)?;
+    // Multiple results are returned as a list of members
+    if resource.get_subject() == endpoint.as_str() {
+        if let Ok(members) = resource.get(urls::COLLECTION_MEMBERS) {
+            for member in members.to_vec()? {
+                println!("{}", member);
+            }
+            return Ok(());
+        }
+    }
+    print_resource(context, &resource, subcommand_matches)
+}
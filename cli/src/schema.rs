@@ -0,0 +1,48 @@
+//! Converts Classes to JSON Schema, and back
+use crate::Context;
+use atomic_lib::{errors::AtomicResult, json_schema, serialize, Storelike};
+
+/// Prints the JSON Schema of a Class
+pub fn export(context: &Context) -> AtomicResult<()> {
+    let subcommand_matches = schema_matches(context)
+        .subcommand_matches("export")
+        .unwrap();
+    let class_input = subcommand_matches
+        .value_of("class")
+        .expect("Add a class value");
+    let class_url = context
+        .mapping
+        .lock()
+        .unwrap()
+        .try_mapping_or_url(class_input)
+        .ok_or(format!("No url found for {}", class_input))?;
+    let class = context.store.get_class(&class_url)?;
+    let schema = json_schema::class_to_json_schema(&class, &context.store)?;
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+/// Reads a JSON Schema file and prints the draft Class and Properties as JSON-AD
+pub fn import(context: &Context) -> AtomicResult<()> {
+    let subcommand_matches = schema_matches(context)
+        .subcommand_matches("import")
+        .unwrap();
+    let path = subcommand_matches
+        .value_of("file")
+        .expect("Add a file path");
+    let string = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read JSON Schema file {}. {}", path, e))?;
+    let schema: serde_json::Value = serde_json::from_str(&string)
+        .map_err(|e| format!("File {} is not valid JSON. {}", path, e))?;
+    let base_url = match subcommand_matches.value_of("base") {
+        Some(base) => base.trim_end_matches('/').to_string(),
+        None => context.get_write_context().server,
+    };
+    let resources = json_schema::import_json_schema(&schema, &base_url, &context.store)?;
+    println!("{}", serialize::resources_to_json_ad(resources)?);
+    Ok(())
+}
+
+fn schema_matches<'a>(context: &'a Context) -> &'a clap::ArgMatches<'a> {
+    context.matches.subcommand_matches("schema").unwrap()
+}
@@ -0,0 +1,136 @@
+//! Check signatures in authentication headers, find the correct agent.
+//! Authentication is used when reading data that requires rights, e.g. the `/audit` endpoint.
+//! The client signs `{requested_subject} {timestamp}` using the private key of the Agent.
+
+use crate::{agents::Agent, errors::AtomicResult, urls, Storelike};
+
+pub const HEADER_PUBLIC_KEY: &str = "x-atomic-public-key";
+pub const HEADER_SIGNATURE: &str = "x-atomic-signature";
+pub const HEADER_TIMESTAMP: &str = "x-atomic-timestamp";
+pub const HEADER_AGENT: &str = "x-atomic-agent";
+
+/// How old (in milliseconds) a signed request may be before it is rejected
+const ACCEPTABLE_TIME_DIFFERENCE: i64 = 10000;
+
+/// Set of values extracted from the request.
+/// Most are coming from headers.
+#[derive(Clone, Debug)]
+pub struct AuthValues {
+    // x-atomic-public-key
+    pub public_key: String,
+    // x-atomic-timestamp
+    pub timestamp: i64,
+    // x-atomic-signature
+    // Base64 encoded signature of `subject_url timestamp`
+    pub signature: String,
+    pub requested_subject: String,
+    pub agent_subject: String,
+}
+
+/// Checks if the signature is valid for this timestamp.
+/// Does not check if the agent has rights to access the subject.
+pub fn check_auth_signature(subject: &str, auth_header: &AuthValues) -> AtomicResult<()> {
+    let agent_pubkey = base64::decode(&auth_header.public_key)
+        .map_err(|e| format!("Invalid public key in authentication header. {}", e))?;
+    let message = format!("{} {}", subject, &auth_header.timestamp);
+    let peer_public_key =
+        ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, agent_pubkey);
+    let signature_bytes = base64::decode(&auth_header.signature)
+        .map_err(|e| format!("Invalid signature in authentication header. {}", e))?;
+    peer_public_key
+        .verify(message.as_bytes(), &signature_bytes)
+        .map_err(|_e| format!("Incorrect signature for auth headers. This could be due to an error during signing or serialization of the message. Message: {}", message))?;
+    Ok(())
+}
+
+/// Checks the signature, the timestamp and whether the public key belongs to the Agent.
+/// Returns the Agent's subject if everything is valid, or `None` if no authentication values are passed.
+pub fn get_agent_from_auth_values_and_check(
+    auth_header_values: Option<AuthValues>,
+    store: &impl Storelike,
+) -> AtomicResult<Option<String>> {
+    let auth_vals = match auth_header_values {
+        Some(vals) => vals,
+        None => return Ok(None),
+    };
+    check_auth_signature(&auth_vals.requested_subject, &auth_vals)
+        .map_err(|e| format!("Error checking authentication headers. {}", e))?;
+    let now = crate::datetime_helpers::now();
+    if (now - auth_vals.timestamp).abs() > ACCEPTABLE_TIME_DIFFERENCE {
+        return Err(format!(
+            "Authentication timestamp {} is too far from the current time {}. Check your clock.",
+            auth_vals.timestamp, now
+        )
+        .into());
+    }
+    let found_public_key = store
+        .get_resource(&auth_vals.agent_subject)?
+        .get(urls::PUBLIC_KEY)?
+        .to_string();
+    if found_public_key != auth_vals.public_key {
+        return Err(format!(
+            "The public key in the authentication headers does not match the public key of Agent {}",
+            auth_vals.agent_subject
+        )
+        .into());
+    }
+    Ok(Some(auth_vals.agent_subject))
+}
+
+/// Creates the authentication headers that a client should send when requesting a subject.
+/// Returns a vector of (header name, header value) pairs.
+pub fn get_authentication_headers(
+    requested_subject: &str,
+    agent: &Agent,
+) -> AtomicResult<Vec<(String, String)>> {
+    let timestamp = crate::datetime_helpers::now();
+    let message = format!("{} {}", requested_subject, timestamp);
+    let private_key = agent
+        .private_key
+        .as_ref()
+        .ok_or("No private key in agent, can't sign authentication headers")?;
+    let signature = crate::commit::sign_message(&message, private_key, &agent.public_key)?;
+    Ok(vec![
+        (HEADER_PUBLIC_KEY.into(), agent.public_key.clone()),
+        (HEADER_SIGNATURE.into(), signature),
+        (HEADER_TIMESTAMP.into(), timestamp.to_string()),
+        (HEADER_AGENT.into(), agent.subject.clone()),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sign_and_check_headers() {
+        let store = crate::Store::init().unwrap();
+        let agent = Agent::new_from_private_key(
+            None,
+            &store,
+            "CapMWIhFUT+w7ANv9oCPqrHrwZpkP2JhzF9JnyT6WcI=",
+        );
+        let subject = "http://localhost/audit";
+        let headers = get_authentication_headers(subject, &agent).unwrap();
+        let get = |name: &str| {
+            headers
+                .iter()
+                .find(|(k, _v)| k == name)
+                .unwrap()
+                .1
+                .clone()
+        };
+        let mut auth_values = AuthValues {
+            public_key: get(HEADER_PUBLIC_KEY),
+            timestamp: get(HEADER_TIMESTAMP).parse().unwrap(),
+            signature: get(HEADER_SIGNATURE),
+            requested_subject: subject.into(),
+            agent_subject: get(HEADER_AGENT),
+        };
+        check_auth_signature(subject, &auth_values).unwrap();
+        // Signatures are only valid for the subject that was requested
+        check_auth_signature("http://localhost/other", &auth_values).unwrap_err();
+        auth_values.timestamp += 1;
+        check_auth_signature(subject, &auth_values).unwrap_err();
+    }
+}
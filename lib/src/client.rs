@@ -8,20 +8,71 @@ use crate::{Resource, Storelike, errors::AtomicResult, parse::parse_json_ad_reso
 /// Ignores all atoms where the subject is different.
 /// WARNING: Calls store methods, and is called by store methods, might get stuck in a loop!
 pub fn fetch_resource(subject: &str, store: &impl Storelike) -> AtomicResult<Resource> {
-    let body = fetch_body(subject, crate::parse::JSON_AD_MIME)?;
+    fetch_resource_with_agent(subject, store, None)
+}
+
+/// Fetches a resource, signs the request if an Agent is passed.
+/// Use this for resources that require read rights.
+pub fn fetch_resource_with_agent(
+    subject: &str,
+    store: &impl Storelike,
+    agent: Option<&crate::agents::Agent>,
+) -> AtomicResult<Resource> {
+    let body = fetch_body_with_agent(subject, crate::parse::JSON_AD_MIME, agent)?;
     let resource = parse_json_ad_resource(&body, store).map_err(|e| format!("Error parsing body of {}: {}", subject, e))?;
     Ok(resource)
 }
 
+/// Returns the Resources that are included in a fetched Resource, such as the members of a Collection with `include=true`.
+/// Only returns Resources that are hosted on the same server as the fetched Resource, since other servers are not trusted to describe them.
+/// Invalid Resources are skipped.
+pub fn included_resources(resource: &Resource, store: &impl Storelike) -> Vec<Resource> {
+    let origin = match Url::parse(resource.get_subject()) {
+        Ok(url) => url.origin(),
+        Err(_) => return Vec::new(),
+    };
+    let mut validator = crate::validate::Validator::new(store, false);
+    let mut included = Vec::new();
+    for value in resource.get_propvals().values() {
+        if let crate::Value::ResourceArrayNested(nested) = value {
+            for sub in nested {
+                if !Url::parse(&sub.subject).map(|url| url.origin() == origin).unwrap_or(false) {
+                    continue;
+                }
+                let sub_resource = Resource::from_propvals(sub.propvals.clone(), sub.subject.clone());
+                match validator.validate_resource(&sub_resource).first() {
+                    Some(issue) => log::warn!("Skipped included Resource in {}. {}", resource.get_subject(), issue),
+                    None => included.push(sub_resource),
+                }
+            }
+        }
+    }
+    included
+}
+
 /// Fetches a URL, returns its body
 pub fn fetch_body(url: &str, content_type: &str) -> AtomicResult<String> {
+    fetch_body_with_agent(url, content_type, None)
+}
+
+/// Fetches a URL, returns its body.
+/// Adds the `x-atomic-*` authentication headers if an Agent is passed.
+pub fn fetch_body_with_agent(
+    url: &str,
+    content_type: &str,
+    agent: Option<&crate::agents::Agent>,
+) -> AtomicResult<String> {
     if !url.starts_with("http") {
         return Err(format!("Could not fetch url '{}', must start with http.", url).into());
     }
-    let resp = ureq::get(&url)
-        .set("Accept", content_type)
-        .timeout_read(2000)
-        .call();
+    let mut req = ureq::get(url);
+    req.set("Accept", content_type).timeout_connect(2000).timeout_read(2000);
+    if let Some(agent) = agent {
+        for (key, value) in crate::authentication::get_authentication_headers(url, agent)? {
+            req.set(&key, &value);
+        }
+    }
+    let resp = req.call();
     if resp.status() != 200 {
         return Err(format!("Could not fetch url '{}'. Status: {}", url, resp.status()).into());
     };
@@ -68,6 +119,38 @@ pub fn post_commit_custom_endpoint(
     commit: &crate::Commit,
     store: &impl Storelike,
 ) -> AtomicResult<()> {
+    match try_post_commit_custom_endpoint(endpoint, commit, store)? {
+        PostResult::Accepted => Ok(()),
+        PostResult::Rejected(reason) | PostResult::Unreachable(reason) => Err(reason.into()),
+    }
+}
+
+/// What happened to a Commit that was sent to a server
+#[derive(Debug, PartialEq)]
+pub enum PostResult {
+    Accepted,
+    /// The server refused the Commit with a 4xx status, e.g. because it is invalid or the Agent has no rights.
+    /// Sending it again won't help.
+    Rejected(String),
+    /// The server could not be reached, timed out, is rate limiting (429) or had an error (5xx).
+    /// The Commit can be sent again later.
+    Unreachable(String),
+}
+
+/// Posts a Commit to the endpoint of the Subject from the Commit.
+/// Unlike `post_commit`, it tells apart rejected Commits and servers that can't be reached.
+pub fn try_post_commit(commit: &crate::Commit, store: &impl Storelike) -> AtomicResult<PostResult> {
+    let base_url = crate::url_helpers::base_url(commit.get_subject())?;
+    let endpoint = format!("{}commit", base_url);
+    try_post_commit_custom_endpoint(&endpoint, commit, store)
+}
+
+/// Posts a Commit to an endpoint, tells apart rejected Commits and servers that can't be reached.
+pub fn try_post_commit_custom_endpoint(
+    endpoint: &str,
+    commit: &crate::Commit,
+    store: &impl Storelike,
+) -> AtomicResult<PostResult> {
     let json = commit.clone().into_resource(store)?.to_json_ad()?;
 
     let resp = ureq::post(&endpoint)
@@ -75,23 +158,144 @@ pub fn post_commit_custom_endpoint(
         .timeout_read(2000)
         .send_string(&json);
 
-    if resp.error() {
-        Err(format!(
+    if resp.synthetic() {
+        Ok(PostResult::Unreachable(format!(
+            "Could not reach {}. {}",
+            endpoint,
+            resp.into_string()?
+        )))
+    } else if resp.status() == 408 || resp.status() == 429 || resp.server_error() {
+        Ok(PostResult::Unreachable(format!(
+            "Could not apply commit to {} right now. Status: {} Body: {}",
+            endpoint,
+            resp.status(),
+            resp.into_string()?
+        )))
+    } else if resp.error() {
+        Ok(PostResult::Rejected(format!(
             "Failed applying commit to {}. Status: {} Body: {}",
             endpoint,
             resp.status(),
             resp.into_string()?
-        )
-        .into())
+        )))
     } else {
+        Ok(PostResult::Accepted)
+    }
+}
+
+/// A WebSocket connection to an Atomic Server, which receives the Commits for subscribed subjects.
+/// Subscribing to a Drive or any other parent also includes its children.
+/// Subscribing to a Collection includes changes to its members.
+/// The server only sends Commits for Resources that the Agent can read.
+#[cfg(feature = "websockets")]
+pub struct Subscriber {
+    socket: tungstenite::WebSocket<tungstenite::client::AutoStream>,
+}
+
+#[cfg(feature = "websockets")]
+impl Subscriber {
+    /// Opens a WebSocket connection to the `/ws` endpoint of the server.
+    /// If an Agent is passed, the request is signed, so the server can check its read rights.
+    pub fn connect(server_url: &str, agent: Option<&crate::agents::Agent>) -> AtomicResult<Subscriber> {
+        let http_url = format!("{}/ws", server_url.trim_end_matches('/'));
+        let ws_url = http_url.replacen("http", "ws", 1);
+        let mut request = tungstenite::http::Request::builder().uri(&ws_url);
+        if let Some(agent) = agent {
+            for (key, value) in crate::authentication::get_authentication_headers(&http_url, agent)? {
+                request = request.header(key.as_str(), value.as_str());
+            }
+        }
+        let (socket, _response) = tungstenite::connect(request.body(())?)
+            .map_err(|e| format!("Could not connect to WebSocket {}: {}", ws_url, e))?;
+        Ok(Subscriber { socket })
+    }
+
+    /// Starts receiving Commits for this subject
+    pub fn subscribe(&mut self, subject: &str) -> AtomicResult<()> {
+        self.send(format!("SUBSCRIBE {}", subject))
+    }
+
+    /// Stops receiving Commits for this subject
+    pub fn unsubscribe(&mut self, subject: &str) -> AtomicResult<()> {
+        self.send(format!("UNSUBSCRIBE {}", subject))
+    }
+
+    fn send(&mut self, message: String) -> AtomicResult<()> {
+        self.socket
+            .write_message(tungstenite::Message::Text(message))
+            .map_err(|e| format!("Could not send WebSocket message: {}", e))?;
         Ok(())
     }
+
+    /// Blocks until the next Commit is received.
+    /// Returns an error if the server sends an error or closes the connection.
+    pub fn next_commit(&mut self, store: &impl Storelike) -> AtomicResult<crate::Commit> {
+        loop {
+            let message = self
+                .socket
+                .read_message()
+                .map_err(|e| format!("Could not read WebSocket message: {}", e))?;
+            match message {
+                tungstenite::Message::Text(text) => return parse_commit_message(&text, store),
+                tungstenite::Message::Close(_) => return Err("WebSocket connection was closed".into()),
+                // Pings are answered by tungstenite
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// Parses a `COMMIT {json-ad}` message that the server sends to subscribers
+#[cfg(feature = "websockets")]
+fn parse_commit_message(text: &str, store: &impl Storelike) -> AtomicResult<crate::Commit> {
+    if let Some(json) = text.strip_prefix("COMMIT ") {
+        let resource = crate::parse::parse_json_ad_commit_resource(json, store)?;
+        crate::Commit::from_resource(resource)
+    } else if let Some(error) = text.strip_prefix("ERROR ") {
+        Err(format!("WebSocket error from server: {}", error).into())
+    } else {
+        Err(format!("Unknown WebSocket message: {}", text).into())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn included_from_same_origin() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let sub = |subject: &str, name: &str| {
+            let mut propvals = crate::resources::PropVals::new();
+            propvals.insert(crate::urls::NAME.into(), crate::Value::String(name.into()));
+            crate::values::SubResource {
+                subject: subject.into(),
+                propvals,
+            }
+        };
+        let mut invalid = sub("https://example.com/invalid", "invalid");
+        invalid.propvals.insert(
+            crate::urls::IS_A.into(),
+            crate::Value::ResourceArray(vec![crate::urls::AGENT.into()]),
+        );
+        let mut resource = Resource::new("https://example.com/collection".into());
+        resource
+            .set_propval_unsafe(
+                crate::urls::COLLECTION_INCLUDED.into(),
+                crate::Value::ResourceArrayNested(vec![
+                    sub("https://example.com/member", "member"),
+                    sub("https://other.example.com/member", "other"),
+                    invalid,
+                ]),
+            )
+            .unwrap();
+        let included = included_resources(&resource, &store);
+        // Other servers and Agents without a public key are skipped
+        assert_eq!(included.len(), 1);
+        assert_eq!(included[0].get_subject(), "https://example.com/member");
+    }
+
     #[test]
     #[ignore]
     fn fetch_resource_basic() {
@@ -0,0 +1,333 @@
+//! Generates Rust structs from Atomic Classes, so your Rust types stay in sync with your data models.
+//! Use it from a `build.rs` script, or run `atomic-cli codegen`.
+//!
+//! Every struct has a `subject` field and a field for each Property of the Class (including inherited ones),
+//! named after the Property's shortname.
+//! Required Properties become plain fields, recommended ones become `Option`s.
+//! The structs implement `TryFrom<Resource>`, and have an `into_resource` method.
+//!
+//! ```no_run
+//! // build.rs
+//! let json_ad = std::fs::read_to_string("schema.json").unwrap();
+//! let store = atomic_lib::Store::init().unwrap();
+//! let code = atomic_lib::codegen::json_ad_to_rust(&json_ad, &store).unwrap();
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! std::fs::write(format!("{}/atomic_models.rs", out_dir), code).unwrap();
+//! // And in your crate: include!(concat!(env!("OUT_DIR"), "/atomic_models.rs"));
+//! ```
+
+use std::collections::HashSet;
+
+use crate::{
+    datatype::DataType,
+    errors::AtomicResult,
+    parse::parse_json_ad_array,
+    schema::{Class, Property},
+    urls, Storelike,
+};
+
+/// Keywords and reserved words of all Rust editions, which can't be used as identifiers.
+/// Field names that are one of these get a `_` suffix, struct names a `Class` suffix.
+const RUST_KEYWORDS: &[&str] = &[
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Types that the generated code uses, so the structs can't have these names
+const USED_TYPES: &[&str] = &["Box", "Err", "None", "Ok", "Option", "Result", "Some", "String", "Vec"];
+
+/// Generates the Rust code for the Classes with these subjects
+pub fn classes_to_rust(class_subjects: &[String], store: &impl Storelike) -> AtomicResult<String> {
+    let mut code = String::from(
+        "// Generated from Atomic Classes by atomic_lib::codegen. Do not edit by hand.\n",
+    );
+    let mut struct_names = HashSet::new();
+    for subject in class_subjects {
+        let class = store.get_class(subject)?;
+        let struct_name = unique_name(to_type_name(&class.shortname), &mut struct_names);
+        code.push('\n');
+        code.push_str(&struct_to_rust(&class, &struct_name, store)?);
+    }
+    Ok(code)
+}
+
+/// Imports a JSON-AD string into the store, and generates the Rust code for every Class in it
+pub fn json_ad_to_rust(json_ad: &str, store: &impl Storelike) -> AtomicResult<String> {
+    let resources = parse_json_ad_array(json_ad, store)
+        .map_err(|e| format!("Unable to parse JSON-AD string: {}", e))?;
+    let mut class_subjects = Vec::new();
+    for resource in resources {
+        let is_class = match resource.get(urls::IS_A) {
+            Ok(classes) => classes.to_subjects()?.iter().any(|c| c == urls::CLASS),
+            Err(_) => false,
+        };
+        if is_class {
+            class_subjects.push(resource.get_subject().clone());
+        }
+        store.add_resource(&resource)?;
+    }
+    classes_to_rust(&class_subjects, store)
+}
+
+/// Generates a struct with its conversions for a single Class
+pub fn class_to_rust(class: &Class, store: &impl Storelike) -> AtomicResult<String> {
+    struct_to_rust(class, &to_type_name(&class.shortname), store)
+}
+
+fn struct_to_rust(class: &Class, struct_name: &str, store: &impl Storelike) -> AtomicResult<String> {
+    let mut fields = Vec::new();
+    // Properties with the same shortname get a number, and can't use the name of the `subject` field
+    let mut field_names: HashSet<String> = HashSet::new();
+    field_names.insert("subject".into());
+    for (prop_subject, required) in class
+        .requires
+        .iter()
+        .map(|p| (p, true))
+        .chain(class.recommends.iter().map(|p| (p, false)))
+    {
+        if fields.iter().any(|f: &Field| &f.property.subject == prop_subject) {
+            continue;
+        }
+        let property = store.get_property(prop_subject)?;
+        fields.push(Field {
+            name: unique_name(to_field_name(&property.shortname), &mut field_names),
+            property,
+            required,
+        });
+    }
+
+    let mut code = String::new();
+    for line in class.description.lines() {
+        code.push_str(format!("/// {}", line.trim()).trim_end());
+        code.push('\n');
+    }
+    code.push_str("#[derive(Clone, Debug, PartialEq)]\n");
+    code.push_str(&format!("pub struct {} {{\n", struct_name));
+    code.push_str("    pub subject: String,\n");
+    for field in &fields {
+        if !field.property.description.is_empty() {
+            let first_line = field.property.description.lines().next().unwrap_or_default();
+            code.push_str(&format!("    /// {}\n", first_line.trim()));
+        }
+        let rust_type = rust_type(&field.property.data_type);
+        if field.required {
+            code.push_str(&format!("    pub {}: {},\n", field.name, rust_type));
+        } else {
+            code.push_str(&format!("    pub {}: Option<{}>,\n", field.name, rust_type));
+        }
+    }
+    code.push_str("}\n\n");
+
+    code.push_str(&format!("impl {} {{\n", struct_name));
+    code.push_str(&format!(
+        "    /// URL of the Class\n    pub const CLASS: &'static str = {:?};\n\n",
+        class.subject
+    ));
+    code.push_str("    /// Converts the struct to a Resource that is an instance of the Class\n");
+    code.push_str("    pub fn into_resource(self) -> atomic_lib::Resource {\n");
+    code.push_str("        let mut propvals = atomic_lib::resources::PropVals::new();\n");
+    code.push_str(&format!(
+        "        propvals.insert({:?}.into(), atomic_lib::Value::ResourceArray(vec![Self::CLASS.into()]));\n",
+        urls::IS_A
+    ));
+    for field in &fields {
+        let value = to_value(&field.property.data_type, "val");
+        if field.required {
+            code.push_str(&format!("        let val = self.{};\n", field.name));
+            code.push_str(&format!(
+                "        propvals.insert({:?}.into(), {});\n",
+                field.property.subject, value
+            ));
+        } else {
+            code.push_str(&format!("        if let Some(val) = self.{} {{\n", field.name));
+            code.push_str(&format!(
+                "            propvals.insert({:?}.into(), {});\n",
+                field.property.subject, value
+            ));
+            code.push_str("        }\n");
+        }
+    }
+    code.push_str("        atomic_lib::Resource::from_propvals(propvals, self.subject)\n");
+    code.push_str("    }\n");
+    code.push_str("}\n\n");
+
+    code.push_str(&format!(
+        "impl std::convert::TryFrom<atomic_lib::Resource> for {} {{\n",
+        struct_name
+    ));
+    code.push_str("    type Error = Box<dyn std::error::Error>;\n\n");
+    code.push_str(
+        "    fn try_from(resource: atomic_lib::Resource) -> Result<Self, Self::Error> {\n",
+    );
+    code.push_str(&format!("        Ok({} {{\n", struct_name));
+    code.push_str("            subject: resource.get_subject().clone(),\n");
+    for field in &fields {
+        let from_value = from_value(&field.property.data_type, "val");
+        if field.required {
+            code.push_str(&format!(
+                "            {}: {{\n                let val = resource.get({:?})?;\n                {}\n            }},\n",
+                field.name, field.property.subject, from_value
+            ));
+        } else {
+            code.push_str(&format!(
+                "            {}: match resource.get({:?}) {{\n                Ok(val) => Some({}),\n                Err(_) => None,\n            }},\n",
+                field.name, field.property.subject, from_value
+            ));
+        }
+    }
+    code.push_str("        })\n");
+    code.push_str("    }\n");
+    code.push_str("}\n");
+    Ok(code)
+}
+
+struct Field {
+    name: String,
+    property: Property,
+    required: bool,
+}
+
+/// The Rust type that holds the Values of a DataType
+pub fn rust_type(datatype: &DataType) -> &'static str {
+    match datatype {
+        DataType::Boolean => "bool",
+        DataType::Integer | DataType::Timestamp => "i64",
+        DataType::Float => "f64",
+        DataType::ResourceArray => "Vec<String>",
+        DataType::AtomicUrl
+        | DataType::Date
+        | DataType::Markdown
+        | DataType::Slug
+        | DataType::String
+        | DataType::Unsupported(_) => "String",
+    }
+}
+
+/// Expression that converts the `&Value` in `var` to the Rust type, may use `?`
+fn from_value(datatype: &DataType, var: &str) -> String {
+    match datatype {
+        DataType::Boolean => format!("{}.to_bool()?", var),
+        DataType::Integer | DataType::Timestamp => format!("{}.to_int()?", var),
+        DataType::Float => format!("{}.to_float()?", var),
+        DataType::ResourceArray => format!("{}.to_subjects()?", var),
+        _ => format!("{}.to_string()", var),
+    }
+}
+
+/// Expression that converts the Rust value in `var` to a `Value`
+fn to_value(datatype: &DataType, var: &str) -> String {
+    match datatype {
+        DataType::AtomicUrl => format!("atomic_lib::Value::AtomicUrl({})", var),
+        DataType::Boolean => format!("atomic_lib::Value::Boolean({})", var),
+        DataType::Date => format!("atomic_lib::Value::Date({})", var),
+        DataType::Integer => format!("atomic_lib::Value::Integer({})", var),
+        DataType::Float => format!("atomic_lib::Value::Float({})", var),
+        DataType::Markdown => format!("atomic_lib::Value::Markdown({})", var),
+        DataType::ResourceArray => format!("atomic_lib::Value::ResourceArray({})", var),
+        DataType::Slug => format!("atomic_lib::Value::Slug({})", var),
+        DataType::String => format!("atomic_lib::Value::String({})", var),
+        DataType::Timestamp => format!("atomic_lib::Value::Timestamp({})", var),
+        DataType::Unsupported(url) => format!(
+            "atomic_lib::Value::Unsupported(atomic_lib::values::UnsupportedValue {{ value: {}, datatype: {:?}.into() }})",
+            var, url
+        ),
+    }
+}
+
+/// Converts a shortname like `my-class` to `MyClass`
+fn to_type_name(shortname: &str) -> String {
+    let name: String = shortname
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "Class".into()
+    } else if USED_TYPES.contains(&name.as_str()) || RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("{}Class", name)
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Converts a shortname like `public-key` to `public_key`
+fn to_field_name(shortname: &str) -> String {
+    let name: String = shortname
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.chars().all(|c| c == '_') {
+        format!("field{}", name)
+    } else if RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Adds a number to the name if it has already been used
+fn unique_name(name: String, used: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut number = 2;
+    while !used.insert(unique.clone()) {
+        unique = format!("{}{}", name, number);
+        number += 1;
+    }
+    unique
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Store;
+
+    #[test]
+    fn generate_struct() {
+        let store = Store::init().unwrap();
+        store.populate().unwrap();
+        let code = classes_to_rust(&[urls::AGENT.into()], &store).unwrap();
+        assert!(code.contains("pub struct Agent {"));
+        assert!(code.contains("    pub public_key: String,\n"));
+        assert!(code.contains("    pub name: Option<String>,\n"));
+        assert!(code.contains("impl std::convert::TryFrom<atomic_lib::Resource> for Agent {"));
+        assert!(code.contains("pub fn into_resource(self) -> atomic_lib::Resource {"));
+
+        let json_ad = r#"[{
+            "@id": "https://example.com/properties/type",
+            "https://atomicdata.dev/properties/isA": ["https://atomicdata.dev/classes/Property"],
+            "https://atomicdata.dev/properties/shortname": "type",
+            "https://atomicdata.dev/properties/description": "The kind of thing",
+            "https://atomicdata.dev/properties/datatype": "https://atomicdata.dev/datatypes/integer"
+        },{
+            "@id": "https://example.com/classes/ThingWithType",
+            "https://atomicdata.dev/properties/isA": ["https://atomicdata.dev/classes/Class"],
+            "https://atomicdata.dev/properties/shortname": "thing-with-type",
+            "https://atomicdata.dev/properties/description": "Has a type",
+            "https://atomicdata.dev/properties/requires": ["https://example.com/properties/type"]
+        }]"#;
+        let code = json_ad_to_rust(json_ad, &store).unwrap();
+        assert!(code.contains("pub struct ThingWithType {"));
+        assert!(code.contains("    /// The kind of thing\n    pub type_: i64,\n"));
+        assert!(code.contains("let val = resource.get(\"https://example.com/properties/type\")?;"));
+        assert!(!code.contains("pub struct Agent"));
+    }
+}
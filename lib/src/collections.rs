@@ -1,6 +1,19 @@
 //! Collections are dynamic resources that refer to multiple resources.
 //! They are constructed using a TPF query
-use crate::{Resource, Storelike, errors::AtomicResult, storelike::ResourceCollection, urls};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    comparison::{Comparison, Operator},
+    errors::AtomicResult,
+    schema::get_class_and_subclasses,
+    storelike::ResourceCollection,
+    urls, Resource, Storelike, Value,
+};
 
 #[derive(Debug)]
 pub struct TPFQuery {
@@ -11,6 +24,141 @@ pub struct TPFQuery {
 
 const DEFAULT_PAGE_SIZE: usize = 30;
 
+/// A condition that the members of a Collection must meet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Filter {
+    /// URL of the Property that the members must have
+    pub property: String,
+    /// The Value that the Property must have. For ResourceArrays, it must be one of the items.
+    /// If None, the Property only has to be present.
+    pub value: Option<String>,
+    /// How the Value is compared, e.g. greater than or starts with. Defaults to `Equals`.
+    pub operator: Operator,
+    /// The upper bound of a `Between` operator
+    pub value_end: Option<String>,
+}
+
+/// A Property to sort the members of a Collection by
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SortKey {
+    /// URL of the Property
+    pub property: String,
+    /// Sorts ascending by default
+    pub desc: bool,
+}
+
+impl SortKey {
+    /// Parses a `sort_by` query parameter: a Property URL, prefixed with `-` for descending order.
+    pub fn from_query_param(param: &str) -> SortKey {
+        match param.strip_prefix('-') {
+            Some(property) => SortKey {
+                property: property.into(),
+                desc: true,
+            },
+            None => SortKey {
+                property: param.into(),
+                desc: false,
+            },
+        }
+    }
+}
+
+impl Filter {
+    /// Parses a `filter` query parameter: a Property URL, optionally followed by a space and a Value.
+    /// E.g. `https://atomicdata.dev/properties/isA https://atomicdata.dev/classes/Class`
+    /// Other operators are put between tildes, e.g. `{property}~gt~10`, `{property}~between~10~20` or `{property}~prefix~abc`.
+    /// Values are never read as operators, so `{property} gt 10` matches the text `gt 10`.
+    pub fn from_query_param(param: &str) -> Filter {
+        if let Some(filter) = Filter::from_operator_param(param) {
+            return filter;
+        }
+        let mut parts = param.splitn(2, ' ');
+        let property: String = parts.next().unwrap_or_default().into();
+        let value = parts.next().map(|v| v.to_string());
+        Filter {
+            property,
+            value,
+            operator: Operator::Equals,
+            value_end: None,
+        }
+    }
+
+    /// Parses `{property}~{operator}~{value}`, or `{property}~between~{value}~{value_end}`.
+    /// Returns None if the param has no known operator between tildes.
+    fn from_operator_param(param: &str) -> Option<Filter> {
+        for (index, _) in param.match_indices('~') {
+            let property = &param[..index];
+            // Property URLs can't contain spaces, so this is a Value of an equality filter
+            if property.contains(' ') {
+                return None;
+            }
+            let (operator, operand) = param[index + 1..].split_once('~')?;
+            let operator = match Operator::from_string(operator) {
+                Ok(operator) => operator,
+                Err(_) => continue,
+            };
+            let (value, value_end) = match operand.split_once('~') {
+                Some((value, value_end)) if operator == Operator::Between => {
+                    (value.to_string(), Some(value_end.to_string()))
+                }
+                _ => (operand.to_string(), None),
+            };
+            return Some(Filter {
+                property: property.into(),
+                value: Some(value),
+                operator,
+                value_end,
+            });
+        }
+        None
+    }
+
+    /// Checks if a single Resource meets the Filter, in the same way as `filter_subjects` finds them.
+    pub fn matches(&self, resource: &Resource, store: &impl Storelike) -> AtomicResult<bool> {
+        let found = match resource.get(&self.property) {
+            Ok(found) => found,
+            Err(_) => return Ok(false),
+        };
+        match (&self.operator, &self.value) {
+            (_, None) => Ok(true),
+            (Operator::Equals, Some(class)) if self.property == urls::IS_A => {
+                let classes = get_class_and_subclasses(store, class)?;
+                Ok(found.to_vec()?.iter().any(|c| classes.contains(c)))
+            }
+            (Operator::Equals, Some(value)) => Ok(value_equals(found, value)),
+            (operator, Some(value)) => {
+                let datatype = store.get_property(&self.property)?.data_type;
+                let comparison = Comparison::new(
+                    operator.clone(),
+                    value,
+                    self.value_end.as_deref(),
+                    &datatype,
+                )?;
+                Ok(comparison.matches(found))
+            }
+        }
+    }
+
+    /// Converts a Resource of the Filter class into a Filter.
+    pub fn from_resource(resource: &Resource) -> AtomicResult<Filter> {
+        Ok(Filter {
+            property: resource.get(urls::COLLECTION_PROPERTY)?.to_string(),
+            value: resource
+                .get(urls::COLLECTION_VALUE)
+                .ok()
+                .map(|v| v.to_string()),
+            operator: match resource.get(urls::COLLECTION_OPERATOR) {
+                Ok(operator) => Operator::from_string(&operator.to_string())?,
+                Err(_) => Operator::Equals,
+            },
+            value_end: resource
+                .get(urls::COLLECTION_VALUE_END)
+                .ok()
+                .map(|v| v.to_string()),
+        })
+    }
+}
+
 /// Used to construct a Collection. Does not contain results / members.
 /// Has to be constructed using `Collection::new()` or `storelike.new_collection()`.
 #[derive(Debug)]
@@ -21,10 +169,29 @@ pub struct CollectionBuilder {
     pub property: Option<String>,
     /// The TPF value which the results are to be filtered by
     pub value: Option<String>,
+    /// Additional filters. Members must meet these, as well as the property and value.
+    pub filters: Vec<Filter>,
+    /// Members only have to meet one of the additional `filters`, instead of all of them. The property and value always apply.
+    pub match_any: bool,
     /// URL of the value to sort by
     pub sort_by: Option<String>,
     /// Sorts ascending by default
     pub sort_desc: bool,
+    /// Properties to sort by when members have the same value for `sort_by`, in order of importance
+    pub sort_then: Vec<SortKey>,
+    /// Members without a value for a sort property come first. They come last by default.
+    pub nulls_first: bool,
+    /// Include the member Resources in the response, instead of only their subjects
+    pub include: bool,
+    /// Also include the Resources that the members refer to, one level deep
+    pub include_nested: bool,
+    /// Store the sorted members, and update them when Resources change, instead of running the query on every request.
+    /// Only used by stores that support it (see `Storelike::get_materialized`).
+    pub materialized: bool,
+    /// Cursor of the member after which the page starts. Overrides `current_page`.
+    pub after: Option<String>,
+    /// Cursor of the member before which the page ends. Overrides `current_page`.
+    pub before: Option<String>,
     /// Current page number, defaults to 0 (first page)
     pub current_page: usize,
     /// How many items per page
@@ -55,6 +222,21 @@ impl CollectionBuilder {
         if self.sort_desc {
             resource.set_propval_string(crate::urls::COLLECTION_SORT_DESC.into(), "true", store)?;
         }
+        if self.nulls_first {
+            resource.set_propval(crate::urls::COLLECTION_NULLS_FIRST.into(), true.into(), store)?;
+        }
+        if self.include {
+            resource.set_propval(crate::urls::COLLECTION_INCLUDE.into(), true.into(), store)?;
+        }
+        if self.include_nested {
+            resource.set_propval(crate::urls::COLLECTION_INCLUDE_NESTED.into(), true.into(), store)?;
+        }
+        if self.match_any {
+            resource.set_propval(crate::urls::COLLECTION_MATCH_ANY.into(), true.into(), store)?;
+        }
+        if self.materialized {
+            resource.set_propval(crate::urls::COLLECTION_MATERIALIZED.into(), true.into(), store)?;
+        }
         resource.set_propval_string(
             crate::urls::COLLECTION_CURRENT_PAGE.into(),
             &self.current_page.to_string(),
@@ -75,14 +257,71 @@ impl CollectionBuilder {
             subject: format!("{}/{}", store.get_base_url(), path),
             property: Some(urls::IS_A.into()),
             value: Some(class_url.into()),
+            filters: Vec::new(),
+            match_any: false,
             sort_by: None,
             sort_desc: false,
+            sort_then: Vec::new(),
+            nulls_first: false,
+            include: false,
+            include_nested: false,
+            materialized: false,
+            after: None,
+            before: None,
             page_size: DEFAULT_PAGE_SIZE,
             current_page: 0,
             name: Some(format!("{} collection", path)),
         }
     }
 
+    /// Describes everything that determines which members the Collection has, and in what order.
+    /// Materialized Collections are rebuilt when this changes.
+    pub fn definition(&self) -> String {
+        format!(
+            "{:?}",
+            (
+                &self.property,
+                &self.value,
+                &self.filters,
+                self.match_any,
+                &self.sort_by,
+                self.sort_desc,
+                &self.sort_then,
+                self.nulls_first,
+            )
+        )
+    }
+
+    /// The query of the Collection: which members it has, and in what order. Does not contain any members yet.
+    /// Use `Materialized::matches` to check if a single Resource is a member.
+    pub fn query(&self) -> Materialized {
+        // Members always have to meet the property and value, `match_any` only applies to the other filters
+        let base = self.property.as_ref().map(|property| Filter {
+            property: property.clone(),
+            value: self.value.clone(),
+            operator: Operator::Equals,
+            value_end: None,
+        });
+        let mut keys = Vec::new();
+        if let Some(sort_by) = &self.sort_by {
+            keys.push(SortKey {
+                property: sort_by.clone(),
+                desc: self.sort_desc,
+            });
+            keys.extend(self.sort_then.iter().cloned());
+        }
+        Materialized {
+            definition: self.definition(),
+            base,
+            filters: self.filters.clone(),
+            value: self.value.clone(),
+            match_any: self.match_any,
+            keys,
+            nulls_first: self.nulls_first,
+            positions: Vec::new(),
+        }
+    }
+
     /// Converts the CollectionBuilder into a collection, with Members
     pub fn into_collection(self, store: &impl Storelike) -> AtomicResult<Collection> {
         Collection::new_with_members(store, self)
@@ -100,12 +339,30 @@ pub struct Collection {
     pub property: Option<String>,
     /// The TPF value which the results are to be filtered by
     pub value: Option<String>,
+    /// Additional filters. Members must meet these, as well as the property and value.
+    pub filters: Vec<Filter>,
+    /// Members only have to meet one of the additional `filters`, instead of all of them. The property and value always apply.
+    pub match_any: bool,
     /// The actual items that you're interested in. List the member subjects of the current page.
     pub members: Vec<String>,
     /// URL of the value to sort by
     pub sort_by: Option<String>,
     // Sorts ascending by default
     pub sort_desc: bool,
+    /// Properties to sort by when members have the same value for `sort_by`, in order of importance
+    pub sort_then: Vec<SortKey>,
+    /// Members without a value for a sort property come first. They come last by default.
+    pub nulls_first: bool,
+    /// Include the member Resources in the response, instead of only their subjects
+    pub include: bool,
+    /// Also include the Resources that the members refer to, one level deep
+    pub include_nested: bool,
+    /// The Resources that are included in the response. Only set if `include` is true.
+    pub included: Option<Vec<Resource>>,
+    /// Cursor for the next page, if there is one. Use it as the `after` query parameter.
+    pub next: Option<String>,
+    /// Cursor for the previous page, if there is one. Use it as the `before` query parameter.
+    pub previous: Option<String>,
     /// How many items per page
     pub page_size: usize,
     /// Current page number, defaults to 0 (first page)
@@ -118,36 +375,102 @@ pub struct Collection {
     pub name: Option<String>,
 }
 
-/// Sorts a vector or resources by some property.
-fn sort_resources(mut resources: ResourceCollection, sort_by: &str, sort_desc: bool) -> ResourceCollection {
-    resources.sort_by(
-        |a, b|
-        {
-            let val_a = a.get(sort_by);
-            let val_b = b.get(sort_by);
-            if val_a.is_err() || val_b.is_err() {
-                return std::cmp::Ordering::Equal
-            }
-            if val_b.unwrap().to_string() > val_a.unwrap().to_string() {
-                if sort_desc {
-                    std::cmp::Ordering::Greater
-                } else {
-                    std::cmp::Ordering::Less
-                }
-            } else if sort_desc {
-                std::cmp::Ordering::Less
-            } else {
-                std::cmp::Ordering::Greater
+/// Compares the values of two Resources for a sort key, by their DataType (see `Value::compare`).
+/// Missing values come last, or first if `nulls_first` is true, regardless of the sort direction.
+fn compare_values(a: Option<&Value>, b: Option<&Value>, key: &SortKey, nulls_first: bool) -> Ordering {
+    match (a, b) {
+        (Some(val_a), Some(val_b)) if key.desc => val_b.compare(val_a),
+        (Some(val_a), Some(val_b)) => val_a.compare(val_b),
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) if nulls_first => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) if nulls_first => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+    }
+}
+
+/// Sorts Resources by the values of the sort keys, compared by their DataType (see `Value::compare`).
+/// Resources without a value come last, or first if `nulls_first` is true, regardless of the sort direction.
+/// Resources with the same values are sorted by their subject, so the order is stable between requests.
+fn sort_resources(mut resources: ResourceCollection, keys: &[SortKey], nulls_first: bool) -> ResourceCollection {
+    resources.sort_by(|a, b| {
+        for key in keys {
+            let ordering = compare_values(a.get(&key.property).ok(), b.get(&key.property).ok(), key, nulls_first);
+            if ordering != Ordering::Equal {
+                return ordering;
             }
         }
-    );
+        a.get_subject().cmp(b.get_subject())
+    });
     resources
 }
 
+/// The place of a member in a sorted Collection: its values for the sort keys, followed by its subject.
+/// Cursors encode a Position, so pages start right after (or before) a member, even if members are added or removed in the meantime.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Position {
+    values: Vec<Option<Value>>,
+    subject: String,
+}
+
+impl Position {
+    fn from_resource(resource: &Resource, keys: &[SortKey]) -> Position {
+        Position {
+            values: keys
+                .iter()
+                .map(|key| resource.get(&key.property).ok().cloned())
+                .collect(),
+            subject: resource.get_subject().clone(),
+        }
+    }
+
+    /// Decodes an opaque cursor, which is a base64 encoded JSON array of the sort values and the subject.
+    fn from_cursor(cursor: &str, keys: &[SortKey], store: &impl Storelike) -> AtomicResult<Position> {
+        let bytes = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .map_err(|e| format!("Invalid cursor {}. {}", cursor, e))?;
+        let mut items: Vec<Option<String>> = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid cursor {}. {}", cursor, e))?;
+        if items.len() != keys.len() + 1 {
+            return Err(format!("Cursor {} does not match the sort order of this Collection", cursor).into());
+        }
+        let subject = items
+            .pop()
+            .flatten()
+            .ok_or(format!("Cursor {} does not contain a subject", cursor))?;
+        let mut values = Vec::new();
+        for (key, item) in keys.iter().zip(items) {
+            values.push(match item {
+                Some(string) => Some(Value::new(&string, &store.get_property(&key.property)?.data_type)?),
+                None => None,
+            });
+        }
+        Ok(Position { values, subject })
+    }
+
+    fn to_cursor(&self) -> AtomicResult<String> {
+        let mut items: Vec<Option<String>> = self.values.iter().map(|v| v.as_ref().map(|v| v.to_string())).collect();
+        items.push(Some(self.subject.clone()));
+        Ok(base64::encode_config(serde_json::to_string(&items)?, base64::URL_SAFE_NO_PAD))
+    }
+
+    /// Compares in the same order as `sort_resources`
+    fn compare(&self, other: &Position, keys: &[SortKey], nulls_first: bool) -> Ordering {
+        for (i, key) in keys.iter().enumerate() {
+            let ordering = compare_values(self.values[i].as_ref(), other.values[i].as_ref(), key, nulls_first);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        self.subject.cmp(&other.subject)
+    }
+}
+
 impl Collection {
     /// Constructs a Collection, which is a paginated list of items with some sorting applied.
     /// Gets the required data from the store.
     /// Applies sorting settings.
+    /// Pages start at the `after` or `before` cursor if one is set, or else at the `current_page`.
+    /// Unless the Collection is materialized, every member is read to sort them, so use `materialized` for big sorted Collections.
     pub fn new_with_members(
         store: &impl Storelike,
         collection_builder: crate::collections::CollectionBuilder,
@@ -155,74 +478,133 @@ impl Collection {
         if collection_builder.page_size < 1 {
             return Err("Page size must be greater than 0".into());
         }
-        // Execute the TPF query, get all the subjects.
-        let atoms = store.tpf(
-            None,
-            collection_builder.property.as_deref(),
-            collection_builder.value.as_deref(),
-            // Collections only show items from inside this store. Maybe later add this as an option to collections
-            false
-        )?;
-        let mut subjects: Vec<String> = atoms.iter().map(|atom| atom.subject.clone()).collect();
-        // Default to no sorting
-        if collection_builder.sort_by.is_some() {
-            let mut resources = Vec::new();
-            for subject in subjects.clone() {
-                resources.push(store.get_resource(&subject)?)
-            };
-            // TODO: Include these resources in the response! They're already fetched. Should speed things up.
-            // https://github.com/joepio/atomic/issues/62
-            resources = sort_resources(resources, &collection_builder.sort_by.clone().unwrap(), collection_builder.sort_desc);
-            subjects.clear();
-            for resource in resources {
-                subjects.push(resource.get_subject().clone())
-            }
-        }
-        let mut all_pages: Vec<Vec<String>> = Vec::new();
-        let mut page: Vec<String> = Vec::new();
-        let current_page = collection_builder.current_page;
-        for (i, subject) in subjects.iter().enumerate() {
-            page.push(subject.into());
-            if page.len() >= collection_builder.page_size {
-                all_pages.push(page);
-                page = Vec::new();
-                // No need to calculte more than necessary
-                if all_pages.len() > current_page {
-                    break;
+        let mut query = collection_builder.query();
+        let keys = query.keys.clone();
+        // Resources that have been fetched for sorting, which can be re-used when including them
+        let mut fetched: HashMap<String, Resource> = HashMap::new();
+        // Materialized members are stored by the subject of the Collection, without query parameters
+        let materialized_subject = collection_builder
+            .subject
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let stored = if collection_builder.materialized {
+            store
+                .get_materialized(&materialized_subject)?
+                .filter(|materialized| materialized.definition == query.definition)
+        } else {
+            None
+        };
+        let positions = match stored {
+            Some(materialized) => materialized.positions,
+            None => {
+                query.positions = query_positions(store, &query, &mut fetched)?;
+                if collection_builder.materialized {
+                    store.set_materialized(&materialized_subject, &query)?;
                 }
+                query.positions
             }
-            // Add the last page when handling the last subject
-            if i == subjects.len() - 1 {
-                all_pages.push(page);
-                break;
-            }
+        };
+        let subjects: Vec<String> = positions.iter().map(|p| p.subject.clone()).collect();
+        let nulls_first = collection_builder.nulls_first;
+        let page_size = collection_builder.page_size;
+        // Keyset pagination: find the first member after the cursor, or the last page before it
+        let start = if let Some(cursor) = &collection_builder.after {
+            let after = Position::from_cursor(cursor, &keys, store)?;
+            positions.partition_point(|p| p.compare(&after, &keys, nulls_first) != Ordering::Greater)
+        } else if let Some(cursor) = &collection_builder.before {
+            let before = Position::from_cursor(cursor, &keys, store)?;
+            positions
+                .partition_point(|p| p.compare(&before, &keys, nulls_first) == Ordering::Less)
+                .saturating_sub(page_size)
+        } else {
+            page_start(collection_builder.current_page, page_size)?
+        };
+        if collection_builder.after.is_none()
+            && collection_builder.before.is_none()
+            && collection_builder.current_page > 0
+            && start >= subjects.len()
+        {
+            return Err("Page number is too high".into());
+        }
+        let end = subjects.len().min(start.saturating_add(page_size));
+        let mut collection = Collection::from_page(subjects, start, collection_builder);
+        if end < positions.len() {
+            collection.next = Some(positions[end - 1].to_cursor()?);
         }
-        if all_pages.is_empty() {
-            all_pages.push(Vec::new())
+        if start > 0 && start < positions.len() {
+            collection.previous = Some(positions[start].to_cursor()?);
         }
-        // Maybe I should default to last page, if current_page is too high?
-        let members = all_pages
-            .get(current_page)
-            .ok_or("Page number is too high")?
-            .clone();
+        if collection.include || collection.include_nested {
+            collection.included = Some(get_included(
+                store,
+                &collection.members,
+                fetched,
+                collection.include_nested,
+            )?);
+        }
+        Ok(collection)
+    }
+
+    /// Constructs a Collection from an already filtered and sorted list of subjects.
+    /// Only applies pagination using the `current_page`, cursors are not supported.
+    /// Useful for Endpoints that use their own index instead of a TPF query, such as `/audit`.
+    pub fn from_subjects(
+        subjects: Vec<String>,
+        collection_builder: crate::collections::CollectionBuilder,
+    ) -> AtomicResult<Collection> {
+        if collection_builder.page_size < 1 {
+            return Err("Page size must be greater than 0".into());
+        }
+        let start = page_start(collection_builder.current_page, collection_builder.page_size)?;
+        if collection_builder.current_page > 0 && start >= subjects.len() {
+            return Err("Page number is too high".into());
+        }
+        Ok(Collection::from_page(subjects, start, collection_builder))
+    }
+
+    /// Constructs a Collection with the page of subjects that starts at `start`.
+    fn from_page(
+        subjects: Vec<String>,
+        start: usize,
+        collection_builder: crate::collections::CollectionBuilder,
+    ) -> Collection {
+        let page_size = collection_builder.page_size;
         let total_items = subjects.len();
-        // Construct the pages (TODO), use pageSize
-        let total_pages =
-            (total_items + collection_builder.page_size - 1) / collection_builder.page_size;
-        let collection = Collection {
+        let members = subjects
+            .into_iter()
+            .skip(start)
+            .take(page_size)
+            .collect();
+        // Adding the page size overflows for huge page sizes, which are bigger than any store, so all items fit on one page
+        let total_pages = match total_items.checked_add(page_size - 1) {
+            Some(sum) => sum / page_size,
+            None => 1,
+        };
+        Collection {
             total_pages,
             members,
             total_items,
             subject: collection_builder.subject,
             property: collection_builder.property,
             value: collection_builder.value,
+            filters: collection_builder.filters,
+            match_any: collection_builder.match_any,
             sort_by: collection_builder.sort_by,
             sort_desc: collection_builder.sort_desc,
-            current_page: collection_builder.current_page,
-            page_size: collection_builder.page_size,
+            sort_then: collection_builder.sort_then,
+            nulls_first: collection_builder.nulls_first,
+            include: collection_builder.include,
+            include_nested: collection_builder.include_nested,
+            included: None,
+            next: None,
+            previous: None,
+            // Pages that start at a cursor don't have to line up with the page numbers
+            current_page: start / page_size,
+            page_size,
             name: collection_builder.name,
-        };
-        Ok(collection)
+        }
     }
 
     pub fn to_resource(&self, store: &impl Storelike) -> AtomicResult<crate::Resource> {
@@ -247,6 +629,9 @@ impl Collection {
         if let Some(val) = &self.name {
             resource.set_propval_string(crate::urls::NAME.into(), val, store)?;
         }
+        if self.match_any {
+            resource.set_propval(crate::urls::COLLECTION_MATCH_ANY.into(), true.into(), store)?;
+        }
         resource.set_propval(
             crate::urls::COLLECTION_MEMBER_COUNT.into(),
             self.total_items.clone().into(),
@@ -269,9 +654,319 @@ impl Collection {
             self.page_size.clone().into(),
             store,
         )?;
-        // Maybe include items directly
+        if let Some(cursor) = &self.next {
+            resource.set_propval(
+                crate::urls::COLLECTION_NEXT.into(),
+                Value::AtomicUrl(self.cursor_url("after", cursor)?),
+                store,
+            )?;
+        }
+        if let Some(cursor) = &self.previous {
+            resource.set_propval(
+                crate::urls::COLLECTION_PREVIOUS.into(),
+                Value::AtomicUrl(self.cursor_url("before", cursor)?),
+                store,
+            )?;
+        }
+        if let Some(included) = &self.included {
+            resource.set_propval(
+                crate::urls::COLLECTION_INCLUDED.into(),
+                included.clone().into(),
+                store,
+            )?;
+        }
         Ok(resource.to_owned())
     }
+
+    /// Returns the subject of the Collection with the cursor as query parameter, replacing any page number or other cursor.
+    fn cursor_url(&self, param: &str, cursor: &str) -> AtomicResult<String> {
+        let mut url = url::Url::parse(&self.subject)?;
+        let query: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(k, _v)| k != "current_page" && k != "after" && k != "before")
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(query)
+            .append_pair(param, cursor);
+        Ok(url.to_string())
+    }
+}
+
+/// Returns the subjects of the Resources that meet the `base` filter and all the other filters,
+/// or any of the other filters if `match_any` is true.
+/// Keeps the order of the results of the first filter.
+pub(crate) fn filter_subjects(
+    store: &impl Storelike,
+    base: Option<&Filter>,
+    filters: &[Filter],
+    match_any: bool,
+) -> AtomicResult<Vec<String>> {
+    let mut subjects: Option<Vec<String>> = None;
+    for filter in filters {
+        let found = filter_members(store, filter)?;
+        let combined = match subjects {
+            None => found,
+            Some(mut subjects) if match_any => {
+                let existing: HashSet<String> = subjects.iter().cloned().collect();
+                subjects.extend(found.into_iter().filter(|s| !existing.contains(s)));
+                subjects
+            }
+            Some(mut subjects) => {
+                let found: HashSet<String> = found.into_iter().collect();
+                subjects.retain(|s| found.contains(s));
+                subjects
+            }
+        };
+        let done = !match_any && combined.is_empty();
+        subjects = Some(combined);
+        if done {
+            break;
+        }
+    }
+    match (base, subjects) {
+        (Some(base), None) => filter_members(store, base),
+        (Some(base), Some(mut subjects)) => {
+            if !subjects.is_empty() {
+                let found: HashSet<String> = filter_members(store, base)?.into_iter().collect();
+                subjects.retain(|s| found.contains(s));
+            }
+            Ok(subjects)
+        }
+        (None, subjects) => Ok(subjects.unwrap_or_default()),
+    }
+}
+
+/// Returns the subjects of the Resources that meet a single Filter
+fn filter_members(store: &impl Storelike, filter: &Filter) -> AtomicResult<Vec<String>> {
+    // Collections only show items from inside this store
+    let found = match (&filter.operator, &filter.value) {
+        // Instances of subclasses are instances of the Class as well
+        (Operator::Equals, Some(class)) if filter.property == urls::IS_A => {
+            let mut found = Vec::new();
+            for class in get_class_and_subclasses(store, class)? {
+                found.extend(store.subjects_with_propval(urls::IS_A, Some(&class), false)?);
+            }
+            found.sort();
+            found.dedup();
+            found
+        }
+        (Operator::Equals, _) | (_, None) => {
+            store.subjects_with_propval(&filter.property, filter.value.as_deref(), false)?
+        }
+        (operator, Some(value)) => {
+            let datatype = store.get_property(&filter.property)?.data_type;
+            let comparison = Comparison::new(
+                operator.clone(),
+                value,
+                filter.value_end.as_deref(),
+                &datatype,
+            )?;
+            store
+                .tpf_compare(&filter.property, &comparison, false)?
+                .into_iter()
+                .map(|atom| atom.subject)
+                .collect()
+        }
+    };
+    Ok(found)
+}
+
+/// Runs the query of a Collection, and returns the Positions of its members in sorted order.
+/// The existing `positions` of the query are ignored.
+/// Adds the Resources that had to be fetched for sorting to `fetched`.
+fn query_positions(
+    store: &impl Storelike,
+    query: &Materialized,
+    fetched: &mut HashMap<String, Resource>,
+) -> AtomicResult<Vec<Position>> {
+    let keys = &query.keys;
+    let mut subjects: Vec<String> = if query.base.is_none() && query.filters.is_empty() {
+        // Execute the TPF query, get all the subjects.
+        let atoms = store.tpf(
+            None,
+            None,
+            query.value.as_deref(),
+            // Collections only show items from inside this store. Maybe later add this as an option to collections
+            false
+        )?;
+        atoms.iter().map(|atom| atom.subject.clone()).collect()
+    } else {
+        filter_subjects(store, query.base.as_ref(), &query.filters, query.match_any)?
+    };
+    if keys.is_empty() {
+        // Without sort keys, members are sorted by subject, so the pages are stable
+        subjects.sort();
+        subjects.dedup();
+        return Ok(subjects
+            .into_iter()
+            .map(|subject| Position {
+                values: Vec::new(),
+                subject,
+            })
+            .collect());
+    }
+    let mut resources = Vec::new();
+    for subject in subjects {
+        resources.push(store.get_resource(&subject)?)
+    };
+    resources = sort_resources(resources, keys, query.nulls_first);
+    let mut positions = Vec::new();
+    for resource in resources {
+        positions.push(Position::from_resource(&resource, keys));
+        fetched.insert(resource.get_subject().clone(), resource);
+    }
+    Ok(positions)
+}
+
+/// The sorted members of a materialized Collection, as stored using `Storelike::set_materialized`.
+/// Contains the query of the Collection, so the members can be updated when a single Resource changes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Materialized {
+    /// The `CollectionBuilder::definition` that the members were built for
+    pub definition: String,
+    /// The property and value of the Collection, which members always have to meet
+    base: Option<Filter>,
+    filters: Vec<Filter>,
+    /// Only used without filters, in which case members can have this Value for any Property
+    value: Option<String>,
+    match_any: bool,
+    keys: Vec<SortKey>,
+    nulls_first: bool,
+    positions: Vec<Position>,
+}
+
+impl Materialized {
+    /// The subjects of all members, in sorted order
+    pub fn subjects(&self) -> Vec<&str> {
+        self.positions.iter().map(|p| p.subject.as_str()).collect()
+    }
+
+    /// The Properties whose Values can change the members or their order.
+    /// None if the members can have any Property.
+    pub fn properties(&self) -> Option<Vec<String>> {
+        if self.base.is_none() && self.filters.is_empty() {
+            return None;
+        }
+        let mut properties: Vec<String> = self
+            .base
+            .iter()
+            .chain(self.filters.iter())
+            .map(|f| f.property.clone())
+            .chain(self.keys.iter().map(|k| k.property.clone()))
+            .collect();
+        properties.sort();
+        properties.dedup();
+        Some(properties)
+    }
+
+    /// Updates the members after a Resource has changed, or has been removed if `resource` is None.
+    /// Returns true if the members have changed.
+    pub fn update(
+        &mut self,
+        subject: &str,
+        resource: Option<&Resource>,
+        store: &impl Storelike,
+    ) -> AtomicResult<bool> {
+        let count = self.positions.len();
+        self.positions.retain(|p| p.subject != subject);
+        let mut changed = count != self.positions.len();
+        if let Some(resource) = resource {
+            if self.matches(resource, store)? {
+                let position = Position::from_resource(resource, &self.keys);
+                let index = self.positions.partition_point(|p| {
+                    p.compare(&position, &self.keys, self.nulls_first) == Ordering::Less
+                });
+                self.positions.insert(index, position);
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Checks if a Resource is a member, in the same way as `query_positions` finds them.
+    pub fn matches(&self, resource: &Resource, store: &impl Storelike) -> AtomicResult<bool> {
+        if self.base.is_none() && self.filters.is_empty() {
+            return Ok(match &self.value {
+                Some(value) => resource
+                    .get_propvals()
+                    .values()
+                    .any(|found| value_equals(found, value)),
+                None => !resource.get_propvals().is_empty(),
+            });
+        }
+        if let Some(base) = &self.base {
+            if !base.matches(resource, store)? {
+                return Ok(false);
+            }
+        }
+        if self.filters.is_empty() {
+            return Ok(true);
+        }
+        for filter in &self.filters {
+            if filter.matches(resource, store)? == self.match_any {
+                return Ok(self.match_any);
+            }
+        }
+        Ok(!self.match_any)
+    }
+}
+
+/// Returns the index of the first member of a page
+fn page_start(current_page: usize, page_size: usize) -> AtomicResult<usize> {
+    current_page
+        .checked_mul(page_size)
+        .ok_or_else(|| "Page number is too high".into())
+}
+
+/// Checks if a Value equals a query Value. For ResourceArrays, the query can also be one of the items.
+fn value_equals(found: &Value, value: &str) -> bool {
+    match found {
+        Value::ResourceArray(items) => items.iter().any(|item| item == value) || found.to_string() == value,
+        _ => found.to_string() == value,
+    }
+}
+
+/// Returns the member Resources of a page, and if `nested` is true, also the Resources that they refer to.
+/// Re-uses the Resources that have already been fetched for sorting.
+/// Only referenced Resources that are already in the store are included, so building a page never fetches other servers.
+fn get_included(
+    store: &impl Storelike,
+    members: &[String],
+    mut fetched: HashMap<String, Resource>,
+    nested: bool,
+) -> AtomicResult<Vec<Resource>> {
+    let mut included: Vec<Resource> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for subject in members {
+        let resource = match fetched.remove(subject) {
+            Some(resource) => resource,
+            None => store.get_resource(subject)?,
+        };
+        seen.insert(subject.clone());
+        included.push(resource);
+    }
+    if nested {
+        let mut references: Vec<String> = Vec::new();
+        for resource in included.iter() {
+            for value in resource.get_propvals().values() {
+                match value {
+                    Value::AtomicUrl(url) => references.push(url.clone()),
+                    Value::ResourceArray(urls) => references.extend(urls.iter().cloned()),
+                    _ => {}
+                }
+            }
+        }
+        for subject in references {
+            if seen.insert(subject.clone()) && store.has_resource(&subject) {
+                if let Ok(resource) = store.get_resource(&subject) {
+                    included.push(resource);
+                }
+            }
+        }
+    }
+    Ok(included)
 }
 
 /// Builds a collection from query params
@@ -280,13 +975,61 @@ pub fn construct_collection(
     query_params: url::form_urlencoded::Parse,
     resource: &mut Resource,
 ) -> AtomicResult<Resource> {
+    let collection_builder = collection_builder_from_resource(store, query_params, resource)?;
+    let collection = Collection::new_with_members(store, collection_builder)?;
+    collection.add_to_resource(resource, store)
+}
+
+/// Returns the query of a Collection, including the filters in the query parameters of the subject.
+/// Returns None if the subject is not a Collection in the store.
+/// Only reads Resources that are in the store, and does not calculate the members, so it is cheap to call.
+pub fn local_collection_query(
+    store: &impl Storelike,
+    subject: &str,
+) -> AtomicResult<Option<Materialized>> {
+    let url = url::Url::parse(subject)?;
+    let mut without_query = url.clone();
+    without_query.set_query(None);
+    let without_query = without_query.as_str().trim_end_matches('/');
+    if !store.has_resource(without_query) {
+        return Ok(None);
+    }
+    let resource = store.get_resource(without_query)?;
+    let is_collection = match resource.get(urls::IS_A) {
+        Ok(classes) => classes.to_vec()?.iter().any(|c| c == urls::COLLECTION),
+        Err(_) => false,
+    };
+    if !is_collection {
+        return Ok(None);
+    }
+    let collection_builder = collection_builder_from_resource(store, url.query_pairs(), &resource)?;
+    Ok(Some(collection_builder.query()))
+}
+
+/// Reads the query of a Collection from its Resource and the query params, without calculating the members.
+fn collection_builder_from_resource(
+    store: &impl Storelike,
+    query_params: url::form_urlencoded::Parse,
+    resource: &Resource,
+) -> AtomicResult<CollectionBuilder> {
     let mut sort_by = None;
     let mut sort_desc = false;
+    let mut query_sort_desc = None;
+    let mut sort_then = Vec::new();
+    let mut sort_keys: Vec<SortKey> = Vec::new();
+    let mut nulls_first = false;
     let mut current_page = 0;
     let mut page_size = DEFAULT_PAGE_SIZE;
     let mut value = None;
     let mut property = None;
     let mut name = None;
+    let mut filters = Vec::new();
+    let mut match_any = false;
+    let mut include = false;
+    let mut include_nested = false;
+    let mut after = None;
+    let mut before = None;
+    let mut materialized = false;
 
     if let Ok(val) = resource.get(urls::COLLECTION_PROPERTY) {
         property = Some(val.to_string());
@@ -297,30 +1040,85 @@ pub fn construct_collection(
     if let Ok(val) = resource.get(urls::NAME) {
         name = Some(val.to_string());
     }
+    if let Ok(val) = resource.get(urls::COLLECTION_FILTERS) {
+        for filter_subject in val.to_vec()? {
+            filters.push(Filter::from_resource(&store.get_resource(filter_subject)?)?);
+        }
+    }
+    if let Ok(val) = resource.get(urls::COLLECTION_MATCH_ANY) {
+        match_any = val.to_bool()?;
+    }
+    if let Ok(val) = resource.get(urls::COLLECTION_SORT_BY) {
+        sort_by = Some(val.to_string());
+    }
+    if let Ok(val) = resource.get(urls::COLLECTION_SORT_DESC) {
+        sort_desc = val.to_bool()?;
+    }
+    if let Ok(val) = resource.get(urls::COLLECTION_NULLS_FIRST) {
+        nulls_first = val.to_bool()?;
+    }
+    if let Ok(val) = resource.get(urls::COLLECTION_INCLUDE) {
+        include = val.to_bool()?;
+    }
+    if let Ok(val) = resource.get(urls::COLLECTION_INCLUDE_NESTED) {
+        include_nested = val.to_bool()?;
+    }
+    if let Ok(val) = resource.get(urls::COLLECTION_MATERIALIZED) {
+        materialized = val.to_bool()?;
+    }
     for (k, v) in query_params {
+        // Queries that change the members are not materialized, only the Collection as it is defined
+        if let "property" | "value" | "filter" | "match" | "sort_by" | "nulls" | "sort_desc" = k.as_ref() {
+            materialized = false;
+        }
         match k.as_ref() {
             "property" => property = Some(v.to_string()),
             "value" => value = Some(v.to_string()),
-            "sort_by" => sort_by = Some(v.to_string()),
-            // TODO: parse bool
-            "sort_desc" => sort_desc = true,
+            "filter" => filters.push(Filter::from_query_param(&v)),
+            "match" => match_any = v == "any",
+            "sort_by" => sort_keys.push(SortKey::from_query_param(&v)),
+            "nulls" => nulls_first = v == "first",
+            "include" => include = v == "true",
+            "include_nested" => include_nested = v == "true",
+            "sort_desc" => query_sort_desc = Some(v != "false"),
             "current_page" => current_page = v.parse::<usize>()?,
+            "after" => after = Some(v.to_string()),
+            "before" => before = Some(v.to_string()),
             "page_size" => page_size = v.parse::<usize>()?,
             _ => {}
         };
     }
-    let collection_builder = crate::collections::CollectionBuilder {
+    // The first sort_by is the main sort property, the others break ties.
+    // The order in the query overrides the one stored in the Collection.
+    if !sort_keys.is_empty() {
+        let first = sort_keys.remove(0);
+        sort_by = Some(first.property);
+        sort_desc = first.desc;
+        sort_then = sort_keys;
+    }
+    if let Some(desc) = query_sort_desc {
+        sort_desc = desc;
+    }
+    let collection_builder = CollectionBuilder {
         subject: resource.get_subject().into(),
         property,
         value,
+        filters,
+        match_any,
         sort_by,
         sort_desc,
+        sort_then,
+        nulls_first,
+        include: include || include_nested,
+        include_nested,
+        materialized,
+        after,
+        before,
         current_page,
         page_size,
         name,
     };
-    let collection = Collection::new_with_members(store, collection_builder)?;
-    collection.add_to_resource(resource, store)
+    Ok(collection_builder)
 }
 
 #[cfg(test)]
@@ -338,8 +1136,17 @@ mod test {
             subject: "test_subject".into(),
             property: Some(urls::IS_A.into()),
             value: Some(urls::CLASS.into()),
+            filters: Vec::new(),
+            match_any: false,
             sort_by: None,
             sort_desc: false,
+            sort_then: Vec::new(),
+            nulls_first: false,
+            include: false,
+            include_nested: false,
+            materialized: false,
+            after: None,
+            before: None,
             page_size: DEFAULT_PAGE_SIZE,
             current_page: 0,
             name: Some("Test collection".into())
@@ -357,8 +1164,17 @@ mod test {
             subject: "test_subject".into(),
             property: Some(urls::IS_A.into()),
             value: Some(urls::CLASS.into()),
+            filters: Vec::new(),
+            match_any: false,
             sort_by: None,
             sort_desc: false,
+            sort_then: Vec::new(),
+            nulls_first: false,
+            include: false,
+            include_nested: false,
+            materialized: false,
+            after: None,
+            before: None,
             page_size: DEFAULT_PAGE_SIZE,
             current_page: 0,
             name: None,
@@ -367,12 +1183,240 @@ mod test {
         assert!(collection.members.contains(&urls::PROPERTY.into()));
     }
 
+    #[test]
+    fn collection_filters_from_query() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        // Properties with the Slug datatype, or the Class itself
+        let url = url::Url::parse(&format!(
+            "https://localhost/props?filter={}&filter={}&page_size=100",
+            urlencoding::encode(&format!("{} {}", urls::IS_A, urls::PROPERTY)),
+            urlencoding::encode(&format!("{} {}", urls::DATATYPE_PROP, urls::SLUG)),
+        ))
+        .unwrap();
+        let mut resource = Resource::new(url.to_string());
+        let collection = construct_collection(&store, url.query_pairs(), &mut resource).unwrap();
+        let members = collection.get(urls::COLLECTION_MEMBERS).unwrap().to_vec().unwrap();
+        assert!(members.contains(&urls::SHORTNAME.to_string()));
+        assert!(!members.contains(&urls::DESCRIPTION.to_string()));
+
+        let url = url::Url::parse(&format!("{}&match=any", url)).unwrap();
+        let mut resource = Resource::new(url.to_string());
+        let collection = construct_collection(&store, url.query_pairs(), &mut resource).unwrap();
+        let members = collection.get(urls::COLLECTION_MEMBERS).unwrap().to_vec().unwrap();
+        assert!(members.contains(&urls::DESCRIPTION.to_string()));
+        assert!(collection.get(urls::COLLECTION_MATCH_ANY).unwrap().to_bool().unwrap());
+
+        // Single Resources can be checked against the same query
+        let mut stored = Resource::new("https://localhost/props".into());
+        stored
+            .set_propval_unsafe(urls::IS_A.into(), vec![urls::COLLECTION.to_string()].into())
+            .unwrap();
+        store.add_resource_unsafe(&stored).unwrap();
+        let query = local_collection_query(&store, url.as_str()).unwrap().unwrap();
+        assert!(query.matches(&store.get_resource(urls::DESCRIPTION).unwrap(), &store).unwrap());
+        let url = url.as_str().replace("&match=any", "");
+        let query = local_collection_query(&store, &url).unwrap().unwrap();
+        assert!(query.matches(&store.get_resource(urls::SHORTNAME).unwrap(), &store).unwrap());
+        assert!(!query.matches(&store.get_resource(urls::DESCRIPTION).unwrap(), &store).unwrap());
+        assert!(local_collection_query(&store, "https://localhost/missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn filter_from_query_param() {
+        let filter = Filter::from_query_param("https://example.com/~user/size~between~10~20");
+        assert_eq!(filter.property, "https://example.com/~user/size");
+        assert_eq!(filter.operator, Operator::Between);
+        assert_eq!(filter.value.as_deref(), Some("10"));
+        assert_eq!(filter.value_end.as_deref(), Some("20"));
+        let filter = Filter::from_query_param("https://example.com/name~contains~a~b");
+        assert_eq!(filter.operator, Operator::Contains);
+        assert_eq!(filter.value.as_deref(), Some("a~b"));
+        // Values that start with an operator are compared as they are
+        let filter = Filter::from_query_param("https://example.com/name gt 10");
+        assert_eq!(filter.operator, Operator::Equals);
+        assert_eq!(filter.value.as_deref(), Some("gt 10"));
+        let filter = Filter::from_query_param("https://example.com/name a~gt~b");
+        assert_eq!(filter.operator, Operator::Equals);
+        assert_eq!(filter.value.as_deref(), Some("a~gt~b"));
+    }
+
+    #[test]
+    fn sort_desc_from_query() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let shortnames = |query: &str| -> Vec<String> {
+            let url = url::Url::parse(&format!(
+                "https://localhost/props?filter={}&page_size=100{}",
+                urlencoding::encode(&format!("{} {}", urls::IS_A, urls::PROPERTY)),
+                query
+            ))
+            .unwrap();
+            let mut resource = Resource::new(url.to_string());
+            resource
+                .set_propval_unsafe(urls::COLLECTION_SORT_BY.into(), Value::AtomicUrl(urls::SHORTNAME.into()))
+                .unwrap();
+            resource
+                .set_propval_unsafe(urls::COLLECTION_SORT_DESC.into(), Value::Boolean(true))
+                .unwrap();
+            let collection = construct_collection(&store, url.query_pairs(), &mut resource).unwrap();
+            collection
+                .get(urls::COLLECTION_MEMBERS)
+                .unwrap()
+                .to_vec()
+                .unwrap()
+                .iter()
+                .map(|m| store.get_resource(m).unwrap().get(urls::SHORTNAME).unwrap().to_string())
+                .collect()
+        };
+        let is_sorted = |names: &[String]| names.windows(2).all(|w| w[0] <= w[1]);
+        assert!(!is_sorted(&shortnames("")));
+        // The query overrides the order that is stored in the Collection
+        assert!(is_sorted(&shortnames("&sort_desc=false")));
+        assert!(is_sorted(&shortnames(&format!("&sort_by={}", urls::SHORTNAME))));
+    }
+
+    #[test]
+    fn include_members() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let url = url::Url::parse(&format!(
+            "https://localhost/classes?property={}&value={}&sort_by={}&include_nested=true&page_size=2",
+            urlencoding::encode(urls::IS_A),
+            urlencoding::encode(urls::CLASS),
+            urlencoding::encode(urls::SHORTNAME),
+        ))
+        .unwrap();
+        let mut resource = Resource::new(url.to_string());
+        let collection = construct_collection(&store, url.query_pairs(), &mut resource).unwrap();
+        let members = collection.get(urls::COLLECTION_MEMBERS).unwrap().to_vec().unwrap().clone();
+        let included = collection.get(urls::COLLECTION_INCLUDED).unwrap().to_subjects().unwrap();
+        // The members come first, followed by the Resources they refer to
+        assert_eq!(included[..2], members[..]);
+        assert!(included.contains(&urls::CLASS.to_string()));
+
+        let json = collection.to_json_ad().unwrap();
+        let parsed = crate::parse::parse_json_ad_resource(&json, &store).unwrap();
+        match parsed.get(urls::COLLECTION_INCLUDED).unwrap() {
+            crate::Value::ResourceArrayNested(resources) => {
+                assert_eq!(resources[0].subject, members[0]);
+                assert!(resources[0].propvals.contains_key(urls::SHORTNAME));
+            }
+            _ => panic!("Included resources should be nested"),
+        }
+        let atoms = parsed.to_atoms().unwrap();
+        assert!(atoms.iter().any(|a| a.subject == members[1] && a.property == urls::SHORTNAME));
+    }
+
+    #[test]
+    fn cursor_pagination() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let get = |url: &str| -> Resource {
+            let url = url::Url::parse(url).unwrap();
+            let mut resource = Resource::new(url.to_string());
+            construct_collection(&store, url.query_pairs(), &mut resource).unwrap()
+        };
+        let members = |collection: &Resource| -> Vec<String> {
+            collection.get(urls::COLLECTION_MEMBERS).unwrap().to_vec().unwrap().clone()
+        };
+        let base = format!(
+            "https://localhost/classes?property={}&value={}&sort_by={}",
+            urlencoding::encode(urls::IS_A),
+            urlencoding::encode(urls::CLASS),
+            urlencoding::encode(urls::SHORTNAME),
+        );
+        let all = members(&get(&format!("{}&page_size=100", base)));
+
+        // Follow the next URLs until the last page
+        let first = get(&format!("{}&page_size=5", base));
+        assert!(first.get(urls::COLLECTION_PREVIOUS).is_err());
+        let mut followed = members(&first);
+        let mut next = first.get(urls::COLLECTION_NEXT).unwrap().to_string();
+        let second = get(&next);
+        // Page numbers still work
+        assert_eq!(members(&second), members(&get(&format!("{}&page_size=5&current_page=1", base))));
+        // Pages that are out of range don't overflow
+        let url = url::Url::parse(&format!("{}&page_size={}&current_page=2", base, usize::MAX)).unwrap();
+        let err = construct_collection(&store, url.query_pairs(), &mut Resource::new(url.to_string())).unwrap_err();
+        assert_eq!(err.to_string(), "Page number is too high");
+        let large_page = get(&format!("{}&page_size={}", next, usize::MAX));
+        assert!(large_page.get(urls::COLLECTION_NEXT).is_err());
+        loop {
+            let page = get(&next);
+            followed.extend(members(&page));
+            match page.get(urls::COLLECTION_NEXT) {
+                Ok(url) => next = url.to_string(),
+                Err(_) => break,
+            }
+        }
+        assert_eq!(followed, all);
+
+        // The previous URL of the second page leads to the first page
+        let previous = second.get(urls::COLLECTION_PREVIOUS).unwrap().to_string();
+        assert_eq!(members(&get(&previous)), members(&first));
+
+        // Adding a member on an earlier page does not shift the next page
+        let next = first.get(urls::COLLECTION_NEXT).unwrap().to_string();
+        let mut new_class = Resource::new("https://localhost/classes/aaa".into());
+        new_class.set_propval_string(urls::SHORTNAME.into(), "aaa", &store).unwrap();
+        new_class.set_propval(urls::IS_A.into(), vec![urls::CLASS.to_string()].into(), &store).unwrap();
+        new_class.set_propval_string(urls::DESCRIPTION.into(), "Sorts first", &store).unwrap();
+        store.add_resource(&new_class).unwrap();
+        assert_eq!(members(&get(&next)), members(&second));
+        assert_eq!(members(&get(&format!("{}&page_size=5", base)))[0], new_class.get_subject().clone());
+    }
+
+    #[test]
+    fn sort_by_datatype() {
+        let resource = |subject: &str, size: Option<i64>, name: &str| {
+            let mut resource = Resource::new(subject.into());
+            if let Some(size) = size {
+                resource
+                    .set_propval_unsafe(urls::COLLECTION_PAGE_SIZE.into(), crate::Value::Integer(size))
+                    .unwrap();
+            }
+            resource
+                .set_propval_unsafe(urls::NAME.into(), crate::Value::String(name.into()))
+                .unwrap();
+            resource
+        };
+        let resources = vec![
+            resource("https://localhost/d", Some(10), "b"),
+            resource("https://localhost/c", None, "a"),
+            resource("https://localhost/b", Some(9), "a"),
+            resource("https://localhost/a", Some(10), "b"),
+            resource("https://localhost/e", Some(10), "a"),
+        ];
+        let subjects = |resources: ResourceCollection| -> Vec<String> {
+            resources.iter().map(|r| r.get_subject().clone()).collect()
+        };
+        let by_size = SortKey::from_query_param(urls::COLLECTION_PAGE_SIZE);
+        // Numeric order, ties by subject, missing values last
+        assert_eq!(
+            subjects(sort_resources(resources.clone(), std::slice::from_ref(&by_size), false)),
+            vec!["https://localhost/b", "https://localhost/a", "https://localhost/d", "https://localhost/e", "https://localhost/c"]
+        );
+        // Descending, missing values first
+        let by_size_desc = SortKey::from_query_param(&format!("-{}", urls::COLLECTION_PAGE_SIZE));
+        assert_eq!(
+            subjects(sort_resources(resources.clone(), &[by_size_desc], true)),
+            vec!["https://localhost/c", "https://localhost/a", "https://localhost/d", "https://localhost/e", "https://localhost/b"]
+        );
+        // Multiple sort properties
+        let by_name = SortKey::from_query_param(urls::NAME);
+        assert_eq!(
+            subjects(sort_resources(resources, &[by_size, by_name], false)),
+            vec!["https://localhost/b", "https://localhost/e", "https://localhost/a", "https://localhost/d", "https://localhost/c"]
+        );
+    }
+
     #[test]
     fn get_collection() {
         let store = crate::Store::init().unwrap();
         store.populate().unwrap();
         let collection = store
-            .get_resource_extended("https://atomicdata.dev/collections/class")
+            .get_resource_extended("https://atomicdata.dev/collections/class", None)
             .unwrap();
         assert!(
             collection
@@ -403,7 +1447,7 @@ mod test {
         store.populate().unwrap();
 
         let collection_page_size = store
-            .get_resource_extended("https://atomicdata.dev/collections/class?page_size=1")
+            .get_resource_extended("https://atomicdata.dev/collections/class?page_size=1", None)
             .unwrap();
         assert!(
             collection_page_size
@@ -413,7 +1457,7 @@ mod test {
                 == "1"
         );
         let collection_page_nr = store
-            .get_resource_extended("https://atomicdata.dev/collections/class?current_page=2&page_size=1")
+            .get_resource_extended("https://atomicdata.dev/collections/class?current_page=2&page_size=1", None)
             .unwrap();
         assert!(
             collection_page_nr
@@ -8,6 +8,22 @@ use crate::{
     datatype::DataType, errors::AtomicResult, resources::PropVals, urls, Resource, Storelike, Value,
 };
 
+/// Servers refuse Commits that were created longer ago than this, in milliseconds (24 hours).
+pub const MAX_COMMIT_AGE_MS: i64 = 86_400_000;
+
+/// The result of a successfully applied Commit.
+/// Passed to `Storelike::handle_commit`, for example to notify subscribers.
+#[derive(Clone, Debug)]
+pub struct CommitResponse {
+    pub commit: Commit,
+    /// The Commit as it is stored, including its URL
+    pub commit_resource: Resource,
+    /// The Resource after the Commit was applied. None if it was destroyed.
+    pub resource_new: Option<Resource>,
+    /// The Resource before the Commit was applied. None if it did not exist yet.
+    pub resource_old: Option<Resource>,
+}
+
 /// A Commit is a set of changes to a Resource.
 /// Use CommitBuilder if you're programmatically constructing a Delta.
 #[derive(Clone, Debug, Serialize)]
@@ -31,6 +47,10 @@ pub struct Commit {
     /// If set to true, deletes the entire resource
     #[serde(rename = "https://atomicdata.dev/properties/destroy")]
     pub destroy: Option<bool>,
+    /// The URL of the last Commit that the author had seen for this Resource.
+    /// Used to detect and merge concurrent Commits.
+    #[serde(rename = "https://atomicdata.dev/properties/previousCommit")]
+    pub previous_commit: Option<String>,
     /// Base64 encoded signature of the JSON serialized Commit
     #[serde(rename = "https://atomicdata.dev/properties/signature")]
     pub signature: Option<String>,
@@ -61,28 +81,7 @@ impl Commit {
         validate_rights: bool,
     ) -> AtomicResult<Resource> {
         if validate_signature {
-            let signature = match self.signature.as_ref() {
-                Some(sig) => sig,
-                None => return Err("No signature set".into()),
-            };
-            // TODO: Check if commit.agent has the rights to update the resource
-            let pubkey_b64 = store
-                .get_resource(&self.signer)?
-                .get(urls::PUBLIC_KEY)?
-                .to_string();
-            let agent_pubkey = base64::decode(pubkey_b64)?;
-            let stringified_commit = self.serialize_deterministically_json_ad(store)?;
-            let peer_public_key =
-                ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, agent_pubkey);
-            let signature_bytes = base64::decode(signature.clone())?;
-            peer_public_key
-                .verify(stringified_commit.as_bytes(), &signature_bytes)
-                .map_err(|_e| {
-                    format!(
-                        "Incorrect signature for Commit. This could be due to an error during signing or serialization of the commit. Stringified commit: {}. Public key",
-                        stringified_commit,
-                    )
-                })?;
+            self.validate_signature(store)?;
         }
         // Check if the created_at lies in the past
         if validate_timestamp {
@@ -101,10 +100,11 @@ impl Commit {
             }
         }
         let commit_resource: Resource = self.clone().into_resource(store)?;
+        let resource_old = store.get_resource(&self.subject).ok();
         // Create a new resource if it doens't exist yet
-        let mut resource = match store.get_resource(&self.subject) {
-            Ok(rs) => rs,
-            Err(_) => Resource::new(self.subject.clone()),
+        let mut resource = match &resource_old {
+            Some(rs) => rs.clone(),
+            None => Resource::new(self.subject.clone()),
         };
         // Set a parent only if the rights checks are to be validated.
         // This should happen _before_ setting any values, to prevent malicious users from giving themselves write rights in a commit!
@@ -124,25 +124,96 @@ impl Commit {
             }
             println!("This should not happen!")
         };
-        let resource_changed = self.apply_changes(resource, store)?;
+        let mut resource_changed = crate::merge::apply_merged(self, resource, store)?;
+        crate::merge::set_last_commit(
+            &mut resource_changed,
+            self,
+            commit_resource.get_subject(),
+            store,
+        )?;
         // Check if all required props are there
         if validate_schema {
             resource_changed.check_required_props(store)?;
+            // Check if referenced Resources have the right Class, both in the Commit itself (e.g. the signer) and in the set Values
+            let mode = store.get_classtype_mode();
+            for (prop, val) in commit_resource
+                .get_propvals()
+                .iter()
+                .chain(self.set.iter().flatten())
+            {
+                let property = store.get_property(prop)?;
+                crate::validate::check_classtype(store, &property, val, mode)?;
+            }
         }
         // If a Destroy field is found, remove the resource and return early
         // TODO: Should we remove the existing commits too? Probably.
         if let Some(destroy) = self.destroy {
             if destroy {
+                // The Commit itself is kept, so the deletion shows up in the audit log
+                store.add_resource(&commit_resource)?;
                 store.remove_resource(&self.subject)?;
+                store.handle_commit(&CommitResponse {
+                    commit: self.clone(),
+                    commit_resource: commit_resource.clone(),
+                    resource_new: None,
+                    resource_old,
+                });
                 return Ok(commit_resource);
             }
         }
         // Save the Commit to the Store
         store.add_resource(&commit_resource)?;
         store.add_resource(&resource_changed)?;
+        store.handle_commit(&CommitResponse {
+            commit: self.clone(),
+            commit_resource: commit_resource.clone(),
+            resource_new: Some(resource_changed),
+            resource_old,
+        });
         Ok(commit_resource)
     }
 
+    /// Returns the subjects of the Resources that the Commit refers to: the signer, the previous Commit, the removed Properties and the set Resources.
+    pub fn referenced_subjects(&self) -> Vec<String> {
+        let mut subjects = vec![self.signer.clone()];
+        subjects.extend(self.previous_commit.iter().cloned());
+        subjects.extend(self.remove.iter().flatten().cloned());
+        for value in self.set.iter().flat_map(|set| set.values()) {
+            match value {
+                Value::AtomicUrl(subject) => subjects.push(subject.clone()),
+                Value::ResourceArray(items) => subjects.extend(items.iter().cloned()),
+                _ => {}
+            }
+        }
+        subjects
+    }
+
+    /// Checks if the signature was created by the private key of the signer.
+    pub fn validate_signature(&self, store: &impl Storelike) -> AtomicResult<()> {
+        let signature = match self.signature.as_ref() {
+            Some(sig) => sig,
+            None => return Err("No signature set".into()),
+        };
+        let pubkey_b64 = store
+            .get_resource(&self.signer)?
+            .get(urls::PUBLIC_KEY)?
+            .to_string();
+        let agent_pubkey = base64::decode(pubkey_b64)?;
+        let stringified_commit = self.serialize_deterministically_json_ad(store)?;
+        let peer_public_key =
+            ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, agent_pubkey);
+        let signature_bytes = base64::decode(signature.clone())?;
+        peer_public_key
+            .verify(stringified_commit.as_bytes(), &signature_bytes)
+            .map_err(|_e| {
+                format!(
+                    "Incorrect signature for Commit. This could be due to an error during signing or serialization of the commit. Stringified commit: {}. Public key",
+                    stringified_commit,
+                )
+            })?;
+        Ok(())
+    }
+
     /// Updates the values in the Resource according to the `set` and `remove` attributes in the Commit
     pub fn apply_changes(&self, mut resource: Resource, store: &impl Storelike) -> AtomicResult<Resource> {
         if let Some(set) = self.set.clone() {
@@ -180,6 +251,10 @@ impl Commit {
             Ok(found) => Some(found.to_bool()?),
             Err(_) => None,
         };
+        let previous_commit = match resource.get(urls::PREVIOUS_COMMIT) {
+            Ok(found) => Some(found.to_string()),
+            Err(_) => None,
+        };
         let signature = resource.get(urls::SIGNATURE)?.to_string();
         let url = Some(resource.get_subject().into());
 
@@ -190,6 +265,7 @@ impl Commit {
             set,
             remove,
             destroy,
+            previous_commit,
             signature: Some(signature),
             url,
         })
@@ -239,6 +315,13 @@ impl Commit {
         if self.destroy.is_some() && self.destroy.unwrap() {
             resource.set_propval(urls::DESTROY.into(), true.into(), store)?;
         }
+        if let Some(previous_commit) = self.previous_commit {
+            resource.set_propval(
+                urls::PREVIOUS_COMMIT.into(),
+                Value::AtomicUrl(previous_commit),
+                store,
+            )?;
+        }
         resource.set_propval(
             SIGNER.into(),
             Value::new(&self.signer, &DataType::AtomicUrl).unwrap(),
@@ -285,6 +368,8 @@ pub struct CommitBuilder {
     remove: HashSet<String>,
     /// If set to true, deletes the entire resource
     destroy: bool,
+    /// The last Commit of the Resource that these changes are based on
+    previous_commit: Option<String>,
     // pub signature: String,
 }
 
@@ -296,6 +381,7 @@ impl CommitBuilder {
             set: HashMap::new(),
             remove: HashSet::new(),
             destroy: false,
+            previous_commit: None,
         }
     }
 
@@ -325,6 +411,12 @@ impl CommitBuilder {
     pub fn destroy(&mut self, destroy: bool) {
         self.destroy = destroy
     }
+
+    /// Set the URL of the last Commit of the Resource that these changes are based on.
+    /// Commits that were applied after that one are concurrent, and will be merged.
+    pub fn set_previous_commit(&mut self, commit_url: String) {
+        self.previous_commit = Some(commit_url)
+    }
 }
 
 /// Signs a CommitBuilder at a specific unix timestamp.
@@ -340,6 +432,7 @@ fn sign_at(
         set: Some(commitbuilder.set),
         remove: Some(commitbuilder.remove.into_iter().collect()),
         destroy: Some(commitbuilder.destroy),
+        previous_commit: commitbuilder.previous_commit,
         created_at: sign_date,
         signature: None,
         url: None,
@@ -364,7 +457,7 @@ fn sign_at(
 }
 
 /// Signs a string using a base64 encoded ed25519 private key. Outputs a base64 encoded ed25519 signature.
-fn sign_message(message: &str, private_key: &str, public_key: &str) -> AtomicResult<String> {
+pub fn sign_message(message: &str, private_key: &str, public_key: &str) -> AtomicResult<String> {
     let private_key_bytes = base64::decode(private_key.to_string()).map_err(|e| {
         format!(
             "Failed decoding private key {}: {}",
@@ -427,6 +520,68 @@ mod test {
         );
     }
 
+    #[test]
+    fn constraints_are_enforced() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let agent = store.create_agent(Some("test_actor")).unwrap();
+        let status = "https://localhost/properties/status";
+        let score = "https://localhost/properties/score";
+        for (subject, datatype, constraints) in [
+            (
+                status,
+                DataType::String,
+                crate::schema::Constraints {
+                    allowed_values: Some(vec!["open".into(), "closed".into()]),
+                    ..Default::default()
+                },
+            ),
+            (
+                score,
+                DataType::Integer,
+                crate::schema::Constraints {
+                    min_value: Some(0.0),
+                    max_value: Some(10.0),
+                    ..Default::default()
+                },
+            ),
+        ] {
+            let property = crate::schema::Property {
+                class_type: None,
+                data_type: datatype,
+                shortname: "constrained".into(),
+                description: "A property with constraints".into(),
+                subject: subject.into(),
+                constraints,
+            };
+            store
+                .add_resource_unsafe(&property.to_resource().unwrap())
+                .unwrap();
+        }
+        let apply = |property: &str, value: Value| {
+            let mut commitbuilder = CommitBuilder::new("https://localhost/constrained".into());
+            commitbuilder.set(property.into(), value);
+            commitbuilder.sign(&agent, &store).unwrap().apply(&store)
+        };
+        assert!(apply(status, Value::String("open".into())).is_ok());
+        assert!(apply(score, Value::Integer(10)).is_ok());
+        let err = apply(status, Value::String("pending".into())).unwrap_err();
+        assert!(err.to_string().contains("allowed values: open, closed"));
+        assert!(apply(score, Value::Integer(11)).is_err());
+
+        let mut resource = store.get_resource("https://localhost/constrained").unwrap();
+        assert!(resource.set_propval(score.into(), Value::Integer(-1), &store).is_err());
+        assert!(resource.set_propval_string(score.into(), "3", &store).is_ok());
+
+        // Lengths can't be negative
+        let mut property = store.get_resource(status).unwrap();
+        property
+            .set_propval_unsafe(urls::MIN_LENGTH.into(), Value::Integer(-1))
+            .unwrap();
+        let err = crate::schema::Property::from_resource(property).unwrap_err();
+        assert!(err.to_string().contains("can't be negative"));
+    }
+
     #[test]
     fn serialize_commit() {
         let store = crate::Store::init().unwrap();
@@ -445,6 +600,7 @@ mod test {
             set: Some(set),
             remove: Some(remove),
             destroy: Some(destroy),
+            previous_commit: None,
             signature: None,
             url: None,
         };
@@ -0,0 +1,181 @@
+//! Typed comparisons for finding Resources by the Value of a Property, such as "greater than" or "starts with".
+//! Used by Collection filters and `Storelike::tpf_compare`.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{datatype::DataType, errors::AtomicResult, Value};
+
+/// How the Values of Resources are compared to the Value of a query
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Operator {
+    /// Equal to the Value. For ResourceArrays, the Value has to be one of the items.
+    Equals,
+    /// Greater than the Value
+    Gt,
+    /// Less than the Value
+    Lt,
+    /// Between two Values, including both
+    Between,
+    /// Starts with the Value
+    Prefix,
+    /// Contains the Value somewhere in its text
+    Contains,
+}
+
+impl Operator {
+    /// Parses the short name of an Operator, as used in query parameters: `eq`, `gt`, `lt`, `between`, `prefix` or `contains`.
+    pub fn from_string(operator: &str) -> AtomicResult<Operator> {
+        match operator {
+            "eq" => Ok(Operator::Equals),
+            "gt" => Ok(Operator::Gt),
+            "lt" => Ok(Operator::Lt),
+            "between" => Ok(Operator::Between),
+            "prefix" => Ok(Operator::Prefix),
+            "contains" => Ok(Operator::Contains),
+            other => Err(format!(
+                "Unknown operator {}. Use eq, gt, lt, between, prefix or contains.",
+                other
+            )
+            .into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Operator::Equals => "eq",
+            Operator::Gt => "gt",
+            Operator::Lt => "lt",
+            Operator::Between => "between",
+            Operator::Prefix => "prefix",
+            Operator::Contains => "contains",
+        }
+    }
+}
+
+/// An Operator with the Values that it compares to, parsed using the DataType of the Property.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub operator: Operator,
+    pub value: Value,
+    /// The upper bound, only used by `Between`
+    pub value_end: Option<Value>,
+}
+
+impl Comparison {
+    /// Parses the Values using the DataType of the Property, and checks if the Operator can be used for that DataType.
+    /// Ranges (`gt`, `lt` and `between`) require numbers, dates or text. `prefix` and `contains` require text or URLs.
+    pub fn new(
+        operator: Operator,
+        value: &str,
+        value_end: Option<&str>,
+        datatype: &DataType,
+    ) -> AtomicResult<Comparison> {
+        let allowed = match operator {
+            Operator::Equals => true,
+            Operator::Gt | Operator::Lt | Operator::Between => is_ordered(datatype),
+            Operator::Prefix | Operator::Contains => is_text(datatype),
+        };
+        if !allowed {
+            return Err(format!(
+                "The {} operator can't be used for Properties with DataType {}",
+                operator.as_str(),
+                datatype
+            )
+            .into());
+        }
+        let value_end = match (&operator, value_end) {
+            (Operator::Between, Some(end)) => Some(Value::new(end, datatype)?),
+            (Operator::Between, None) => {
+                return Err("The between operator requires a second value".into())
+            }
+            _ => None,
+        };
+        let value = match (&operator, datatype) {
+            // The Value is one of the items of the array
+            (Operator::Equals, DataType::ResourceArray) => Value::AtomicUrl(value.into()),
+            // Parts of a text don't have to be valid Values themselves, e.g. the start of a Slug or URL
+            (Operator::Prefix, _) | (Operator::Contains, _) => Value::String(value.into()),
+            _ => Value::new(value, datatype)?,
+        };
+        Ok(Comparison {
+            operator,
+            value,
+            value_end,
+        })
+    }
+
+    /// Checks if the Value of a Resource meets the Comparison.
+    pub fn matches(&self, value: &Value) -> bool {
+        let query = self.value.to_string();
+        match self.operator {
+            Operator::Equals => match value {
+                Value::ResourceArray(items) => items.contains(&query),
+                _ => value.to_string() == query,
+            },
+            Operator::Gt => value.compare(&self.value) == Ordering::Greater,
+            Operator::Lt => value.compare(&self.value) == Ordering::Less,
+            Operator::Between => match &self.value_end {
+                Some(end) => {
+                    value.compare(&self.value) != Ordering::Less
+                        && value.compare(end) != Ordering::Greater
+                }
+                None => false,
+            },
+            Operator::Prefix => value.to_string().starts_with(&query),
+            Operator::Contains => value.to_string().contains(&query),
+        }
+    }
+}
+
+/// DataTypes of which the Values can be put in order
+fn is_ordered(datatype: &DataType) -> bool {
+    matches!(
+        datatype,
+        DataType::Integer
+            | DataType::Float
+            | DataType::Timestamp
+            | DataType::Date
+            | DataType::String
+            | DataType::Slug
+            | DataType::Markdown
+    )
+}
+
+/// DataTypes of which the Values are text
+fn is_text(datatype: &DataType) -> bool {
+    matches!(
+        datatype,
+        DataType::String | DataType::Slug | DataType::Markdown | DataType::AtomicUrl
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compare_by_datatype() {
+        let between =
+            Comparison::new(Operator::Between, "9", Some("10"), &DataType::Integer).unwrap();
+        assert!(between.matches(&Value::Integer(10)));
+        assert!(!between.matches(&Value::Integer(11)));
+        let after = Comparison::new(Operator::Gt, "2021-01-01", None, &DataType::Date).unwrap();
+        assert!(after.matches(&Value::Date("2021-03-01".into())));
+        let prefix = Comparison::new(
+            Operator::Prefix,
+            "https://atomicdata.dev/cl",
+            None,
+            &DataType::AtomicUrl,
+        )
+        .unwrap();
+        assert!(prefix.matches(&Value::AtomicUrl(
+            "https://atomicdata.dev/classes/Class".into()
+        )));
+        // Values must be valid for the DataType, and operators must make sense for it
+        assert!(Comparison::new(Operator::Gt, "nine", None, &DataType::Integer).is_err());
+        assert!(Comparison::new(Operator::Prefix, "1", None, &DataType::Integer).is_err());
+        assert!(Comparison::new(Operator::Between, "1", None, &DataType::Integer).is_err());
+    }
+}
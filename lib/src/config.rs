@@ -1,10 +1,18 @@
 //! Configuration logic which can be used in both CLI and Server contexts
 //! For serializaing, storing, and parsing the `~/.config/atomic/config.toml` file
+//! The private key can be stored as plain text, or encrypted using a passphrase.
 
 use crate::errors::AtomicResult;
+use ring::rand::SecureRandom;
 use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 
+/// Environment variable that contains the passphrase for an encrypted config
+pub const PASSPHRASE_ENV: &str = "ATOMIC_PASSPHRASE";
+/// Amount of PBKDF2 iterations for deriving the encryption key from the passphrase
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
 /// A set of options that are shared between CLI and Server contexts
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
@@ -16,6 +24,90 @@ pub struct Config {
     pub private_key: String,
 }
 
+/// How the config is stored on disk.
+/// Contains either a plain text `private_key` or an `encrypted_private_key`.
+#[derive(Debug, Serialize, Deserialize)]
+struct ConfigFile {
+    server: String,
+    agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    private_key: Option<String>,
+    // Tables have to be serialized after the plain values in TOML
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encrypted_private_key: Option<EncryptedPrivateKey>,
+}
+
+/// A private key that is encrypted with AES-256-GCM.
+/// The encryption key is derived from a passphrase using PBKDF2-HMAC-SHA256.
+/// All binary values are base64 encoded.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EncryptedPrivateKey {
+    pub ciphertext: String,
+    pub salt: String,
+    pub nonce: String,
+    pub iterations: u32,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> AtomicResult<[u8; 32]> {
+    let iterations =
+        NonZeroU32::new(iterations).ok_or("Amount of iterations must be greater than 0")?;
+    let mut key = [0u8; 32];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    Ok(key)
+}
+
+fn aead_key(key: &[u8; 32]) -> AtomicResult<ring::aead::LessSafeKey> {
+    let unbound = ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, key)
+        .map_err(|_e| "Could not create encryption key")?;
+    Ok(ring::aead::LessSafeKey::new(unbound))
+}
+
+/// Encrypts a private key using a passphrase. Uses a random salt and nonce.
+pub fn encrypt_private_key(private_key: &str, passphrase: &str) -> AtomicResult<EncryptedPrivateKey> {
+    let rng = ring::rand::SystemRandom::new();
+    let mut salt = [0u8; 16];
+    rng.fill(&mut salt)
+        .map_err(|_e| "Could not generate salt")?;
+    let mut nonce = [0u8; ring::aead::NONCE_LEN];
+    rng.fill(&mut nonce)
+        .map_err(|_e| "Could not generate nonce")?;
+    let key = aead_key(&derive_key(passphrase, &salt, PBKDF2_ITERATIONS)?)?;
+    let mut in_out = private_key.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        ring::aead::Nonce::assume_unique_for_key(nonce),
+        ring::aead::Aad::empty(),
+        &mut in_out,
+    )
+    .map_err(|_e| "Could not encrypt private key")?;
+    Ok(EncryptedPrivateKey {
+        ciphertext: base64::encode(in_out),
+        salt: base64::encode(salt),
+        nonce: base64::encode(nonce),
+        iterations: PBKDF2_ITERATIONS,
+    })
+}
+
+/// Decrypts a private key using a passphrase.
+/// Returns an error if the passphrase is incorrect.
+pub fn decrypt_private_key(encrypted: &EncryptedPrivateKey, passphrase: &str) -> AtomicResult<String> {
+    let salt = base64::decode(&encrypted.salt)?;
+    let nonce_bytes = base64::decode(&encrypted.nonce)?;
+    let nonce = ring::aead::Nonce::try_assume_unique_for_key(&nonce_bytes)
+        .map_err(|_e| "Invalid nonce in encrypted private key")?;
+    let key = aead_key(&derive_key(passphrase, &salt, encrypted.iterations)?)?;
+    let mut in_out = base64::decode(&encrypted.ciphertext)?;
+    let decrypted = key
+        .open_in_place(nonce, ring::aead::Aad::empty(), &mut in_out)
+        .map_err(|_e| "Could not decrypt private key. Is the passphrase correct?")?;
+    Ok(String::from_utf8(decrypted.to_vec())?)
+}
+
 /// Returns the default path for the config file: `~/.config/atomic`
 pub fn default_config_dir_path() -> AtomicResult<PathBuf> {
     Ok(dirs::home_dir()
@@ -30,21 +122,118 @@ pub fn default_config_file_path() -> AtomicResult<PathBuf> {
     Ok(default_dir)
 }
 
-/// Reads config file from a specified path
-pub fn read_config(path: &Path) -> AtomicResult<Config> {
+fn read_config_file(path: &Path) -> AtomicResult<ConfigFile> {
     let config_string = std::fs::read_to_string(path)
         .map_err(|e| format!("Error reading config from {:?}. {}", path, e))?;
-    let config: Config = toml::from_str(&config_string)
+    let config: ConfigFile = toml::from_str(&config_string)
         .map_err(|e| format!("Could not parse toml in config file {:?}. {}", path, e))?;
     Ok(config)
 }
 
+/// Checks if the private key in the config file is encrypted
+pub fn config_is_encrypted(path: &Path) -> AtomicResult<bool> {
+    Ok(read_config_file(path)?.encrypted_private_key.is_some())
+}
+
+/// Reads config file from a specified path.
+/// If the private key is encrypted, the passphrase is read from the `ATOMIC_PASSPHRASE` environment variable.
+pub fn read_config(path: &Path) -> AtomicResult<Config> {
+    read_config_with(path, || {
+        Err(format!(
+            "The private key in {:?} is encrypted. Set the {} environment variable to decrypt it.",
+            path, PASSPHRASE_ENV
+        )
+        .into())
+    })
+}
+
+/// Reads config file from a specified path.
+/// If the private key is encrypted and the `ATOMIC_PASSPHRASE` environment variable is not set,
+/// `get_passphrase` is called, which can for example prompt the user.
+pub fn read_config_with(
+    path: &Path,
+    get_passphrase: impl FnOnce() -> AtomicResult<String>,
+) -> AtomicResult<Config> {
+    let file = read_config_file(path)?;
+    let private_key = match (file.private_key, file.encrypted_private_key) {
+        (_, Some(encrypted)) => {
+            let passphrase = match std::env::var(PASSPHRASE_ENV) {
+                Ok(passphrase) => passphrase,
+                Err(_) => get_passphrase()?,
+            };
+            decrypt_private_key(&encrypted, &passphrase)?
+        }
+        (Some(private_key), None) => private_key,
+        (None, None) => return Err(format!("No private key found in config file {:?}", path).into()),
+    };
+    Ok(Config {
+        server: file.server,
+        agent: file.agent,
+        private_key,
+    })
+}
+
 /// Writes config file from a specified path
 /// Overwrites any existing config
 pub fn write_config(path: &Path, config: Config) -> AtomicResult<String> {
+    write_config_file(
+        path,
+        ConfigFile {
+            server: config.server,
+            agent: config.agent,
+            private_key: Some(config.private_key),
+            encrypted_private_key: None,
+        },
+    )
+}
+
+/// Writes config file from a specified path, encrypts the private key using the passphrase.
+/// Overwrites any existing config
+pub fn write_config_encrypted(path: &Path, config: Config, passphrase: &str) -> AtomicResult<String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase can't be empty".into());
+    }
+    let encrypted_private_key = encrypt_private_key(&config.private_key, passphrase)?;
+    write_config_file(
+        path,
+        ConfigFile {
+            server: config.server,
+            agent: config.agent,
+            private_key: None,
+            encrypted_private_key: Some(encrypted_private_key),
+        },
+    )
+}
+
+fn write_config_file(path: &Path, config: ConfigFile) -> AtomicResult<String> {
     let out =
         toml::to_string_pretty(&config).map_err(|e| format!("Error serializing config. {}", e))?;
     std::fs::write(path, out.clone())
         .map_err(|e| format!("Error writing config to {:?}. {}", path, e))?;
     Ok(out)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encrypted_config_roundtrip() {
+        let config = Config {
+            server: "http://localhost".into(),
+            agent: "http://localhost/agents/test".into(),
+            private_key: "CapMWIhFUT+w7ANv9oCPqrHrwZpkP2JhzF9JnyT6WcI=".into(),
+        };
+        let _ = std::fs::create_dir_all("tmp");
+        let path = Path::new("tmp/encrypted_config.toml");
+        let out = write_config_encrypted(path, config.clone(), "correct horse").unwrap();
+        assert!(!out.contains(&config.private_key));
+        assert!(config_is_encrypted(path).unwrap());
+        let read = read_config_with(path, || Ok("correct horse".into())).unwrap();
+        assert_eq!(read.private_key, config.private_key);
+        read_config_with(path, || Ok("wrong horse".into())).unwrap_err();
+        write_config(path, config.clone()).unwrap();
+        assert!(!config_is_encrypted(path).unwrap());
+        assert_eq!(read_config(path).unwrap().private_key, config.private_key);
+    }
+}
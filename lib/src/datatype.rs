@@ -1,8 +1,10 @@
-//! Datatypes constrain values of Atoms
+//! Datatypes constrain values of Atoms.
+//! Applications can add their own Datatypes (e.g. email or duration) using `register_datatype`.
 
-use std::{fmt, string::ParseError};
+use lazy_static::lazy_static;
+use std::{fmt, string::ParseError, sync::RwLock};
 use serde::{Deserialize, Serialize};
-use crate::urls;
+use crate::{errors::AtomicResult, urls, Resource, Storelike, Value};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum DataType {
@@ -16,6 +18,7 @@ pub enum DataType {
     Slug,
     String,
     Timestamp,
+    /// Not built in. Its Values are validated if the Datatype is registered using `register_datatype`.
     Unsupported(String),
 }
 
@@ -72,3 +75,212 @@ impl fmt::Display for DataType {
         }
     }
 }
+
+/// A Datatype that is not built in, such as an email address or a duration.
+/// Its Values are stored as `Value::Unsupported`.
+/// Register it using `register_datatype`, so that `Value::new`, the JSON-AD parser and the serializers use it.
+#[derive(Clone, Debug)]
+pub struct CustomDatatype {
+    /// URL of the Datatype, which Properties use as their `datatype`
+    pub url: String,
+    pub shortname: String,
+    pub description: String,
+    /// Validates a Value and returns the (normalized) string that is stored
+    pub parse: fn(value: &str) -> AtomicResult<String>,
+    /// Converts a JSON-AD value to the string that is passed to `parse`.
+    /// The default only accepts JSON strings.
+    pub from_json: fn(json: &serde_json::Value) -> AtomicResult<String>,
+    /// Converts a stored Value to JSON, for the JSON-AD and JSON serializations.
+    /// The default serializes it as a JSON string.
+    pub to_json: fn(value: &str) -> serde_json::Value,
+    /// Datatype of the Values in RDF literals, e.g. `http://www.w3.org/2001/XMLSchema#duration`.
+    /// Uses `url` if empty.
+    pub rdf_datatype: Option<String>,
+}
+
+impl CustomDatatype {
+    /// Creates a Datatype whose Values are serialized as strings
+    pub fn new(
+        url: &str,
+        shortname: &str,
+        description: &str,
+        parse: fn(value: &str) -> AtomicResult<String>,
+    ) -> CustomDatatype {
+        CustomDatatype {
+            url: url.into(),
+            shortname: shortname.into(),
+            description: description.into(),
+            parse,
+            from_json: string_from_json,
+            to_json: string_to_json,
+            rdf_datatype: None,
+        }
+    }
+
+    /// Converts the Datatype to a Resource, so it can be added to a store. Does not save it.
+    pub fn to_resource(&self, store: &impl Storelike) -> AtomicResult<Resource> {
+        let mut resource = Resource::new(self.url.clone());
+        resource.set_propval(
+            urls::IS_A.into(),
+            Value::ResourceArray(vec![urls::DATATYPE_CLASS.into()]),
+            store,
+        )?;
+        resource.set_propval_string(urls::SHORTNAME.into(), &self.shortname, store)?;
+        resource.set_propval_string(urls::DESCRIPTION.into(), &self.description, store)?;
+        Ok(resource)
+    }
+}
+
+fn string_from_json(json: &serde_json::Value) -> AtomicResult<String> {
+    match json {
+        serde_json::Value::String(string) => Ok(string.clone()),
+        other => Err(format!("Expected a string, got {}", other).into()),
+    }
+}
+
+fn string_to_json(value: &str) -> serde_json::Value {
+    serde_json::Value::String(value.into())
+}
+
+lazy_static! {
+    /// The Datatypes registered by the application. A Vec, because there are only a few of them.
+    static ref CUSTOM_DATATYPES: RwLock<Vec<CustomDatatype>> = RwLock::new(Vec::new());
+}
+
+/// Registers a Datatype for the whole process. Replaces an earlier one with the same URL.
+pub fn register_datatype(datatype: CustomDatatype) {
+    let mut datatypes = CUSTOM_DATATYPES.write().unwrap();
+    datatypes.retain(|existing| existing.url != datatype.url);
+    datatypes.push(datatype);
+}
+
+/// Removes a registered Datatype. Its Values are no longer validated, like other unsupported Datatypes.
+pub fn unregister_datatype(url: &str) {
+    CUSTOM_DATATYPES
+        .write()
+        .unwrap()
+        .retain(|existing| existing.url != url);
+}
+
+/// Returns the registered Datatype with this URL
+pub fn get_custom_datatype(url: &str) -> Option<CustomDatatype> {
+    CUSTOM_DATATYPES
+        .read()
+        .unwrap()
+        .iter()
+        .find(|datatype| datatype.url == url)
+        .cloned()
+}
+
+/// Returns true if any Datatype has been registered
+pub fn has_custom_datatypes() -> bool {
+    !CUSTOM_DATATYPES.read().unwrap().is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{schema::Property, Store};
+
+    const EMAIL: &str = "https://example.com/datatypes/email";
+    const JSON: &str = "https://example.com/datatypes/json";
+
+    fn parse_email(value: &str) -> AtomicResult<String> {
+        match value.split_once('@') {
+            Some((user, domain)) if !user.is_empty() && domain.contains('.') => {
+                Ok(value.to_lowercase())
+            }
+            _ => Err("Should look like name@example.com".into()),
+        }
+    }
+
+    fn parse_json(value: &str) -> AtomicResult<String> {
+        let json: serde_json::Value = serde_json::from_str(value)?;
+        Ok(json.to_string())
+    }
+
+    fn add_property(store: &Store, shortname: &str, datatype: &str) -> String {
+        let subject = format!("https://example.com/properties/{}", shortname);
+        let property = Property {
+            class_type: None,
+            data_type: match_datatype(datatype),
+            shortname: shortname.into(),
+            description: format!("A {}", shortname),
+            subject: subject.clone(),
+            constraints: Default::default(),
+        };
+        store
+            .add_resource_unsafe(&property.to_resource().unwrap())
+            .unwrap();
+        subject
+    }
+
+    /// Unregisters the Datatypes when the test ends, also if it fails,
+    /// since the registry is shared with the other tests.
+    struct Registered(Vec<&'static str>);
+
+    impl Drop for Registered {
+        fn drop(&mut self) {
+            for url in &self.0 {
+                unregister_datatype(url);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_datatypes() {
+        let _registered = Registered(vec![EMAIL, JSON]);
+        let mut email = CustomDatatype::new(EMAIL, "email", "An email address", parse_email);
+        email.rdf_datatype = Some("http://www.w3.org/2001/XMLSchema#string".into());
+        register_datatype(email);
+        let mut json = CustomDatatype::new(JSON, "json", "Any JSON value", parse_json);
+        json.from_json = |json| Ok(json.to_string());
+        json.to_json = |value| serde_json::from_str(value).unwrap_or_default();
+        register_datatype(json);
+
+        let email_datatype = match_datatype(EMAIL);
+        let value = Value::new("Joe@Example.com", &email_datatype).unwrap();
+        assert_eq!(value.to_string(), "joe@example.com");
+        assert_eq!(value.datatype(), email_datatype);
+        assert!(Value::new("not an email", &email_datatype).is_err());
+
+        let store = Store::init().unwrap();
+        let email_prop = add_property(&store, "email", EMAIL);
+        let json_prop = add_property(&store, "settings", JSON);
+        let json_ad = format!(
+            r#"{{
+                "@id": "https://example.com/joe",
+                "{}": "Joe@Example.com",
+                "{}": {{"theme": "dark", "size": 2}}
+            }}"#,
+            email_prop, json_prop
+        );
+        let resource = crate::parse::parse_json_ad_resource(&json_ad, &store).unwrap();
+        assert_eq!(resource.get(&email_prop).unwrap().to_string(), "joe@example.com");
+        let serialized = resource.to_json_ad().unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(parsed[&json_prop]["theme"], "dark");
+        let reparsed = crate::parse::parse_json_ad_resource(&serialized, &store).unwrap();
+        assert_eq!(
+            reparsed.get(&json_prop).unwrap().to_string(),
+            resource.get(&json_prop).unwrap().to_string()
+        );
+
+        // Arrays of Properties that are in the store can have a custom Datatype too
+        let array = format!(r#"{{"@id": "https://example.com/a", "{}": [1, "a"]}}"#, json_prop);
+        let with_array = crate::parse::parse_json_ad_resource(&array, &store).unwrap();
+        assert_eq!(with_array.get(&json_prop).unwrap().to_string(), r#"[1,"a"]"#);
+
+        let invalid = json_ad.replace("Joe@Example.com", "joe");
+        assert!(crate::parse::parse_json_ad_resource(&invalid, &store).is_err());
+
+        #[cfg(feature = "rdf")]
+        {
+            let ntriples =
+                crate::serialize::atoms_to_ntriples(resource.to_atoms().unwrap(), &store).unwrap();
+            assert!(ntriples
+                .contains(r#""joe@example.com"^^<http://www.w3.org/2001/XMLSchema#string>"#));
+            assert!(ntriples.contains(&format!("^^<{}>", JSON)));
+        }
+    }
+}
@@ -2,17 +2,27 @@
 //! Powered by Sled - an embedded database.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
 use crate::{
+    collections::Materialized,
+    comparison::{Comparison, Operator},
     errors::AtomicResult,
     resources::PropVals,
     storelike::{ResourceCollection, Storelike},
-    Atom, Resource,
+    urls,
+    validate::ClasstypeMode,
+    Atom, Resource, Value,
 };
 
+/// Key in the default tree that is set once the number index has been built
+const NUMBER_INDEX_BUILT: &str = "index_numbers_built";
+
+/// Function that is called after a Commit is applied, see `Db::set_handle_commit`
+pub type HandleCommit = dyn Fn(&crate::commit::CommitResponse) + Send + Sync;
+
 /// The Db is a persistent on-disk Atomic Data store.
 /// It's an implementation of Storelike.
 #[derive(Clone)]
@@ -26,9 +36,37 @@ pub struct Db {
     resources: sled::Tree,
     // Stores all Atoms. The key is the atom.value, the value a vector of Atoms.
     index_vals: sled::Tree,
+    /// Index of all Resources by their Property / Value combinations, used for filtering Collections.
+    /// The key is `{property}\0{value}\0{subject}`, the value is empty.
+    /// ResourceArrays are indexed by every item, and by the whole array.
     index_props: sled::Tree,
+    /// Index of all numeric Values (Integers, Floats and Timestamps), ordered by their Value, used for range queries.
+    /// The key is `{property}\0{value}{subject}`, where the value is 8 bytes that sort in the same order as the numbers.
+    index_numbers: sled::Tree,
+    /// Index of all Commits by their signer, ordered by their createdAt.
+    /// The key is `{signer}\0{created_at}{commit_subject}`, the value is the subject of the changed Resource.
+    index_signer: sled::Tree,
+    /// Index of all Commits, ordered by the moment they were added to this store, used for the Commit feed.
+    /// Commits can arrive later than their createdAt, so this makes sure that they never end up before a cursor that a follower already has.
+    /// The key is an 8 byte sequence number, the value is `{created_at}{commit_subject}\0{subject}`.
+    index_arrival: sled::Tree,
+    /// The sequence number of every Commit in `index_arrival`. The key is the subject of the Commit.
+    index_arrival_commits: sled::Tree,
+    /// The sorted members of materialized Collections, updated when Resources change.
+    /// The key is the subject of the Collection, the value a bincode serialized `Materialized`.
+    materialized: sled::Tree,
+    /// The Properties that can change the members of each materialized Collection, see `Materialized::properties`.
+    /// The key is the subject of the Collection, the value a bincode serialized `Option<Vec<String>>`.
+    materialized_props: sled::Tree,
+    /// The materialized Collections by the Properties that can change their members, so a change only reads the Collections it can affect.
+    /// The key is `{property}\0{collection}`, the value is empty. Collections that can have any Property use an empty property.
+    materialized_index: sled::Tree,
     /// The base_url is the domain where the db will be hosted, e.g. http://localhost/
     base_url: String,
+    /// Is called after every applied Commit
+    on_commit: Option<Arc<HandleCommit>>,
+    /// How strictly the `classtype` of Properties is checked when applying Commits
+    classtype_mode: ClasstypeMode,
 }
 
 impl Db {
@@ -39,15 +77,45 @@ impl Db {
         let db = sled::open(path).map_err(|e|format!("Failed creating DB at this location. Is another instance of Atomic Server running? {}", e))?;
         let resources = db.open_tree("resources").map_err(|e|format!("Failed building resources. Your DB might be corrupt. Go back to a previous version and export your data. {}", e))?;
         let index_props = db.open_tree("index_props")?;
+        let index_numbers = db.open_tree("index_numbers")?;
         let index_vals = db.open_tree("index_vals")?;
+        let index_signer = db.open_tree("index_signer")?;
+        // Stores created by older versions have an index of Commits by createdAt, which is replaced by the arrival index
+        db.drop_tree("index_time")?;
+        let index_arrival = db.open_tree("index_arrival")?;
+        let index_arrival_commits = db.open_tree("index_arrival_commits")?;
+        let materialized = db.open_tree("materialized")?;
+        let materialized_props = db.open_tree("materialized_props")?;
+        let materialized_index = db.open_tree("materialized_index")?;
+        // Stores created by older versions don't have the index, so their Collections are rebuilt on the next request
+        if materialized_index.is_empty() && !materialized.is_empty() {
+            materialized.clear()?;
+            materialized_props.clear()?;
+        }
         let store = Db {
             db,
             default_agent: Arc::new(Mutex::new(None)),
             resources,
             index_vals,
             index_props,
+            index_numbers,
+            index_signer,
+            index_arrival,
+            index_arrival_commits,
+            materialized,
+            materialized_props,
+            materialized_index,
             base_url,
+            on_commit: None,
+            classtype_mode: ClasstypeMode::default(),
         };
+        // Stores created by older versions don't have a property or number index yet
+        if !store.db.contains_key(NUMBER_INDEX_BUILT)? {
+            if !store.resources.is_empty() {
+                store.build_prop_index()?;
+            }
+            store.db.insert(NUMBER_INDEX_BUILT, &[])?;
+        }
         crate::populate::populate_base_models(&store)?;
         Ok(store)
     }
@@ -60,6 +128,376 @@ impl Db {
     //     todo!();
     // }
 
+    /// Sets a function that is called after every applied Commit.
+    /// Used by the server to notify WebSocket subscribers.
+    pub fn set_handle_commit(&mut self, on_commit: Box<HandleCommit>) {
+        self.on_commit = Some(Arc::from(on_commit));
+    }
+
+    /// Sets how strictly the `classtype` of Properties is checked when applying Commits. Lenient by default.
+    pub fn set_classtype_mode(&mut self, mode: ClasstypeMode) {
+        self.classtype_mode = mode;
+    }
+
+    /// Returns the Commits signed by some Agent, ordered by their createdAt (oldest first).
+    /// Optionally filters by a time window (unix milliseconds, both inclusive).
+    /// Uses the signer index, so it does not have to iterate over all Commits.
+    pub fn get_commits_by_signer(
+        &self,
+        signer: &str,
+        from: Option<i64>,
+        until: Option<i64>,
+    ) -> AtomicResult<Vec<IndexedCommit>> {
+        let prefix = signer_index_prefix(signer);
+        let mut start = prefix.clone();
+        start.extend_from_slice(&timestamp_to_key(from.unwrap_or(0)));
+        let mut end = prefix.clone();
+        end.extend_from_slice(&timestamp_to_key(until.unwrap_or(i64::MAX - 1) + 1));
+        let mut commits = Vec::new();
+        for item in self.index_signer.range(start..end) {
+            let (key, value) = item?;
+            let created_at_bytes = &key[prefix.len()..prefix.len() + 8];
+            let mut ts = [0u8; 8];
+            ts.copy_from_slice(created_at_bytes);
+            commits.push(IndexedCommit {
+                commit: String::from_utf8(key[prefix.len() + 8..].to_vec())?,
+                subject: String::from_utf8(value.to_vec())?,
+                created_at: u64::from_be_bytes(ts) as i64,
+            });
+        }
+        Ok(commits)
+    }
+
+    /// Returns a page of the Commits signed by some Agent, newest first.
+    /// Optionally filters by a time window (unix milliseconds, both inclusive) and by the Drive of the changed Resources.
+    /// If a `cursor` is passed, starts right after the Commit that the cursor points to.
+    /// Reads the signer index backwards and stops after `limit` Commits, so it does not read all Commits of the signer.
+    /// Returns a cursor that points to the last Commit if there are more Commits after it.
+    pub fn get_commits_by_signer_page(
+        &self,
+        signer: &str,
+        from: Option<i64>,
+        until: Option<i64>,
+        drive: Option<&str>,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> AtomicResult<(Vec<IndexedCommit>, Option<String>)> {
+        let prefix = signer_index_prefix(signer);
+        let mut start = prefix.clone();
+        start.extend_from_slice(&timestamp_to_key(from.unwrap_or(0)));
+        let mut end = prefix.clone();
+        end.extend_from_slice(&timestamp_to_key(until.unwrap_or(i64::MAX - 1) + 1));
+        // The cursor is the key of the last Commit of the previous page, without the prefix
+        if let Some(cursor) = cursor {
+            let mut key = prefix.clone();
+            key.extend(
+                base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+                    .map_err(|e| format!("Invalid cursor {}. {}", cursor, e))?,
+            );
+            if key < end {
+                end = key;
+            }
+        }
+        let mut commits = Vec::new();
+        let mut last_key: Vec<u8> = Vec::new();
+        let mut next = None;
+        for item in self.index_signer.range(start..end).rev() {
+            let (key, value) = item?;
+            let subject = String::from_utf8(value.to_vec())?;
+            if let Some(drive) = drive {
+                if !crate::hierarchy::is_inside(&subject, drive) {
+                    continue;
+                }
+            }
+            if commits.len() == limit {
+                next = Some(base64::encode_config(&last_key, base64::URL_SAFE_NO_PAD));
+                break;
+            }
+            let mut ts = [0u8; 8];
+            ts.copy_from_slice(&key[prefix.len()..prefix.len() + 8]);
+            commits.push(IndexedCommit {
+                commit: String::from_utf8(key[prefix.len() + 8..].to_vec())?,
+                subject,
+                created_at: u64::from_be_bytes(ts) as i64,
+            });
+            last_key = key[prefix.len()..].to_vec();
+        }
+        Ok((commits, next))
+    }
+
+    /// Returns Commits in the order in which they were added to this store (oldest first), with a createdAt of at least `since` (unix milliseconds).
+    /// If a `cursor` is passed, starts right after the Commit that the cursor points to.
+    /// Only includes Commits that edit Resources inside `drive`, if it is passed.
+    /// Returns at most `limit` Commits, plus a cursor that points to the last one.
+    /// Use that cursor to get the next page, or to resume later on. An empty page means there are no newer Commits (yet).
+    pub fn get_commits_since(
+        &self,
+        since: i64,
+        cursor: Option<&str>,
+        drive: Option<&str>,
+        limit: usize,
+    ) -> AtomicResult<(Vec<IndexedCommit>, Option<String>)> {
+        // The cursor is the sequence number of the last Commit of the previous page
+        let start = match cursor {
+            Some(cursor) => {
+                let key = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+                    .map_err(|e| format!("Invalid cursor {}. {}", cursor, e))?;
+                if key.len() != 8 {
+                    return Err(format!("Invalid cursor {}.", cursor).into());
+                }
+                std::ops::Bound::Excluded(key)
+            }
+            None => std::ops::Bound::Unbounded,
+        };
+        let mut commits = Vec::new();
+        let mut last_key: Vec<u8> = Vec::new();
+        for item in self.index_arrival.range((start, std::ops::Bound::Unbounded)) {
+            let (key, value) = item?;
+            let commit = arrival_index_value_to_commit(&value)?;
+            if commit.created_at < since {
+                continue;
+            }
+            if let Some(drive) = drive {
+                if !crate::hierarchy::is_inside(&commit.subject, drive) {
+                    continue;
+                }
+            }
+            if commits.len() == limit {
+                break;
+            }
+            commits.push(commit);
+            last_key = key.to_vec();
+        }
+        let next = if last_key.is_empty() {
+            cursor.map(|c| c.to_string())
+        } else {
+            Some(base64::encode_config(last_key, base64::URL_SAFE_NO_PAD))
+        };
+        Ok((commits, next))
+    }
+
+    /// Returns the feed cursor of the last Commit that was mirrored from some remote Drive
+    pub fn get_mirror_cursor(&self, drive: &str) -> AtomicResult<Option<String>> {
+        let mirrors = self.db.open_tree("mirrors")?;
+        match mirrors.get(drive.as_bytes())? {
+            Some(cursor) => Ok(Some(String::from_utf8(cursor.to_vec())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores the feed cursor of the last Commit that was mirrored from some remote Drive
+    pub fn set_mirror_cursor(&self, drive: &str, cursor: &str) -> AtomicResult<()> {
+        let mirrors = self.db.open_tree("mirrors")?;
+        mirrors.insert(drive.as_bytes(), cursor.as_bytes())?;
+        Ok(())
+    }
+
+    /// Adds a Resource to the indexes, if it is a Commit.
+    fn index_commit(&self, resource: &Resource) -> AtomicResult<()> {
+        let is_commit = match resource.get(urls::IS_A) {
+            Ok(classes) => classes.to_vec()?.iter().any(|c| c == urls::COMMIT),
+            Err(_) => false,
+        };
+        if !is_commit {
+            return Ok(());
+        }
+        let signer = resource.get(urls::SIGNER)?.to_string();
+        let created_at = resource.get(urls::CREATED_AT)?.to_int()?;
+        let subject = resource.get(urls::SUBJECT)?.to_string();
+        let mut key = signer_index_prefix(&signer);
+        key.extend_from_slice(&timestamp_to_key(created_at));
+        key.extend_from_slice(resource.get_subject().as_bytes());
+        self.index_signer.insert(key, subject.as_bytes())?;
+        // Commits don't change, so a Commit that is added again keeps its place in the feed
+        if self.index_arrival_commits.contains_key(resource.get_subject())? {
+            return Ok(());
+        }
+        let sequence = self.db.generate_id()?.to_be_bytes();
+        let mut value = timestamp_to_key(created_at).to_vec();
+        value.extend_from_slice(resource.get_subject().as_bytes());
+        value.push(0);
+        value.extend_from_slice(subject.as_bytes());
+        self.index_arrival.insert(sequence, value)?;
+        self.index_arrival_commits
+            .insert(resource.get_subject().as_bytes(), &sequence)?;
+        Ok(())
+    }
+
+    /// Removes a Resource from the indexes, if it is a Commit.
+    fn remove_commit_from_index(&self, resource: &Resource) -> AtomicResult<()> {
+        if let (Ok(signer), Ok(created_at)) =
+            (resource.get(urls::SIGNER), resource.get(urls::CREATED_AT))
+        {
+            let mut key = signer_index_prefix(&signer.to_string());
+            key.extend_from_slice(&timestamp_to_key(created_at.to_int()?));
+            key.extend_from_slice(resource.get_subject().as_bytes());
+            self.index_signer.remove(key)?;
+        }
+        if let Some(sequence) = self.index_arrival_commits.remove(resource.get_subject())? {
+            self.index_arrival.remove(sequence)?;
+        }
+        Ok(())
+    }
+
+    /// Iterates over all Resources and adds the Commits to the indexes.
+    /// Useful for stores that were created before the indexes existed.
+    /// Commits that are not in the arrival index yet are added in createdAt order.
+    pub fn build_commit_index(&self) -> AtomicResult<()> {
+        let mut commits = Vec::new();
+        for resource in self.iter_resources(true) {
+            if let Ok(created_at) = resource.get(urls::CREATED_AT).and_then(|v| v.to_int()) {
+                commits.push((created_at, resource));
+            }
+        }
+        commits.sort_by_key(|(created_at, _resource)| *created_at);
+        for (_created_at, resource) in commits {
+            self.index_commit(&resource)?;
+        }
+        Ok(())
+    }
+
+    /// Updates the members of the materialized Collections after a Resource has changed, or has been removed if `resource` is None.
+    /// `changed` contains the Properties whose Values were added, changed or removed.
+    /// Only Collections that filter or sort by one of these Properties are read.
+    /// If the Resource is a materialized Collection itself, its members are removed, so they are rebuilt on the next request.
+    /// Collections that filter by `isA` also contain the instances of subclasses, so they are removed when the `subClassOf` of a Class changes.
+    /// Never fails, since the Resource has already been stored: Collections that can't be updated are removed, so they are rebuilt.
+    fn update_materialized(
+        &self,
+        subject: &str,
+        resource: Option<&Resource>,
+        changed: &HashSet<String>,
+    ) {
+        self.remove_materialized(subject);
+        // Collections only show items from inside this store, but can filter by external Classes
+        let is_local = subject.starts_with(&self.base_url);
+        let subclasses_changed = changed.contains(urls::SUBCLASS_OF);
+        if !is_local && !subclasses_changed {
+            return;
+        }
+        // Collections that can have any Property use an empty property in the index
+        let collections_for = |properties: &[&str]| -> HashSet<String> {
+            let mut collections = HashSet::new();
+            for property in properties {
+                let prefix = materialized_index_key(property, None);
+                for item in self.materialized_index.scan_prefix(&prefix) {
+                    match item {
+                        Ok((key, _value)) => {
+                            collections.insert(String::from_utf8_lossy(&key[prefix.len()..]).to_string());
+                        }
+                        Err(e) => log::error!("Could not read materialized Collections. {}", e),
+                    }
+                }
+            }
+            collections
+        };
+        let removed = if subclasses_changed {
+            let filter_classes = collections_for(&["", urls::IS_A]);
+            for collection in filter_classes.iter() {
+                self.remove_materialized(collection);
+            }
+            filter_classes
+        } else {
+            HashSet::new()
+        };
+        if !is_local || changed.is_empty() {
+            return;
+        }
+        let mut properties: Vec<&str> = vec![""];
+        properties.extend(changed.iter().map(|p| p.as_str()));
+        for collection in collections_for(&properties) {
+            if removed.contains(&collection) {
+                continue;
+            }
+            if let Err(e) = self.update_one_materialized(&collection, subject, resource) {
+                log::error!(
+                    "Removing materialized Collection {}, it is rebuilt on the next request. {}",
+                    collection,
+                    e
+                );
+                self.remove_materialized(&collection);
+            }
+        }
+    }
+
+    fn update_one_materialized(
+        &self,
+        collection: &str,
+        subject: &str,
+        resource: Option<&Resource>,
+    ) -> AtomicResult<()> {
+        let bin = self
+            .materialized
+            .get(collection.as_bytes())?
+            .ok_or("Materialized Collection is missing")?;
+        let mut materialized: Materialized = bincode::deserialize(&bin)
+            .map_err(|e| format!("Materialized Collection is corrupt. {}", e))?;
+        if materialized.update(subject, resource, self)? {
+            self.materialized
+                .insert(collection.as_bytes(), bincode::serialize(&materialized)?)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the members of a materialized Collection, so they are rebuilt on the next request
+    fn remove_materialized(&self, collection: &str) {
+        let removed = self
+            .remove_materialized_index(collection)
+            .and(self.materialized.remove(collection.as_bytes()).map_err(|e| e.into()));
+        if let Err(e) = removed {
+            log::error!("Could not remove materialized Collection {}. {}", collection, e);
+        }
+    }
+
+    /// Removes a materialized Collection from `materialized_props` and `materialized_index`
+    fn remove_materialized_index(&self, collection: &str) -> AtomicResult<()> {
+        if let Some(props_bin) = self.materialized_props.remove(collection.as_bytes())? {
+            // Corrupt Properties are only removed from the index when the Collection is materialized again
+            if let Ok(props) = bincode::deserialize::<Option<Vec<String>>>(&props_bin) {
+                for property in props.unwrap_or_else(|| vec![String::new()]) {
+                    self.materialized_index
+                        .remove(materialized_index_key(&property, Some(collection)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds or removes the Property / Value combinations of a Resource in the property and number indexes.
+    fn update_prop_index(&self, subject: &str, propvals: &PropVals, add: bool) -> AtomicResult<()> {
+        for (property, value) in propvals.iter() {
+            if let Some(number) = number_to_key(value) {
+                let key = number_index_key(property, Some(number), Some(subject));
+                if add {
+                    self.index_numbers.insert(key, &[])?;
+                } else {
+                    self.index_numbers.remove(key)?;
+                }
+            }
+            let mut values = vec![value.to_string()];
+            if let Value::ResourceArray(items) = value {
+                values.extend(items.iter().cloned());
+            }
+            for value in values {
+                let key = prop_index_key(property, Some(&value), Some(subject));
+                if add {
+                    self.index_props.insert(key, &[])?;
+                } else {
+                    self.index_props.remove(key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterates over all Resources and adds them to the property and number indexes.
+    pub fn build_prop_index(&self) -> AtomicResult<()> {
+        for resource in self.iter_resources(true) {
+            self.update_prop_index(resource.get_subject(), resource.get_propvals(), true)?;
+        }
+        Ok(())
+    }
+
     /// Internal method for fetching Resource data.
     fn set_propvals(&self, subject: &str, propvals: &PropVals) -> AtomicResult<()> {
         let resource_bin = bincode::serialize(propvals)?;
@@ -121,11 +559,18 @@ impl Storelike for Db {
         // This only works if no external functions rely on using add_resource for atom-like operations!
         // However, add_atom uses set_propvals, which skips the validation.
         resource.check_required_props(self)?;
-        self.set_propvals(resource.get_subject(), &resource.get_propvals())
+        self.add_resource_unsafe(resource)
     }
 
     fn add_resource_unsafe(&self, resource: &Resource) -> AtomicResult<()> {
-        self.set_propvals(resource.get_subject(), &resource.get_propvals())
+        self.index_commit(resource)?;
+        let old = self.get_propvals(resource.get_subject()).unwrap_or_default();
+        self.update_prop_index(resource.get_subject(), &old, false)?;
+        self.update_prop_index(resource.get_subject(), resource.get_propvals(), true)?;
+        self.set_propvals(resource.get_subject(), resource.get_propvals())?;
+        let changed = changed_properties(&old, resource.get_propvals());
+        self.update_materialized(resource.get_subject(), Some(resource), &changed);
+        Ok(())
     }
 
     fn get_base_url(&self) -> &str {
@@ -145,6 +590,13 @@ impl Storelike for Db {
         }
     }
 
+    fn has_resource(&self, subject: &str) -> bool {
+        match bincode::serialize(subject) {
+            Ok(key) => self.resources.contains_key(key).unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
     fn get_resource(&self, subject: &str) -> AtomicResult<Resource> {
         let propvals = self.get_propvals(subject);
 
@@ -157,7 +609,13 @@ impl Storelike for Db {
         }
     }
 
-    fn get_resource_extended(&self, subject: &str) -> AtomicResult<Resource> {
+    fn handle_commit(&self, commit_response: &crate::commit::CommitResponse) {
+        if let Some(on_commit) = &self.on_commit {
+            on_commit(commit_response);
+        }
+    }
+
+    fn get_resource_extended(&self, subject: &str, for_agent: Option<&str>) -> AtomicResult<Resource> {
         // This might add a trailing slash
         let mut url = url::Url::parse(subject)?;
         let clone = url.clone();
@@ -174,7 +632,7 @@ impl Storelike for Db {
         let mut endpoint_resource = None;
         endpoints.into_iter().for_each(|endpoint| {
             if url.path().starts_with(&endpoint.path) {
-                endpoint_resource = Some((endpoint.handle)(clone.clone(), self))
+                endpoint_resource = Some((endpoint.handle)(clone.clone(), self, for_agent))
             }
         });
 
@@ -192,6 +650,9 @@ impl Storelike for Db {
         for class in resource.get_classes(self)? {
             match class.subject.as_ref() {
                 crate::urls::COLLECTION => {
+                    if crate::plugins::commit_feed::is_commit_feed_request(&resource, &clone) {
+                        return crate::plugins::commit_feed::construct_commit_feed(self, &clone, &mut resource, for_agent)
+                    }
                     return crate::collections::construct_collection(self, query_params, &mut resource)
                 }
                 crate::urls::INVITE => {
@@ -207,20 +668,25 @@ impl Storelike for Db {
     }
 
     fn all_resources(&self, include_external: bool) -> ResourceCollection {
-        let mut resources: ResourceCollection = Vec::new();
+        self.iter_resources(include_external).collect()
+    }
+
+    /// Reads the Resources one by one from the resources tree.
+    fn iter_resources<'a>(
+        &'a self,
+        include_external: bool,
+    ) -> Box<dyn Iterator<Item = Resource> + 'a> {
         let self_url = self.get_self_url().expect("No self URL set, is required in DB");
-        for item in self.resources.into_iter() {
+        Box::new(self.resources.iter().filter_map(move |item| {
             let (subject, resource_bin) = item.expect(DB_CORRUPT_MSG);
             let subject: String = bincode::deserialize(&subject).expect(DB_CORRUPT_MSG);
             if !include_external && !subject.starts_with(&self_url) {
-                continue;
+                return None;
             }
             let propvals: PropVals = bincode::deserialize(&resource_bin)
                 .unwrap_or_else(|e| panic!("{}. {}", corrupt_db_message(&subject), e));
-            let resource = Resource::from_propvals(propvals, subject);
-            resources.push(resource);
-        }
-        resources
+            Some(Resource::from_propvals(propvals, subject))
+        }))
     }
 
     fn populate(&self) -> AtomicResult<()> {
@@ -228,7 +694,12 @@ impl Storelike for Db {
         crate::populate::populate_default_store(self)?;
         crate::populate::populate_hierarchy(self)?;
         crate::populate::populate_collections(self)?;
-        crate::populate::populate_endpoints(self)
+        crate::populate::populate_endpoints(self)?;
+        // Stores created by older versions don't have a commit index yet
+        if self.index_signer.is_empty() || self.index_arrival.is_empty() {
+            self.build_commit_index()?;
+        }
+        Ok(())
     }
 
     fn remove_resource(&self, subject: &str) -> AtomicResult<()> {
@@ -236,19 +707,312 @@ impl Storelike for Db {
         // https://github.com/joepio/atomic/issues/46
         let binary_subject = bincode::serialize(subject).unwrap();
         let found = self.resources.remove(&binary_subject)?;
-        if found.is_none() {
-            return Err(format!(
+        match found {
+            Some(binpropval) => {
+                let propvals: PropVals = bincode::deserialize(&binpropval)
+                    .map_err(|e| format!("{} {}", corrupt_db_message(subject), e))?;
+                self.update_prop_index(subject, &propvals, false)?;
+                self.update_materialized(subject, None, &propvals.keys().cloned().collect());
+                self.remove_commit_from_index(&Resource::from_propvals(propvals, subject.into()))
+            }
+            None => Err(format!(
                 "Resource {} could not be deleted, because it was not found in the store.",
                 subject
             )
-            .into());
+            .into()),
         }
-        Ok(())
     }
 
     fn set_default_agent(&self, agent: crate::agents::Agent) {
         self.default_agent.lock().unwrap().replace(agent);
     }
+
+    /// Uses the property index, so it does not have to iterate over all Resources.
+    fn subjects_with_propval(
+        &self,
+        property: &str,
+        value: Option<&str>,
+        include_external: bool,
+    ) -> AtomicResult<Vec<String>> {
+        let prefix = prop_index_key(property, value, None);
+        let self_url = self.get_self_url().ok_or("No self URL set, is required in DB")?;
+        let mut subjects: Vec<String> = Vec::new();
+        let mut found: HashSet<String> = HashSet::new();
+        for item in self.index_props.scan_prefix(&prefix) {
+            let (key, _value) = item?;
+            // The subject is everything after the last separator
+            let start = key.iter().rposition(|b| *b == 0).map(|i| i + 1).unwrap_or(0);
+            let subject = String::from_utf8(key[start..].to_vec())?;
+            if !include_external && !subject.starts_with(&self_url) {
+                continue;
+            }
+            if found.insert(subject.clone()) {
+                subjects.push(subject);
+            }
+        }
+        Ok(subjects)
+    }
+
+    fn get_classtype_mode(&self) -> ClasstypeMode {
+        self.classtype_mode
+    }
+
+    /// Corrupt members are removed and logged, so they are rebuilt.
+    fn get_materialized(&self, collection: &str) -> AtomicResult<Option<Materialized>> {
+        match self.materialized.get(collection.as_bytes())? {
+            Some(bin) => match bincode::deserialize(&bin) {
+                Ok(materialized) => Ok(Some(materialized)),
+                Err(e) => {
+                    log::error!(
+                        "Materialized Collection {} is corrupt, it is rebuilt. {}",
+                        collection,
+                        e
+                    );
+                    self.remove_materialized(collection);
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn set_materialized(&self, collection: &str, materialized: &Materialized) -> AtomicResult<()> {
+        // The Properties change when the Collection is redefined
+        self.remove_materialized_index(collection)?;
+        self.materialized
+            .insert(collection.as_bytes(), bincode::serialize(materialized)?)?;
+        let properties = materialized.properties();
+        self.materialized_props
+            .insert(collection.as_bytes(), bincode::serialize(&properties)?)?;
+        for property in properties.unwrap_or_else(|| vec![String::new()]) {
+            self.materialized_index
+                .insert(materialized_index_key(&property, Some(collection)), &[])?;
+        }
+        Ok(())
+    }
+
+    /// Scans the number index for numeric Values, and the property index for text and dates, so it only reads the matching Resources.
+    /// Atoms are ordered by their Value.
+    fn tpf_compare(
+        &self,
+        property: &str,
+        comparison: &Comparison,
+        include_external: bool,
+    ) -> AtomicResult<Vec<Atom>> {
+        let self_url = self.get_self_url().ok_or("No self URL set, is required in DB")?;
+        let mut subjects: Vec<String> = Vec::new();
+        if let Some(start) = number_to_key(&comparison.value) {
+            let prefix = number_index_key(property, None, None);
+            // Keys of a number are followed by a subject, so a key that ends in 0xFF comes after all of them
+            let after = |number: [u8; 8]| {
+                let mut key = number_index_key(property, Some(number), None);
+                key.push(u8::MAX);
+                key
+            };
+            let end = comparison.value_end.as_ref().and_then(number_to_key);
+            let range = match (&comparison.operator, end) {
+                (Operator::Gt, _) => self.index_numbers.range(after(start)..prefix_end(&prefix)),
+                (Operator::Lt, _) => self
+                    .index_numbers
+                    .range(prefix.clone()..number_index_key(property, Some(start), None)),
+                (Operator::Between, Some(end)) => self
+                    .index_numbers
+                    .range(number_index_key(property, Some(start), None)..after(end)),
+                (Operator::Equals, _) => self
+                    .index_numbers
+                    .range(number_index_key(property, Some(start), None)..after(start)),
+                _ => return Err(format!("The {} operator can't be used for numbers", comparison.operator.as_str()).into()),
+            };
+            for item in range {
+                let (key, _value) = item?;
+                subjects.push(String::from_utf8(key[prefix.len() + 8..].to_vec())?);
+            }
+        } else {
+            let query = comparison.value.to_string();
+            let prefix = prop_index_key(property, None, None);
+            // Values are followed by a null byte, so `{value}\x01` comes after all keys of that value, but before longer values
+            let after = |value: &str| {
+                let mut key = prop_index_key(property, None, None);
+                key.extend_from_slice(value.as_bytes());
+                key.push(1);
+                key
+            };
+            let iter = match &comparison.operator {
+                Operator::Gt => self.index_props.range(after(&query)..prefix_end(&prefix)),
+                Operator::Lt => self
+                    .index_props
+                    .range(prefix.clone()..prop_index_key(property, Some(&query), None)),
+                Operator::Between => {
+                    let end = comparison
+                        .value_end
+                        .as_ref()
+                        .ok_or("The between operator requires a second value")?
+                        .to_string();
+                    self.index_props
+                        .range(prop_index_key(property, Some(&query), None)..after(&end))
+                }
+                Operator::Equals => self.index_props.scan_prefix(prop_index_key(property, Some(&query), None)),
+                Operator::Prefix => {
+                    let mut start = prefix.clone();
+                    start.extend_from_slice(query.as_bytes());
+                    self.index_props.scan_prefix(start)
+                }
+                Operator::Contains => self.index_props.scan_prefix(&prefix),
+            };
+            for item in iter {
+                let (key, _value) = item?;
+                let separator = key.iter().rposition(|b| *b == 0).unwrap_or(0);
+                if comparison.operator == Operator::Contains {
+                    let value = String::from_utf8_lossy(&key[prefix.len()..separator]);
+                    if !value.contains(&query) {
+                        continue;
+                    }
+                }
+                subjects.push(String::from_utf8(key[separator + 1..].to_vec())?);
+            }
+        }
+        let mut atoms = Vec::new();
+        let mut found: HashSet<String> = HashSet::new();
+        for subject in subjects {
+            if !include_external && !subject.starts_with(&self_url) {
+                continue;
+            }
+            if !found.insert(subject.clone()) {
+                continue;
+            }
+            let resource = self.get_resource(&subject)?;
+            // The property index also contains the items of ResourceArrays, so check the actual Value
+            if let Ok(value) = resource.get(property) {
+                if comparison.matches(value) {
+                    atoms.push(Atom::new(subject, property.into(), value.clone()));
+                }
+            }
+        }
+        Ok(atoms)
+    }
+}
+
+/// A Commit as found in one of the Commit indexes
+#[derive(Clone, Debug)]
+pub struct IndexedCommit {
+    /// Subject of the Commit itself
+    pub commit: String,
+    /// Subject of the Resource that was changed by the Commit
+    pub subject: String,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+}
+
+/// Parses a value of the arrival index, see `Db::index_arrival`
+fn arrival_index_value_to_commit(value: &[u8]) -> AtomicResult<IndexedCommit> {
+    if value.len() < 8 {
+        return Err("Invalid value in the arrival index".into());
+    }
+    let mut ts = [0u8; 8];
+    ts.copy_from_slice(&value[..8]);
+    let rest = &value[8..];
+    let separator = rest
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or("Invalid value in the arrival index")?;
+    Ok(IndexedCommit {
+        commit: String::from_utf8(rest[..separator].to_vec())?,
+        subject: String::from_utf8(rest[separator + 1..].to_vec())?,
+        created_at: u64::from_be_bytes(ts) as i64,
+    })
+}
+
+/// All index keys for a signer start with this prefix. The null byte can't be part of a URL.
+fn signer_index_prefix(signer: &str) -> Vec<u8> {
+    let mut prefix = signer.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+/// Key of the property index. Leave out the value and subject to get a prefix for scanning.
+/// The null byte separates the parts, since it can't be part of a URL.
+fn prop_index_key(property: &str, value: Option<&str>, subject: Option<&str>) -> Vec<u8> {
+    let mut key = property.as_bytes().to_vec();
+    key.push(0);
+    if let Some(value) = value {
+        key.extend_from_slice(value.as_bytes());
+        key.push(0);
+        if let Some(subject) = subject {
+            key.extend_from_slice(subject.as_bytes());
+        }
+    }
+    key
+}
+
+/// Key of the materialized Collections index. Leave out the Collection to get a prefix for scanning.
+fn materialized_index_key(property: &str, collection: Option<&str>) -> Vec<u8> {
+    let mut key = property.as_bytes().to_vec();
+    key.push(0);
+    if let Some(collection) = collection {
+        key.extend_from_slice(collection.as_bytes());
+    }
+    key
+}
+
+/// Key of the number index. Leave out the number and subject to get a prefix for scanning.
+fn number_index_key(property: &str, number: Option<[u8; 8]>, subject: Option<&str>) -> Vec<u8> {
+    let mut key = property.as_bytes().to_vec();
+    key.push(0);
+    if let Some(number) = number {
+        key.extend_from_slice(&number);
+        if let Some(subject) = subject {
+            key.extend_from_slice(subject.as_bytes());
+        }
+    }
+    key
+}
+
+/// Converts Integers, Timestamps and Floats to bytes that sort in the same order as the numbers themselves.
+/// A Property has a single DataType, so Integers and Floats are never compared to each other.
+fn number_to_key(value: &Value) -> Option<[u8; 8]> {
+    match value {
+        // Flipping the sign bit puts negative numbers before positive ones
+        Value::Integer(i) | Value::Timestamp(i) => Some(((*i as u64) ^ (1 << 63)).to_be_bytes()),
+        Value::Float(f) => {
+            let bits = f.to_bits();
+            // Negative floats sort in reverse order, so flip all their bits
+            let ordered = if bits >> 63 == 1 { !bits } else { bits | (1 << 63) };
+            Some(ordered.to_be_bytes())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the first key after all keys that start with the prefix, which ends with a null byte.
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    if let Some(last) = end.last_mut() {
+        *last = 1;
+    }
+    end
+}
+
+/// Big endian bytes sort in the same order as the timestamps themselves.
+fn timestamp_to_key(timestamp: i64) -> [u8; 8] {
+    (timestamp.max(0) as u64).to_be_bytes()
+}
+
+/// The Properties whose Values differ between two versions of a Resource
+fn changed_properties(old: &PropVals, new: &PropVals) -> HashSet<String> {
+    let mut changed: HashSet<String> = old
+        .iter()
+        .filter(|(prop, val)| match new.get(*prop) {
+            Some(new_val) => new_val.to_string() != val.to_string(),
+            None => true,
+        })
+        .map(|(prop, _)| prop.clone())
+        .collect();
+    changed.extend(
+        new.keys()
+            .filter(|prop| !old.contains_key(*prop))
+            .cloned(),
+    );
+    changed
 }
 
 fn corrupt_db_message(subject: &str) -> String {
@@ -346,12 +1110,541 @@ mod test {
         println!("{:?}", store.all_resources(false));
         let collections_collection_url = format!("{}/collections", store.get_base_url());
         let my_resource = store
-            .get_resource_extended(&collections_collection_url)
+            .get_resource_extended(&collections_collection_url, None)
             .unwrap();
         let my_value = my_resource
             .get(crate::urls::COLLECTION_MEMBER_COUNT)
             .unwrap();
         println!("My value: {}", my_value);
-        assert_eq!(my_value.to_string(), "11");
+        assert_eq!(my_value.to_string(), "12");
+    }
+
+    #[test]
+    fn handle_commit() {
+        let mut store = DB.lock().unwrap().clone();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        store.set_handle_commit(Box::new(move |response| {
+            received_clone
+                .lock()
+                .unwrap()
+                .push(response.commit.subject.clone());
+        }));
+        let agent = store.get_default_agent().unwrap();
+        let subject = format!("{}/handled", store.get_base_url());
+        let mut commitbuilder = crate::commit::CommitBuilder::new(subject.clone());
+        commitbuilder.set(
+            crate::urls::DESCRIPTION.into(),
+            crate::Value::Markdown("handled".into()),
+        );
+        let commit = commitbuilder.sign(&agent, &store).unwrap();
+        commit.apply_opts(&store, false, true, true, false).unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![subject]);
+    }
+
+    #[test]
+    fn audit_log() {
+        let store = DB.lock().unwrap().clone();
+        let agent = store.create_agent(Some("auditor")).unwrap();
+        let subject = format!("{}/audited", store.get_base_url());
+        let mut commitbuilder = crate::commit::CommitBuilder::new(subject.clone());
+        commitbuilder.set(
+            crate::urls::DESCRIPTION.into(),
+            crate::Value::Markdown("audited".into()),
+        );
+        let commit = commitbuilder.sign(&agent, &store).unwrap();
+        commit.apply_opts(&store, false, true, true, false).unwrap();
+        let found = store
+            .get_commits_by_signer(&agent.subject, None, None)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].subject, subject);
+        let too_late = store
+            .get_commits_by_signer(&agent.subject, Some(commit.created_at + 1), None)
+            .unwrap();
+        assert!(too_late.is_empty());
+
+        let audit_url = format!(
+            "{}/audit?signer={}",
+            store.get_base_url(),
+            urlencoding::encode(&agent.subject)
+        );
+        // Requires authentication and write rights on the drive
+        store.get_resource_extended(&audit_url, None).unwrap_err();
+        store
+            .get_resource_extended(&audit_url, Some(&agent.subject))
+            .unwrap_err();
+        let mut drive = Resource::new(store.get_base_url().into());
+        drive
+            .set_propval(
+                crate::urls::WRITE.into(),
+                vec![agent.subject.clone()].into(),
+                &store,
+            )
+            .unwrap();
+        store.add_resource_unsafe(&drive).unwrap();
+        let log = store
+            .get_resource_extended(&audit_url, Some(&agent.subject))
+            .unwrap();
+        let members = log
+            .get(crate::urls::COLLECTION_MEMBERS)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        assert_eq!(members, &vec![found[0].commit.clone()]);
+
+        // Pages are read from the index, newest first
+        let mut commitbuilder = crate::commit::CommitBuilder::new(subject.clone());
+        commitbuilder.set(
+            crate::urls::DESCRIPTION.into(),
+            crate::Value::Markdown("audited again".into()),
+        );
+        let mut newer = commitbuilder.sign(&agent, &store).unwrap();
+        newer.created_at = commit.created_at + 1;
+        newer.apply_unsafe(&store).unwrap();
+        let (first, cursor) = store
+            .get_commits_by_signer_page(&agent.subject, None, None, None, None, 1)
+            .unwrap();
+        assert_eq!(first[0].created_at, newer.created_at);
+        let (second, last_cursor) = store
+            .get_commits_by_signer_page(&agent.subject, None, None, None, cursor.as_deref(), 1)
+            .unwrap();
+        assert_eq!(second[0].commit, found[0].commit);
+        assert!(last_cursor.is_none());
+        let log = store
+            .get_resource_extended(&format!("{}&page_size=1", audit_url), Some(&agent.subject))
+            .unwrap();
+        let next = log.get(crate::urls::COLLECTION_NEXT).unwrap().to_string();
+        let older = store
+            .get_resource_extended(&next, Some(&agent.subject))
+            .unwrap();
+        let members = older
+            .get(crate::urls::COLLECTION_MEMBERS)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        assert_eq!(members, &vec![found[0].commit.clone()]);
+        assert!(older.get(crate::urls::COLLECTION_NEXT).is_err());
+    }
+
+    #[test]
+    fn commit_feed() {
+        let store = DB.lock().unwrap().clone();
+        let agent = store.create_agent(Some("feeder")).unwrap();
+        let drive = format!("{}/feed", store.get_base_url());
+        let mut commits = Vec::new();
+        for i in 0..2 {
+            let mut commitbuilder =
+                crate::commit::CommitBuilder::new(format!("{}/item{}", drive, i));
+            commitbuilder.set(
+                crate::urls::DESCRIPTION.into(),
+                crate::Value::Markdown("fed".into()),
+            );
+            let commit = commitbuilder.sign(&agent, &store).unwrap();
+            commit.apply_opts(&store, false, true, true, false).unwrap();
+            commits.push(
+                commit
+                    .clone()
+                    .into_resource(&store)
+                    .unwrap()
+                    .get_subject()
+                    .clone(),
+            );
+        }
+        let (first, cursor) = store.get_commits_since(0, None, Some(&drive), 1).unwrap();
+        assert_eq!(first.len(), 1);
+        let (second, last_cursor) = store
+            .get_commits_since(0, cursor.as_deref(), Some(&drive), 1)
+            .unwrap();
+        assert_eq!(second.len(), 1);
+        // The last cursor can be used to resume later on
+        let (none_yet, same_cursor) = store
+            .get_commits_since(0, last_cursor.as_deref(), Some(&drive), 1)
+            .unwrap();
+        assert!(none_yet.is_empty());
+        assert_eq!(same_cursor, last_cursor);
+        let mut found = vec![first[0].commit.clone(), second[0].commit.clone()];
+        found.sort();
+        commits.sort();
+        assert_eq!(found, commits);
+        // Commits that arrive late are still shown after the cursor, even though they were created earlier
+        let mut commitbuilder = crate::commit::CommitBuilder::new(format!("{}/late", drive));
+        commitbuilder.set(
+            crate::urls::DESCRIPTION.into(),
+            crate::Value::Markdown("late".into()),
+        );
+        let mut late = commitbuilder.sign(&agent, &store).unwrap();
+        late.created_at = 1;
+        late.apply_unsafe(&store).unwrap();
+        let (late_found, _cursor) = store
+            .get_commits_since(0, last_cursor.as_deref(), Some(&drive), 1)
+            .unwrap();
+        assert_eq!(late_found[0].subject, format!("{}/late", drive));
+        assert_eq!(late_found[0].created_at, 1);
+        // `since` filters by createdAt
+        let (recent, _cursor) = store
+            .get_commits_since(2, last_cursor.as_deref(), Some(&drive), 1)
+            .unwrap();
+        assert!(recent.is_empty());
+
+        let feed_url = format!(
+            "{}/commits?drive={}&page_size=1",
+            store.get_base_url(),
+            urlencoding::encode(&drive)
+        );
+        // Requires read rights on the drive
+        store
+            .get_resource_extended(&feed_url, Some(&agent.subject))
+            .unwrap_err();
+        let mut drive_resource = Resource::new(drive.clone());
+        drive_resource
+            .set_propval(
+                crate::urls::READ.into(),
+                vec![agent.subject.clone()].into(),
+                &store,
+            )
+            .unwrap();
+        store.add_resource_unsafe(&drive_resource).unwrap();
+        let feed = store
+            .get_resource_extended(&feed_url, Some(&agent.subject))
+            .unwrap();
+        let members = feed
+            .get(crate::urls::COLLECTION_MEMBERS)
+            .unwrap()
+            .to_vec()
+            .unwrap();
+        assert_eq!(members, &vec![first[0].commit.clone()]);
+        assert!(feed.get(crate::urls::COLLECTION_NEXT).is_ok());
+    }
+
+    #[test]
+    fn collection_filters() {
+        let store = DB.lock().unwrap().clone();
+        let agent = "https://localhost/agents/filter";
+        for (i, (status, readers)) in [
+            ("open", vec![agent]),
+            ("open", vec![]),
+            ("closed", vec![agent]),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut resource = Resource::new(format!("https://localhost/filtered/{}", i));
+            resource
+                .set_propval_unsafe(urls::DESCRIPTION.into(), Value::Markdown((*status).into()))
+                .unwrap();
+            let readers: Vec<String> = readers.iter().map(|r| r.to_string()).collect();
+            resource
+                .set_propval_unsafe(urls::READ.into(), readers.into())
+                .unwrap();
+            store.add_resource_unsafe(&resource).unwrap();
+        }
+        let members = |description: Option<&str>, filters: Vec<crate::collections::Filter>, match_any: bool| {
+            let builder = crate::collections::CollectionBuilder {
+                subject: "https://localhost/filtered".into(),
+                property: description.map(|_| urls::DESCRIPTION.into()),
+                value: description.map(|d| d.into()),
+                filters,
+                match_any,
+                sort_by: None,
+                sort_desc: false,
+                sort_then: Vec::new(),
+                nulls_first: false,
+                include: false,
+                include_nested: false,
+                materialized: false,
+                after: None,
+                before: None,
+                current_page: 0,
+                page_size: 10,
+                name: None,
+            };
+            let mut members = builder.into_collection(&store).unwrap().members;
+            members.sort();
+            members
+        };
+        let open = crate::collections::Filter::from_query_param(&format!("{} open", urls::DESCRIPTION));
+        let readable = crate::collections::Filter::from_query_param(&format!("{} {}", urls::READ, agent));
+        assert_eq!(
+            members(None, vec![open.clone(), readable.clone()], false),
+            vec!["https://localhost/filtered/0"]
+        );
+        assert_eq!(members(None, vec![open.clone(), readable.clone()], true).len(), 3);
+        // The property and value of the Collection always apply, match_any only combines the other filters
+        assert_eq!(
+            members(Some("closed"), vec![open.clone(), readable.clone()], true),
+            vec!["https://localhost/filtered/2"]
+        );
+
+        // The index is updated when a Resource changes
+        let mut changed = store.get_resource("https://localhost/filtered/1").unwrap();
+        changed
+            .set_propval_unsafe(urls::READ.into(), vec![agent.to_string()].into())
+            .unwrap();
+        store.add_resource_unsafe(&changed).unwrap();
+        store.remove_resource("https://localhost/filtered/0").unwrap();
+        assert_eq!(
+            members(None, vec![open, readable], false),
+            vec!["https://localhost/filtered/1"]
+        );
+        // Filters without a value only require the Property to be present
+        let has_read = crate::collections::Filter::from_query_param(urls::READ);
+        assert!(has_read.value.is_none());
+        assert_eq!(
+            store
+                .subjects_with_propval(urls::READ, None, false)
+                .unwrap()
+                .iter()
+                .filter(|s| s.starts_with("https://localhost/filtered/"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn range_filters() {
+        use crate::comparison::{Comparison, Operator};
+        let store = DB.lock().unwrap().clone();
+        for (i, (size, shortname)) in [(-20, "alpha"), (3, "beta"), (10, "alphabet"), (200, "gamma")]
+            .iter()
+            .enumerate()
+        {
+            let mut resource = Resource::new(format!("https://localhost/ranged/{}", i));
+            resource
+                .set_propval_unsafe(urls::COLLECTION_PAGE_SIZE.into(), Value::Integer(*size))
+                .unwrap();
+            resource
+                .set_propval_unsafe(urls::SHORTNAME.into(), Value::Slug((*shortname).into()))
+                .unwrap();
+            resource
+                .set_propval_unsafe(urls::DESCRIPTION.into(), Value::Markdown("ranged".into()))
+                .unwrap();
+            store.add_resource_unsafe(&resource).unwrap();
+        }
+        let subjects = |property: &str, operator: Operator, value: &str, end: Option<&str>| {
+            let datatype = store.get_property(property).unwrap().data_type;
+            let comparison = Comparison::new(operator, value, end, &datatype).unwrap();
+            let mut subjects: Vec<String> = store
+                .tpf_compare(property, &comparison, false)
+                .unwrap()
+                .into_iter()
+                .map(|atom| atom.subject)
+                .filter(|s| s.starts_with("https://localhost/ranged/"))
+                .collect();
+            subjects.sort();
+            subjects
+        };
+        let ranged = |i: usize| format!("https://localhost/ranged/{}", i);
+        // Numbers are compared numerically, including negative ones
+        assert_eq!(subjects(urls::COLLECTION_PAGE_SIZE, Operator::Gt, "3", None), vec![ranged(2), ranged(3)]);
+        assert_eq!(subjects(urls::COLLECTION_PAGE_SIZE, Operator::Lt, "3", None), vec![ranged(0)]);
+        assert_eq!(
+            subjects(urls::COLLECTION_PAGE_SIZE, Operator::Between, "-20", Some("10")),
+            vec![ranged(0), ranged(1), ranged(2)]
+        );
+        assert_eq!(subjects(urls::SHORTNAME, Operator::Prefix, "alpha", None), vec![ranged(0), ranged(2)]);
+        assert_eq!(subjects(urls::SHORTNAME, Operator::Gt, "alpha", None), vec![ranged(1), ranged(2), ranged(3)]);
+        assert_eq!(subjects(urls::SHORTNAME, Operator::Contains, "mm", None), vec![ranged(3)]);
+        // The same Comparisons can be used in Collection filters
+        let members = |filter: String| {
+            let builder = crate::collections::CollectionBuilder {
+                subject: "https://localhost/ranged".into(),
+                property: Some(urls::DESCRIPTION.into()),
+                value: Some("ranged".into()),
+                filters: vec![crate::collections::Filter::from_query_param(&filter)],
+                match_any: false,
+                sort_by: None,
+                sort_desc: false,
+                sort_then: Vec::new(),
+                nulls_first: false,
+                include: false,
+                include_nested: false,
+                materialized: false,
+                after: None,
+                before: None,
+                current_page: 0,
+                page_size: 10,
+                name: None,
+            };
+            builder.into_collection(&store).map(|c| c.members)
+        };
+        assert_eq!(
+            members(format!("{}~between~0~100", urls::COLLECTION_PAGE_SIZE)).unwrap(),
+            vec![ranged(1), ranged(2)]
+        );
+        // Operators are validated against the DataType of the Property
+        assert!(members(format!("{}~prefix~1", urls::COLLECTION_PAGE_SIZE)).is_err());
+        // Without tildes, an operator is just part of the Value
+        assert!(members(format!("{} gt 3", urls::COLLECTION_PAGE_SIZE)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn materialized_collection() {
+        let store = DB.lock().unwrap().clone();
+        let prefix = "https://localhost/materialized";
+        let add = |id: &str, shortname: &str| {
+            let mut resource = Resource::new(format!("{}/{}", prefix, id));
+            resource
+                .set_propval_unsafe(urls::SHORTNAME.into(), Value::Slug(shortname.into()))
+                .unwrap();
+            resource
+                .set_propval_unsafe(urls::DESCRIPTION.into(), Value::Markdown("materialized".into()))
+                .unwrap();
+            store.add_resource_unsafe(&resource).unwrap();
+        };
+        add("c", "c");
+        add("a", "a");
+        add("b", "b");
+        let builder = |sort_desc: bool| crate::collections::CollectionBuilder {
+            subject: format!("{}?current_page=0", prefix),
+            property: Some(urls::DESCRIPTION.into()),
+            value: Some("materialized".into()),
+            filters: Vec::new(),
+            match_any: false,
+            sort_by: Some(urls::SHORTNAME.into()),
+            sort_desc,
+            sort_then: Vec::new(),
+            nulls_first: false,
+            include: false,
+            include_nested: false,
+            materialized: true,
+            after: None,
+            before: None,
+            current_page: 0,
+            page_size: 10,
+            name: None,
+        };
+        let subject = |id: &str| format!("{}/{}", prefix, id);
+        let stored = || {
+            let materialized = store.get_materialized(prefix).unwrap().unwrap();
+            materialized
+                .subjects()
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>()
+        };
+        let members = builder(false).into_collection(&store).unwrap().members;
+        assert_eq!(members, vec![subject("a"), subject("b"), subject("c")]);
+        assert_eq!(stored(), members);
+        // Only the Properties of the query are indexed
+        let indexed = |property: &str| {
+            store
+                .materialized_index
+                .contains_key(materialized_index_key(property, Some(prefix)))
+                .unwrap()
+        };
+        assert!(indexed(urls::DESCRIPTION) && indexed(urls::SHORTNAME));
+        assert!(!indexed(urls::NAME) && !indexed(""));
+
+        // Changed Resources are moved to their new position, without running the query again
+        add("d", "aa");
+        add("c", "0");
+        store.remove_resource(&subject("b")).unwrap();
+        let expected = vec![subject("c"), subject("a"), subject("d")];
+        assert_eq!(stored(), expected);
+        assert_eq!(builder(false).into_collection(&store).unwrap().members, expected);
+
+        // A different definition rebuilds the members
+        let reversed = builder(true).into_collection(&store).unwrap().members;
+        assert_eq!(reversed, vec![subject("d"), subject("a"), subject("c")]);
+        assert_eq!(stored(), reversed);
+
+        // Corrupt members don't make writes fail, they are removed and rebuilt
+        store.materialized.insert(prefix.as_bytes(), &b"corrupt"[..]).unwrap();
+        add("e", "e");
+        assert!(store.get_materialized(prefix).unwrap().is_none());
+        assert!(!indexed(urls::DESCRIPTION));
+        let rebuilt = builder(true).into_collection(&store).unwrap().members;
+        assert_eq!(rebuilt, vec![subject("e"), subject("d"), subject("a"), subject("c")]);
+    }
+
+    #[test]
+    fn materialized_subclasses() {
+        let store = DB.lock().unwrap().clone();
+        let prefix = "https://localhost/subclassed";
+        let class = |id: &str, subclass_of: Option<&str>| {
+            let mut resource = Resource::new(format!("{}/{}", prefix, id));
+            resource
+                .set_propval_unsafe(urls::IS_A.into(), Value::ResourceArray(vec![urls::CLASS.into()]))
+                .unwrap();
+            if let Some(parent) = subclass_of {
+                resource
+                    .set_propval_unsafe(
+                        urls::SUBCLASS_OF.into(),
+                        Value::ResourceArray(vec![format!("{}/{}", prefix, parent)]),
+                    )
+                    .unwrap();
+            }
+            store.add_resource_unsafe(&resource).unwrap();
+        };
+        let instance = |id: &str, class: &str| {
+            let mut resource = Resource::new(format!("{}/{}", prefix, id));
+            resource
+                .set_propval_unsafe(
+                    urls::IS_A.into(),
+                    Value::ResourceArray(vec![format!("{}/{}", prefix, class)]),
+                )
+                .unwrap();
+            store.add_resource_unsafe(&resource).unwrap();
+        };
+        class("animal", None);
+        class("dog", None);
+        instance("cat", "animal");
+        instance("rex", "dog");
+        let collection = format!("{}/animals", prefix);
+        let members = || {
+            crate::collections::CollectionBuilder {
+                subject: collection.clone(),
+                property: Some(urls::IS_A.into()),
+                value: Some(format!("{}/animal", prefix)),
+                filters: Vec::new(),
+                match_any: false,
+                sort_by: None,
+                sort_desc: false,
+                sort_then: Vec::new(),
+                nulls_first: false,
+                include: false,
+                include_nested: false,
+                materialized: true,
+                after: None,
+                before: None,
+                current_page: 0,
+                page_size: 10,
+                name: None,
+            }
+            .into_collection(&store)
+            .unwrap()
+            .members
+        };
+        assert_eq!(members(), vec![format!("{}/cat", prefix)]);
+        assert!(store.get_materialized(&collection).unwrap().is_some());
+
+        // The instances of a new subclass are members too
+        class("dog", Some("animal"));
+        assert!(store.get_materialized(&collection).unwrap().is_none());
+        assert_eq!(members(), vec![format!("{}/cat", prefix), format!("{}/rex", prefix)]);
+    }
+
+    #[test]
+    fn validate_dangling_reference() {
+        let store = DB.lock().unwrap().clone();
+        let mut resource = Resource::new("https://localhost/dangling".into());
+        resource
+            .set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(store.get_base_url().into()))
+            .unwrap();
+        let mut validator = crate::validate::Validator::new(&store, false);
+        assert!(validator.validate_resource(&resource).is_empty());
+        resource
+            .set_propval_unsafe(
+                urls::PARENT.into(),
+                Value::AtomicUrl("https://localhost/removed".into()),
+            )
+            .unwrap();
+        let issues = validator.validate_resource(&resource);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert_eq!(
+            issues[0].kind,
+            crate::validate::IssueKind::DanglingReference
+        );
     }
 }
@@ -5,6 +5,8 @@
 use crate::{
     errors::AtomicResult,
     plugins::{
+        aggregate::aggregate_endpoint,
+        audit::audit_endpoint,
         path::path_endpoint,
         versioning::{all_versions_endpoint, version_endpoint},
     },
@@ -15,8 +17,9 @@ use crate::{
 pub struct Endpoint {
     /// The part behind the server domain, e.g. '/versions' or '/collections'. Include the slash.
     pub path: String,
-    /// The function that is called when the request matches the path
-    pub handle: fn(subject: url::Url, store: &Db) -> AtomicResult<Resource>,
+    /// The function that is called when the request matches the path.
+    /// `for_agent` is the authenticated Agent that performs the request, if any.
+    pub handle: fn(subject: url::Url, store: &Db, for_agent: Option<&str>) -> AtomicResult<Resource>,
     /// The list of properties that can be passed to the Endpoint as Query parameters
     pub params: Vec<String>,
     pub description: String,
@@ -46,6 +49,8 @@ pub fn default_endpoints() -> Vec<Endpoint> {
     vec![
         version_endpoint(),
         all_versions_endpoint(),
-        path_endpoint()
+        path_endpoint(),
+        audit_endpoint(),
+        aggregate_endpoint(),
     ]
 }
@@ -1,6 +1,8 @@
 //! The Hierarchy model describes how Resources are structed in a tree-like shape.
 //! It dealt with authorization (read / write grants)
 
+use std::collections::HashSet;
+
 use crate::{Resource, Storelike, errors::AtomicResult, urls};
 
 /// Looks for children relations, adds to the resource. Performs a TPF query, might be expensive.
@@ -22,23 +24,110 @@ pub fn check_write(
   store: &impl Storelike,
   resource: &Resource,
   agent: String,
+) -> AtomicResult<bool> {
+  check_write_visited(store, resource, &agent, &mut HashSet::new())
+}
+
+/// Like `check_write`, but stops at parents that have already been checked.
+fn check_write_visited(
+  store: &impl Storelike,
+  resource: &Resource,
+  agent: &str,
+  visited: &mut HashSet<String>,
 ) -> AtomicResult<bool> {
   // Check if the resource's write rights explicitly refers to the agent
   if let Ok(arr_val) = resource.get(urls::WRITE) {
-    if arr_val.to_vec()?.contains(&agent) {
+    if arr_val.to_vec()?.iter().any(|writer| writer == agent) {
       return Ok(true)
     };
   }
+  visited.insert(resource.get_subject().clone());
   // Try the parents recursively
   if let Ok(val) = resource.get(urls::PARENT) {
-    let parent = store.get_resource(&val.to_string())?;
-    check_write(store, &parent, agent)
+    let parent_subject = val.to_string();
+    // Prevent endless loops for resources that are their own parent, or circular hierarchies
+    if visited.contains(&parent_subject) {
+      return Ok(false);
+    }
+    let parent = store.get_resource(&parent_subject)?;
+    check_write_visited(store, &parent, agent, visited)
   } else {
     // resource has no parent and agent is not in Write array - check fails
     Ok(false)
   }
 }
 
+/// Recursively checks a Resource and its Parents for read rights.
+/// Resources that can be read by the Public Agent can be read by anyone.
+pub fn check_read(
+  store: &impl Storelike,
+  resource: &Resource,
+  agent: Option<&str>,
+) -> AtomicResult<bool> {
+  if let Ok(arr_val) = resource.get(urls::READ) {
+    let readers = arr_val.to_vec()?;
+    if readers.iter().any(|r| r == urls::PUBLIC_AGENT || Some(r.as_str()) == agent) {
+      return Ok(true);
+    };
+  }
+  // Try the parents recursively
+  if let Ok(val) = resource.get(urls::PARENT) {
+    let parent_subject = val.to_string();
+    // Prevent endless loops for resources that are their own parent
+    if &parent_subject == resource.get_subject() {
+      return Ok(false);
+    }
+    let parent = store.get_resource(&parent_subject)?;
+    check_read(store, &parent, agent)
+  } else {
+    Ok(false)
+  }
+}
+
+/// Checks if the URL is `parent` itself, or a path below it.
+/// Unlike `starts_with`, `https://example.com/drive2` is not inside `https://example.com/drive`.
+pub fn is_inside(url: &str, parent: &str) -> bool {
+  match url.strip_prefix(parent) {
+    Some(rest) => rest.is_empty() || parent.ends_with('/') || rest.starts_with(['/', '?', '#']),
+    None => false,
+  }
+}
+
+/// Finds the Drive of a Resource, which is the top-most Resource in its parent hierarchy.
+/// For Commits, this is the Drive of the Resource that the Commit edits.
+/// Returns the subject of the Resource itself if it has no parent.
+pub fn find_drive(store: &impl Storelike, resource: &Resource) -> AtomicResult<String> {
+  if let Ok(target) = resource.get(urls::SUBJECT) {
+    if resource.get(urls::SIGNER).is_ok() {
+      return match store.get_resource(&target.to_string()) {
+        Ok(target_resource) => find_drive(store, &target_resource),
+        // The target has been destroyed, or is not in this store
+        Err(_) => Ok(store.get_base_url().into()),
+      };
+    }
+  }
+  let mut current = resource.get_subject().clone();
+  let mut parent = resource.get(urls::PARENT).ok().map(|v| v.to_string());
+  let mut visited = vec![current.clone()];
+  while let Some(parent_subject) = parent {
+    // A Drive that is edited by a Commit gets itself as its default parent
+    if parent_subject == current {
+      break;
+    }
+    if visited.contains(&parent_subject) {
+      return Err(format!("Circular parent hierarchy in {}", resource.get_subject()).into());
+    }
+    parent = store
+      .get_resource(&parent_subject)?
+      .get(urls::PARENT)
+      .ok()
+      .map(|v| v.to_string());
+    visited.push(parent_subject.clone());
+    current = parent_subject;
+  }
+  Ok(current)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -49,6 +138,68 @@ mod test {
     // - basic check_write (should be false for newly created agent)
     // - Malicious Commit (which grants itself write rights)
 
+    #[test]
+    fn find_drive_for_nested_resource() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let drive = Resource::new("https://localhost".into());
+        store.add_resource_unsafe(&drive).unwrap();
+        let mut folder = Resource::new("https://localhost/folder".into());
+        folder.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(drive.get_subject().into())).unwrap();
+        store.add_resource_unsafe(&folder).unwrap();
+        let mut item = Resource::new("https://localhost/folder/item".into());
+        item.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(folder.get_subject().into())).unwrap();
+        assert_eq!(find_drive(&store, &item).unwrap(), "https://localhost");
+        assert_eq!(find_drive(&store, &drive).unwrap(), "https://localhost");
+        let mut own_parent = drive.clone();
+        own_parent.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(drive.get_subject().into())).unwrap();
+        store.add_resource_unsafe(&own_parent).unwrap();
+        assert_eq!(find_drive(&store, &item).unwrap(), "https://localhost");
+    }
+
+    #[test]
+    fn check_read_rights() {
+        let store = crate::Store::init().unwrap();
+        let agent = "https://localhost/agents/reader";
+        let mut drive = Resource::new("https://localhost".into());
+        drive.set_propval_unsafe(urls::READ.into(), vec![agent.to_string()].into()).unwrap();
+        store.add_resource_unsafe(&drive).unwrap();
+        let mut item = Resource::new("https://localhost/item".into());
+        item.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(drive.get_subject().into())).unwrap();
+        assert!(check_read(&store, &item, Some(agent)).unwrap());
+        assert!(!check_read(&store, &item, None).unwrap());
+        item.set_propval_unsafe(urls::READ.into(), vec![urls::PUBLIC_AGENT.to_string()].into()).unwrap();
+        assert!(check_read(&store, &item, None).unwrap());
+    }
+
+    #[test]
+    fn inside_drive() {
+        assert!(is_inside("https://a.com/drive", "https://a.com/drive"));
+        assert!(is_inside("https://a.com/drive/item", "https://a.com/drive"));
+        assert!(is_inside("https://a.com/drive2", "https://a.com/"));
+        assert!(!is_inside("https://a.com/drive2", "https://a.com/drive"));
+    }
+
+    #[test]
+    fn check_write_circular_parents() {
+        let store = crate::Store::init().unwrap();
+        let agent = "https://localhost/agents/writer";
+        let mut drive = Resource::new("https://localhost".into());
+        drive.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(drive.get_subject().into())).unwrap();
+        store.add_resource_unsafe(&drive).unwrap();
+        assert!(!check_write(&store, &drive, agent.into()).unwrap());
+        let mut a = Resource::new("https://localhost/a".into());
+        a.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl("https://localhost/b".into())).unwrap();
+        store.add_resource_unsafe(&a).unwrap();
+        let mut b = Resource::new("https://localhost/b".into());
+        b.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(a.get_subject().into())).unwrap();
+        store.add_resource_unsafe(&b).unwrap();
+        assert!(!check_write(&store, &a, agent.into()).unwrap());
+        b.set_propval_unsafe(urls::WRITE.into(), vec![agent.to_string()].into()).unwrap();
+        store.add_resource_unsafe(&b).unwrap();
+        assert!(check_write(&store, &a, agent.into()).unwrap());
+    }
+
     #[test]
     fn authorization() {
         let store = crate::Store::init().unwrap();
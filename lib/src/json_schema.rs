@@ -0,0 +1,387 @@
+//! Converts Atomic Classes to [JSON Schema](https://json-schema.org/) documents, and back.
+//! Useful for sharing data models with tools that don't speak Atomic Data.
+//! Objects are described by the shortnames of their Properties, like the plain JSON serialization.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::{
+    datatype::DataType,
+    errors::AtomicResult,
+    schema::{Class, Constraints, Property},
+    values::SLUG_REGEX,
+    Resource, Storelike,
+};
+
+/// The JSON Schema dialect of exported documents
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Non-standard `format` for Timestamps (milliseconds since the Unix epoch), so they can be distinguished from Integers
+const TIMESTAMP_FORMAT: &str = "unix-time-ms";
+
+/// Generates a JSON Schema for the instances of a Class, including the Properties it inherits.
+/// Every Property schema has the Property URL as its `$id`.
+pub fn class_to_json_schema(class: &Class, store: &impl Storelike) -> AtomicResult<JsonValue> {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for (prop_subject, is_required) in class
+        .requires
+        .iter()
+        .map(|p| (p, true))
+        .chain(class.recommends.iter().map(|p| (p, false)))
+    {
+        let property = store.get_property(prop_subject)?;
+        if is_required {
+            required.push(JsonValue::String(property.shortname.clone()));
+        }
+        properties.insert(
+            property.shortname.clone(),
+            property_to_json_schema(&property),
+        );
+    }
+    Ok(json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "$id": class.subject,
+        "title": class.shortname,
+        "description": class.description,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    }))
+}
+
+/// Generates the JSON Schema of a single Property, by mapping its DataType and Constraints.
+pub fn property_to_json_schema(property: &Property) -> JsonValue {
+    let mut schema = match &property.data_type {
+        DataType::AtomicUrl => json!({"type": "string", "format": "uri"}),
+        DataType::Boolean => json!({"type": "boolean"}),
+        DataType::Date => json!({"type": "string", "format": "date"}),
+        DataType::Integer => json!({"type": "integer"}),
+        DataType::Float => json!({"type": "number"}),
+        DataType::Markdown => json!({"type": "string", "contentMediaType": "text/markdown"}),
+        DataType::ResourceArray => {
+            json!({"type": "array", "items": {"type": "string", "format": "uri"}})
+        }
+        DataType::Slug => json!({"type": "string", "pattern": SLUG_REGEX}),
+        DataType::String => json!({"type": "string"}),
+        DataType::Timestamp => json!({"type": "integer", "format": TIMESTAMP_FORMAT}),
+        // Any JSON value
+        DataType::Unsupported(_) => json!({}),
+    };
+    let map = schema.as_object_mut().unwrap();
+    map.insert("$id".into(), property.subject.clone().into());
+    map.insert("description".into(), property.description.clone().into());
+    let constraints = &property.constraints;
+    if let Some(min) = constraints.min_value {
+        map.insert("minimum".into(), json!(min));
+    }
+    if let Some(max) = constraints.max_value {
+        map.insert("maximum".into(), json!(max));
+    }
+    let is_array = property.data_type == DataType::ResourceArray;
+    if let Some(min) = constraints.min_length {
+        let key = if is_array { "minItems" } else { "minLength" };
+        map.insert(key.into(), json!(min));
+    }
+    if let Some(max) = constraints.max_length {
+        let key = if is_array { "maxItems" } else { "maxLength" };
+        map.insert(key.into(), json!(max));
+    }
+    if let Some(pattern) = &constraints.pattern {
+        if is_array {
+            map["items"]["pattern"] = pattern.clone().into();
+        } else {
+            map.insert("pattern".into(), pattern.clone().into());
+        }
+    }
+    if let Some(allowed) = &constraints.allowed_values {
+        let allowed = JsonValue::from(allowed.clone());
+        if is_array {
+            map["items"]["enum"] = allowed;
+        } else {
+            map.insert("enum".into(), allowed);
+        }
+    }
+    schema
+}
+
+/// Creates a draft Class and its Properties from a JSON Schema of an object.
+/// The Class is named after the `title` of the schema.
+/// New Resources get subjects like `{base_url}/classes/{title}` and `{base_url}/properties/{name}`.
+/// Properties with an `$id` that is already a known Property are re-used instead of created.
+/// Returns the Class first, followed by the new Properties. Nothing is saved to the store.
+pub fn import_json_schema(
+    schema: &JsonValue,
+    base_url: &str,
+    store: &impl Storelike,
+) -> AtomicResult<Vec<Resource>> {
+    let title = schema
+        .get("title")
+        .and_then(|t| t.as_str())
+        .ok_or("The JSON Schema needs a title, which is used as the shortname of the Class")?;
+    let class_shortname = to_shortname(title)?;
+    let required: Vec<&str> = match schema.get("required").and_then(|r| r.as_array()) {
+        Some(required) => required.iter().filter_map(|r| r.as_str()).collect(),
+        None => Vec::new(),
+    };
+    let mut class = Class {
+        requires: Vec::new(),
+        recommends: Vec::new(),
+        shortname: class_shortname.clone(),
+        description: description(schema, title),
+        subject: format!("{}/classes/{}", base_url, class_shortname),
+        subclass_of: Vec::new(),
+    };
+    let mut new_properties = Vec::new();
+    let mut required_subjects = HashMap::new();
+    if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+        for (name, prop_schema) in properties {
+            let existing = prop_schema
+                .get("$id")
+                .and_then(|id| id.as_str())
+                .and_then(|id| store.get_property(id).ok());
+            let subject = match existing {
+                Some(property) => property.subject,
+                None => {
+                    let property = json_schema_to_property(name, prop_schema, base_url)?;
+                    let subject = property.subject.clone();
+                    new_properties.push(property.to_resource()?);
+                    subject
+                }
+            };
+            if required.contains(&name.as_str()) {
+                required_subjects.insert(name.as_str(), subject);
+            } else {
+                class.recommends.push(subject);
+            }
+        }
+    }
+    // Keep the order of the `required` list
+    class.requires = required
+        .iter()
+        .filter_map(|name| required_subjects.remove(name))
+        .collect();
+    let mut resources = vec![class.to_resource()?];
+    resources.extend(new_properties);
+    Ok(resources)
+}
+
+/// Converts the schema of a single field into a Property, by mapping its type, format and constraints.
+fn json_schema_to_property(
+    name: &str,
+    schema: &JsonValue,
+    base_url: &str,
+) -> AtomicResult<Property> {
+    let get_str = |key: &str| schema.get(key).and_then(|v| v.as_str());
+    let get_usize = |key: &str| schema.get(key).and_then(|v| v.as_u64()).map(|v| v as usize);
+    // Nullable fields have multiple types, such as ["string", "null"]
+    let json_type = match schema.get("type") {
+        Some(JsonValue::String(t)) => t.as_str(),
+        Some(JsonValue::Array(types)) => types
+            .iter()
+            .filter_map(|t| t.as_str())
+            .find(|t| *t != "null")
+            .unwrap_or("null"),
+        _ => return Err(format!("Field {} has no type", name).into()),
+    };
+    let pattern = get_str("pattern");
+    let data_type = match (json_type, get_str("format")) {
+        ("string", Some("uri")) | ("string", Some("iri")) => DataType::AtomicUrl,
+        ("string", Some("date")) => DataType::Date,
+        ("string", _) if get_str("contentMediaType") == Some("text/markdown") => DataType::Markdown,
+        ("string", _) if pattern == Some(SLUG_REGEX) => DataType::Slug,
+        ("string", _) => DataType::String,
+        ("integer", Some(TIMESTAMP_FORMAT)) => DataType::Timestamp,
+        ("integer", _) => DataType::Integer,
+        ("number", _) => DataType::Float,
+        ("boolean", _) => DataType::Boolean,
+        ("array", _) if is_uri_array(schema) => DataType::ResourceArray,
+        ("array", _) => {
+            return Err(format!(
+                "Field {} is an array, but only arrays of URLs (with `\"items\": {{\"type\": \"string\", \"format\": \"uri\"}}`) can be converted to a Property",
+                name
+            )
+            .into())
+        }
+        (other, _) => {
+            return Err(format!(
+                "Field {} has type {}, which can't be converted to a Property",
+                name, other
+            )
+            .into())
+        }
+    };
+    let is_array = data_type == DataType::ResourceArray;
+    // Arrays describe the pattern and allowed values of their items
+    let values_schema = if is_array {
+        schema.get("items").unwrap_or(&JsonValue::Null)
+    } else {
+        schema
+    };
+    let constraints = Constraints {
+        min_value: schema.get("minimum").and_then(|v| v.as_f64()),
+        max_value: schema.get("maximum").and_then(|v| v.as_f64()),
+        min_length: get_usize(if is_array { "minItems" } else { "minLength" }),
+        max_length: get_usize(if is_array { "maxItems" } else { "maxLength" }),
+        pattern: values_schema
+            .get("pattern")
+            .and_then(|p| p.as_str())
+            .filter(|p| data_type != DataType::Slug || *p != SLUG_REGEX)
+            .map(|p| p.to_string()),
+        allowed_values: values_schema
+            .get("enum")
+            .and_then(|e| e.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|item| match item {
+                        JsonValue::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect()
+            }),
+    };
+    let shortname = to_shortname(name)?;
+    Ok(Property {
+        class_type: None,
+        data_type,
+        description: description(schema, name),
+        subject: format!("{}/properties/{}", base_url, shortname),
+        shortname,
+        constraints,
+    })
+}
+
+/// Checks if the items of an array schema are URLs, which is the only kind of array that Atomic Data supports
+fn is_uri_array(schema: &JsonValue) -> bool {
+    let items = match schema.get("items") {
+        Some(items) => items,
+        None => return false,
+    };
+    let is_string = match items.get("type") {
+        Some(JsonValue::String(t)) => t == "string",
+        Some(JsonValue::Array(types)) => types.iter().all(|t| t == "string" || t == "null"),
+        _ => false,
+    };
+    is_string && matches!(items.get("format").and_then(|f| f.as_str()), Some("uri") | Some("iri"))
+}
+
+fn description(schema: &JsonValue, name: &str) -> String {
+    match schema.get("description").and_then(|d| d.as_str()) {
+        Some(description) => description.into(),
+        None => format!("{} (imported from JSON Schema)", name),
+    }
+}
+
+/// Converts a name such as `firstName` or `first_name` to a valid shortname: `first-name`.
+/// Fails if the name has no letters or digits.
+fn to_shortname(name: &str) -> AtomicResult<String> {
+    let mut shortname = String::new();
+    let mut previous_lowercase = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && previous_lowercase {
+                shortname.push('-');
+            }
+            shortname.push(c.to_ascii_lowercase());
+            previous_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+        } else {
+            if !shortname.is_empty() && !shortname.ends_with('-') {
+                shortname.push('-');
+            }
+            previous_lowercase = false;
+        }
+    }
+    let shortname = shortname.trim_end_matches('-').to_string();
+    if shortname.is_empty() {
+        return Err(format!("Can't convert {:?} to a shortname, it has no letters or digits", name).into());
+    }
+    Ok(shortname)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{urls, Store};
+
+    #[test]
+    fn export_and_import() {
+        let store = Store::init().unwrap();
+        store.populate().unwrap();
+        let class = store.get_class(urls::CLASS).unwrap();
+        let schema = class_to_json_schema(&class, &store).unwrap();
+        assert_eq!(schema["properties"]["shortname"]["pattern"], SLUG_REGEX);
+        assert_eq!(schema["properties"]["requires"]["type"], "array");
+        assert_eq!(schema["required"], json!(["shortname", "description"]));
+
+        // Known Properties are re-used
+        let resources = import_json_schema(&schema, "https://localhost", &store).unwrap();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(
+            resources[0].get_subject(),
+            "https://localhost/classes/class"
+        );
+        assert_eq!(
+            resources[0].get(urls::REQUIRES).unwrap().to_vec().unwrap(),
+            &vec![urls::SHORTNAME.to_string(), urls::DESCRIPTION.into()]
+        );
+
+        let person = json!({
+            "title": "Person",
+            "type": "object",
+            "required": ["firstName"],
+            "properties": {
+                "firstName": {"type": "string", "maxLength": 50},
+                "birthDate": {"type": "string", "format": "date"},
+                "status": {"type": ["string", "null"], "enum": ["active", "retired"]},
+                "age": {"type": "integer", "minimum": 0}
+            }
+        });
+        let resources = import_json_schema(&person, "https://localhost", &store).unwrap();
+        assert_eq!(resources.len(), 5);
+        let first_name = Property::from_resource(resources[0].clone());
+        assert!(first_name.is_err(), "The Class comes first");
+        let property = |shortname: &str| {
+            let resource = resources
+                .iter()
+                .find(|r| r.get_subject() == &format!("https://localhost/properties/{}", shortname))
+                .unwrap();
+            Property::from_resource(resource.clone()).unwrap()
+        };
+        assert_eq!(property("first-name").constraints.max_length, Some(50));
+        assert_eq!(property("birth-date").data_type, DataType::Date);
+        assert_eq!(
+            property("status").constraints.allowed_values,
+            Some(vec!["active".into(), "retired".into()])
+        );
+        assert_eq!(property("age").constraints.min_value, Some(0.0));
+        // Exporting the imported Properties gives back the same constraints
+        assert_eq!(
+            property_to_json_schema(&property("age"))["minimum"],
+            json!(0.0)
+        );
+    }
+
+    #[test]
+    fn import_unsupported() {
+        let store = Store::init().unwrap();
+        store.populate().unwrap();
+        let import = |properties: JsonValue| {
+            let schema = json!({"title": "Thing", "type": "object", "properties": properties});
+            import_json_schema(&schema, "https://localhost", &store)
+        };
+        let links = import(json!({"links": {"type": "array", "items": {"type": "string", "format": "uri"}}})).unwrap();
+        assert_eq!(
+            Property::from_resource(links[1].clone()).unwrap().data_type,
+            DataType::ResourceArray
+        );
+        // Only arrays of URLs can be converted
+        assert!(import(json!({"tags": {"type": "array", "items": {"type": "string"}}})).is_err());
+        assert!(import(json!({"tags": {"type": "array"}})).is_err());
+        // Names need letters or digits
+        assert!(import(json!({"!?": {"type": "string"}})).is_err());
+        let schema = json!({"title": "---", "type": "object"});
+        assert!(import_json_schema(&schema, "https://localhost", &store).is_err());
+    }
+}
@@ -42,8 +42,11 @@ assert!(fetched_new_resource.get_shortname("description", &store).unwrap().to_st
 
 pub mod agents;
 pub mod atoms;
+pub mod authentication;
 pub mod client;
+pub mod codegen;
 pub mod collections;
+pub mod comparison;
 pub mod commit;
 pub mod datetime_helpers;
 #[cfg(feature = "db")]
@@ -55,13 +58,19 @@ pub mod errors;
 #[cfg(feature = "db")]
 pub mod endpoints;
 pub mod hierarchy;
+pub mod json_schema;
 pub mod mapping;
+pub mod merge;
+#[cfg(feature = "db")]
+pub mod mirror;
 pub mod parse;
 #[cfg(feature = "db")]
 pub mod plugins;
 pub mod populate;
+pub mod queue;
 pub mod resources;
 pub mod schema;
+pub mod serde_resource;
 pub mod serialize;
 pub mod store;
 pub mod storelike;
@@ -0,0 +1,425 @@
+//! Merges concurrent Commits, property by property.
+//! Two Commits are concurrent if they are based on the same previous Commit (see `CommitBuilder::set_previous_commit`).
+//! Changes to different properties are both kept.
+//! Concurrent changes to a ResourceArray are merged: items that were added by either Commit are kept, and items that were removed by either Commit are removed.
+//! For other properties, the value of the latest Commit (by `created_at`, then by signature) is kept, and the property is added to the `conflicts` of the Resource.
+//! Since the outcome does not depend on the order in which Commits are applied, every store that has the same Commits ends up with the same Resource.
+
+use std::{cmp::Ordering, collections::HashSet};
+
+use crate::{errors::AtomicResult, urls, Commit, Resource, Storelike, Value};
+
+/// Orders Commits by their `created_at`, and then by their signature.
+/// This is the order in which concurrent changes are resolved.
+pub fn compare(a: &Commit, b: &Commit) -> Ordering {
+    a.created_at
+        .cmp(&b.created_at)
+        .then_with(|| a.signature.cmp(&b.signature))
+}
+
+/// Returns true if Commit `a` wins from Commit `b` when they conflict.
+pub fn is_later(a: &Commit, b: &Commit) -> bool {
+    compare(a, b) == Ordering::Greater
+}
+
+/// Returns the signature part of a Commit URL.
+/// The same Commit can have different URLs on different servers (e.g. when it is applied locally before it is sent), but its signature is the same.
+fn commit_signature(commit_url: &str) -> &str {
+    commit_url.rsplit("/commits/").next().unwrap_or(commit_url)
+}
+
+/// Returns true if the Commit URL refers to the Commit.
+fn is_commit(commit: &Commit, commit_url: &str) -> bool {
+    commit.signature.as_deref() == Some(commit_signature(commit_url))
+}
+
+/// Sorts Commits by `compare`, but always puts a Commit after its previous Commit.
+/// Commits can be created in the same millisecond, so their timestamps alone can't be trusted for this.
+fn sort_commits(mut commits: Vec<Commit>) -> Vec<Commit> {
+    commits.sort_by(compare);
+    let known: HashSet<String> = commits.iter().filter_map(|c| c.signature.clone()).collect();
+    let mut sorted: Vec<Commit> = Vec::new();
+    let mut placed: HashSet<String> = HashSet::new();
+    while !commits.is_empty() {
+        let ready = commits.iter().position(|c| match &c.previous_commit {
+            Some(previous) => {
+                let signature = commit_signature(previous);
+                !known.contains(signature) || placed.contains(signature)
+            }
+            None => true,
+        });
+        // Only happens when Commits refer to each other in a cycle
+        let commit = commits.remove(ready.unwrap_or(0));
+        if let Some(signature) = &commit.signature {
+            placed.insert(signature.clone());
+        }
+        sorted.push(commit);
+    }
+    sorted
+}
+
+/// Searches the local store for all commits with this subject
+pub fn get_commits_for_resource(
+    subject: &str,
+    store: &impl Storelike,
+) -> AtomicResult<Vec<Commit>> {
+    let commit_atoms = store.tpf(None, Some(urls::SUBJECT), Some(subject), false)?;
+    let mut commit_resources = Vec::new();
+    for atom in commit_atoms {
+        let commit = Commit::from_resource(store.get_resource(&atom.subject)?)?;
+        commit_resources.push(commit)
+    }
+    Ok(commit_resources)
+}
+
+/// Applies the changes of the Commit to the current version of the Resource.
+/// If the Commit is based on an older version, the Commits that were applied since are merged with it.
+/// Falls back to overwriting the values if the Commit has no previous Commit, or if that Commit is not in the store.
+pub fn apply_merged(
+    commit: &Commit,
+    resource: Resource,
+    store: &impl Storelike,
+) -> AtomicResult<Resource> {
+    let previous = match &commit.previous_commit {
+        Some(previous) => previous,
+        None => return commit.apply_changes(resource, store),
+    };
+    match resource.get(urls::LAST_COMMIT) {
+        Ok(last) if commit_signature(&last.to_string()) != commit_signature(previous) => {}
+        _ => return commit.apply_changes(resource, store),
+    };
+    let commits = sort_commits(get_commits_for_resource(&commit.subject, store)?);
+    let index = match commits.iter().position(|c| is_commit(c, previous)) {
+        Some(index) => index,
+        None => return commit.apply_changes(resource, store),
+    };
+    let previous_commit = commits[index].clone();
+    // The same Commits that `replay` considers to be concurrent
+    let concurrent: Vec<Commit> = commits
+        .into_iter()
+        .skip(index + 1)
+        .filter(|c| c.signature != commit.signature)
+        .collect();
+    let base = replay(&commit.subject, previous_commit.url.as_deref(), store)?;
+    merge_changes(commit, resource, &base, &concurrent, store)
+}
+
+/// Applies the changes of the Commit to a Resource that already contains the changes of the concurrent Commits.
+/// `base` is the version that all these Commits are based on.
+fn merge_changes(
+    commit: &Commit,
+    mut resource: Resource,
+    base: &Resource,
+    concurrent: &[Commit],
+    store: &impl Storelike,
+) -> AtomicResult<Resource> {
+    let sets = |c: &Commit, prop: &str| c.set.as_ref().and_then(|set| set.get(prop)).is_some();
+    let removes = |c: &Commit, prop: &str| {
+        c.remove
+            .as_ref()
+            .map(|remove| remove.iter().any(|p| p == prop))
+            .unwrap_or(false)
+    };
+    let mut conflicts: Vec<String> = Vec::new();
+    if let Some(set) = &commit.set {
+        for (prop, value) in set.iter() {
+            let others: Vec<&Commit> = concurrent
+                .iter()
+                .filter(|c| sets(c, prop) || removes(c, prop))
+                .collect();
+            if others.is_empty() {
+                resource.set_propval(prop.into(), value.clone(), store)?;
+                continue;
+            }
+            let wins = others.iter().all(|c| is_later(commit, c));
+            if let Value::ResourceArray(ours) = value {
+                if others.iter().all(|c| sets(c, prop)) {
+                    let theirs = array_of(&resource, prop);
+                    let merged = merge_arrays(&array_of(base, prop), ours, &theirs, wins);
+                    resource.set_propval(prop.into(), merged.into(), store)?;
+                    continue;
+                }
+            }
+            let same = others.iter().all(|c| {
+                c.set
+                    .as_ref()
+                    .and_then(|set| set.get(prop))
+                    .map(|theirs| theirs.to_string() == value.to_string())
+                    .unwrap_or(false)
+            });
+            if !same {
+                conflicts.push(prop.clone());
+            }
+            if wins {
+                resource.set_propval(prop.into(), value.clone(), store)?;
+            }
+        }
+    }
+    if let Some(remove) = &commit.remove {
+        for prop in remove.iter() {
+            let others: Vec<&Commit> = concurrent.iter().filter(|c| sets(c, prop)).collect();
+            if !others.is_empty() {
+                conflicts.push(prop.clone());
+            }
+            if others.iter().all(|c| is_later(commit, c)) {
+                resource.remove_propval(prop);
+            }
+        }
+    }
+    if !conflicts.is_empty() {
+        let mut all = array_of(&resource, urls::CONFLICTS);
+        all.extend(conflicts);
+        all.sort();
+        all.dedup();
+        resource.set_propval(urls::CONFLICTS.into(), all.into(), store)?;
+    }
+    Ok(resource)
+}
+
+fn array_of(resource: &Resource, prop: &str) -> Vec<String> {
+    match resource.get(prop) {
+        Ok(Value::ResourceArray(items)) => items.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Three-way merge of two ResourceArrays.
+/// Keeps the items that were added by either side, and drops the base items that were removed by either side.
+/// The items of the winning side come first, followed by the new items of the other side.
+fn merge_arrays(
+    base: &[String],
+    ours: &[String],
+    theirs: &[String],
+    ours_wins: bool,
+) -> Vec<String> {
+    let (first, second) = if ours_wins {
+        (ours, theirs)
+    } else {
+        (theirs, ours)
+    };
+    let keep =
+        |item: &String| !base.contains(item) || (ours.contains(item) && theirs.contains(item));
+    let mut merged: Vec<String> = Vec::new();
+    for item in first.iter().chain(second.iter()) {
+        if keep(item) && !merged.contains(item) {
+            merged.push(item.clone());
+        }
+    }
+    merged
+}
+
+/// Sets the `lastCommit` of the Resource to the Commit, unless a later Commit has already been applied to it.
+/// A Commit always replaces its previous Commit, even if they were created in the same millisecond.
+pub fn set_last_commit(
+    resource: &mut Resource,
+    commit: &Commit,
+    commit_url: &str,
+    store: &impl Storelike,
+) -> AtomicResult<()> {
+    if let Ok(last) = resource.get(urls::LAST_COMMIT) {
+        let is_previous = commit
+            .previous_commit
+            .as_ref()
+            .map(|previous| commit_signature(previous) == commit_signature(&last.to_string()))
+            .unwrap_or(false);
+        if is_previous {
+            return resource.set_propval_unsafe(
+                urls::LAST_COMMIT.into(),
+                Value::AtomicUrl(commit_url.into()),
+            );
+        }
+        if let Ok(last_resource) = store.get_resource(&last.to_string()) {
+            if is_later(&Commit::from_resource(last_resource)?, commit) {
+                return Ok(());
+            }
+        }
+    }
+    resource.set_propval_unsafe(
+        urls::LAST_COMMIT.into(),
+        Value::AtomicUrl(commit_url.into()),
+    )
+}
+
+/// Constructs the Resource from all its Commits in the store, merging the concurrent ones.
+/// Stops after applying the Commit with URL `until`, if it is passed.
+pub fn replay(
+    subject: &str,
+    until: Option<&str>,
+    store: &impl Storelike,
+) -> AtomicResult<Resource> {
+    let commits = sort_commits(get_commits_for_resource(subject, store)?);
+    let mut version = Resource::new(subject.into());
+    // Every applied Commit, with the version that it resulted in
+    let mut applied: Vec<(Commit, Resource)> = Vec::new();
+    for commit in commits {
+        let url = match commit.url.clone() {
+            Some(url) => url,
+            None => continue,
+        };
+        let concurrent_since = match (&commit.previous_commit, applied.last()) {
+            (Some(previous), Some((last, _))) if !is_commit(last, previous) => applied
+                .iter()
+                .position(|(applied_commit, _)| is_commit(applied_commit, previous)),
+            _ => None,
+        };
+        version = match concurrent_since {
+            Some(index) => {
+                let base = applied[index].1.clone();
+                let concurrent: Vec<Commit> = applied[index + 1..]
+                    .iter()
+                    .map(|(c, _)| c.clone())
+                    .collect();
+                merge_changes(&commit, version, &base, &concurrent, store)?
+            }
+            None => commit.apply_changes(version, store)?,
+        };
+        version.set_propval_unsafe(urls::LAST_COMMIT.into(), Value::AtomicUrl(url.clone()))?;
+        let is_target = until
+            .map(|target| is_commit(&commit, target))
+            .unwrap_or(false);
+        applied.push((commit, version.clone()));
+        if is_target {
+            break;
+        }
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commit::CommitBuilder, Store};
+
+    #[test]
+    fn concurrent_commits_converge() {
+        let store_a = Store::init().unwrap();
+        store_a.populate().unwrap();
+        let agent = store_a.create_agent(Some("merger")).unwrap();
+        let store_b = Store::init().unwrap();
+        store_b.populate().unwrap();
+        store_b
+            .add_resource(&store_a.get_resource(&agent.subject).unwrap())
+            .unwrap();
+
+        let subject = "http://localhost/merged";
+        let mut first = CommitBuilder::new(subject.into());
+        first.set(urls::DESCRIPTION.into(), Value::Markdown("first".into()));
+        first.set(
+            urls::READ.into(),
+            vec!["http://localhost/a".to_string()].into(),
+        );
+        let first = first.sign(&agent, &store_a).unwrap();
+        let first_url = first.clone().into_resource(&store_a).unwrap();
+        let first_url = first_url.get_subject();
+
+        // Two concurrent Commits, both based on the first one
+        let mut left = CommitBuilder::new(subject.into());
+        left.set_previous_commit(first_url.clone());
+        left.set(urls::DESCRIPTION.into(), Value::Markdown("left".into()));
+        left.set(
+            urls::READ.into(),
+            vec![
+                "http://localhost/a".to_string(),
+                "http://localhost/b".to_string(),
+            ]
+            .into(),
+        );
+        let left = left.sign(&agent, &store_a).unwrap();
+        let mut right = CommitBuilder::new(subject.into());
+        right.set_previous_commit(first_url.clone());
+        right.set(urls::DESCRIPTION.into(), Value::Markdown("right".into()));
+        right.set(
+            urls::READ.into(),
+            vec!["http://localhost/c".to_string()].into(),
+        );
+        right.set(urls::SHORTNAME.into(), Value::Slug("right".into()));
+        let right = right.sign(&agent, &store_a).unwrap();
+        let latest = if is_later(&right, &left) {
+            "right"
+        } else {
+            "left"
+        };
+
+        for commit in [&first, &left, &right].iter() {
+            commit.apply(&store_a).unwrap();
+        }
+        for commit in [&first, &right, &left].iter() {
+            commit.apply(&store_b).unwrap();
+        }
+        let resource_a = store_a.get_resource(subject).unwrap();
+        let resource_b = store_b.get_resource(subject).unwrap();
+        // The latest Commit wins the conflicting value, the other changes are kept
+        assert_eq!(
+            resource_a.get(urls::DESCRIPTION).unwrap().to_string(),
+            latest
+        );
+        assert_eq!(
+            resource_a.get(urls::SHORTNAME).unwrap().to_string(),
+            "right"
+        );
+        // "a" was removed by the right Commit, "b" and "c" were added
+        let mut read = resource_a
+            .get(urls::READ)
+            .unwrap()
+            .to_vec()
+            .unwrap()
+            .clone();
+        read.sort();
+        assert_eq!(read, vec!["http://localhost/b", "http://localhost/c"]);
+        assert_eq!(
+            resource_a.get(urls::CONFLICTS).unwrap().to_vec().unwrap(),
+            &vec![urls::DESCRIPTION.to_string()]
+        );
+        for prop in [
+            urls::DESCRIPTION,
+            urls::READ,
+            urls::SHORTNAME,
+            urls::CONFLICTS,
+            urls::LAST_COMMIT,
+        ]
+        .iter()
+        {
+            assert_eq!(
+                resource_a.get(prop).unwrap().to_string(),
+                resource_b.get(prop).unwrap().to_string(),
+                "{} differs",
+                prop
+            );
+        }
+
+        // Versions are constructed in the same way
+        let version = replay(subject, None, &store_b).unwrap();
+        for prop in [urls::DESCRIPTION, urls::READ, urls::CONFLICTS].iter() {
+            assert_eq!(
+                version.get(prop).unwrap().to_string(),
+                resource_a.get(prop).unwrap().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn commits_follow_their_previous_commit() {
+        let commit = |signature: &str, previous: Option<&str>| Commit {
+            subject: "http://localhost/sorted".into(),
+            // Created in the same millisecond
+            created_at: 1,
+            signer: "http://localhost/agent".into(),
+            set: None,
+            remove: None,
+            destroy: None,
+            previous_commit: previous.map(|p| format!("http://localhost/commits/{}", p)),
+            signature: Some(signature.into()),
+            url: Some(format!("http://localhost/commits/{}", signature)),
+        };
+        let sorted = sort_commits(vec![
+            commit("c", None),
+            commit("a", Some("c")),
+            commit("b", Some("a")),
+        ]);
+        let signatures: Vec<&str> = sorted
+            .iter()
+            .map(|c| c.signature.as_deref().unwrap())
+            .collect();
+        assert_eq!(signatures, vec!["c", "a", "b"]);
+    }
+}
@@ -0,0 +1,179 @@
+//! Mirroring follows a Drive on some other Atomic Server, and keeps a read-only copy of it in the Db.
+//! It pulls the signed Commits from the Commit feed (`/commits?drive=`) of the origin server.
+//! The signature of every Commit is verified again, so the origin can't forge the Commits of other Agents.
+//! The rights and the schema are not checked again, for these the mirror trusts the origin.
+//! The mirrored Resources keep their original URLs.
+
+use crate::{
+    agents::Agent, client, errors::AtomicResult, urls, Commit, Db, Resource, Storelike, Value,
+};
+
+/// Amount of Commits that are fetched per request
+const PAGE_SIZE: usize = 100;
+
+/// Pulls the new Commits of the remote Drive, and applies them to the store.
+/// Continues where the previous sync stopped.
+/// Signs the requests with the Agent, which needs read rights on the remote Drive.
+/// Returns the amount of applied Commits.
+pub fn sync_drive(store: &Db, drive: &str, agent: Option<&Agent>) -> AtomicResult<usize> {
+    let origin = drive_origin(drive)?;
+    let mut cursor = store.get_mirror_cursor(drive)?;
+    if cursor.is_none() {
+        // The Drive itself is often not created by a Commit, but its rights are needed by the mirror
+        let drive_resource = client::fetch_resource_with_agent(drive, store, agent)?;
+        store.add_resource_unsafe(&drive_resource)?;
+    }
+    let mut applied = 0;
+    loop {
+        let mut feed_url = url::Url::parse(&format!("{}/commits", origin))?;
+        feed_url
+            .query_pairs_mut()
+            .append_pair("drive", drive)
+            .append_pair("page_size", &PAGE_SIZE.to_string());
+        if let Some(cursor) = &cursor {
+            feed_url.query_pairs_mut().append_pair("cursor", cursor);
+        }
+        let feed = client::fetch_resource_with_agent(feed_url.as_str(), store, agent)?;
+        let members = match feed.get(urls::COLLECTION_MEMBERS) {
+            Ok(members) => members.to_vec()?.clone(),
+            Err(_) => Vec::new(),
+        };
+        for commit_subject in members.iter() {
+            let commit_resource = client::fetch_resource_with_agent(commit_subject, store, agent)?;
+            apply_mirrored_commit(store, drive, commit_resource)?;
+            applied += 1;
+        }
+        let next_cursor = match feed.get(urls::COLLECTION_NEXT) {
+            Ok(next) => url::Url::parse(&next.to_string())?
+                .query_pairs()
+                .find(|(k, _v)| k == "cursor")
+                .map(|(_k, v)| v.to_string()),
+            Err(_) => None,
+        };
+        if let Some(next_cursor) = next_cursor {
+            store.set_mirror_cursor(drive, &next_cursor)?;
+            cursor = Some(next_cursor);
+        }
+        if members.is_empty() || cursor.is_none() {
+            return Ok(applied);
+        }
+    }
+}
+
+/// Verifies and applies a Commit that was fetched from the origin of the Drive.
+/// The Commit is stored under its original URL.
+pub fn apply_mirrored_commit(
+    store: &Db,
+    drive: &str,
+    commit_resource: Resource,
+) -> AtomicResult<()> {
+    let commit = Commit::from_resource(commit_resource.clone())?;
+    if !crate::hierarchy::is_inside(&commit.subject, drive) {
+        return Err(format!(
+            "Commit {} edits {}, which is not part of the mirrored Drive {}.",
+            commit_resource.get_subject(),
+            commit.subject,
+            drive
+        )
+        .into());
+    }
+    // The origin has already checked the rights and the schema
+    let local_commit = commit.apply_opts(store, false, true, false, false)?;
+    if local_commit.get_subject() != commit_resource.get_subject() {
+        store.remove_resource(local_commit.get_subject())?;
+        store.add_resource_unsafe(&commit_resource)?;
+    }
+    if commit.destroy == Some(true) {
+        return Ok(());
+    }
+    let origin = drive_origin(drive)?;
+    let mut resource = store.get_resource(&commit.subject)?;
+    let mut changed = false;
+    // Mirrors the default parent that the origin sets when it applies the Commit
+    if resource.get(urls::PARENT).is_err() && commit.subject != origin {
+        resource.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(origin))?;
+        changed = true;
+    }
+    // The last Commit should point to the original URL as well
+    if let Ok(last) = resource.get(urls::LAST_COMMIT) {
+        if &last.to_string() == local_commit.get_subject() {
+            resource.set_propval_unsafe(
+                urls::LAST_COMMIT.into(),
+                Value::AtomicUrl(commit_resource.get_subject().into()),
+            )?;
+            changed = true;
+        }
+    }
+    if changed {
+        store.add_resource_unsafe(&resource)?;
+    }
+    Ok(())
+}
+
+/// Returns the URL of the server that hosts the Drive, e.g. `https://example.com`
+pub fn drive_origin(drive: &str) -> AtomicResult<String> {
+    Ok(url::Url::parse(drive)?.origin().ascii_serialization())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn init(name: &str, base_url: &str) -> Db {
+        let tmp_dir_path = format!("tmp/{}", name);
+        let _try_remove_existing = std::fs::remove_dir_all(&tmp_dir_path);
+        let store = Db::init(&tmp_dir_path, base_url.into()).unwrap();
+        store.populate().unwrap();
+        store
+    }
+
+    #[test]
+    fn mirror_commits() {
+        let origin = init("mirror_origin", "https://origin.localhost");
+        let mirror = init("mirror_mirror", "https://mirror.localhost");
+        let drive = "https://origin.localhost/drive";
+        let agent = origin.create_agent(Some("origin")).unwrap();
+        // The mirror would otherwise fetch the Agent from the origin
+        mirror
+            .add_resource_unsafe(&origin.get_resource(&agent.subject).unwrap())
+            .unwrap();
+
+        let subject = format!("{}/mirrored", drive);
+        let mut commitbuilder = crate::commit::CommitBuilder::new(subject.clone());
+        commitbuilder.set(urls::DESCRIPTION.into(), Value::Markdown("mirrored".into()));
+        let commit = commitbuilder.sign(&agent, &origin).unwrap();
+        let commit_resource = commit
+            .apply_opts(&origin, false, true, true, false)
+            .unwrap();
+
+        apply_mirrored_commit(&mirror, drive, commit_resource.clone()).unwrap();
+        let mirrored = mirror.get_resource(&subject).unwrap();
+        assert_eq!(
+            mirrored.get(urls::DESCRIPTION).unwrap().to_string(),
+            "mirrored"
+        );
+        assert_eq!(
+            mirrored.get(urls::PARENT).unwrap().to_string(),
+            "https://origin.localhost"
+        );
+        assert_eq!(
+            mirrored.get(urls::LAST_COMMIT).unwrap().to_string(),
+            commit_resource.get_subject().as_str()
+        );
+        // The Commit keeps its original URL
+        let (found, _cursor) = mirror.get_commits_since(0, None, Some(drive), 10).unwrap();
+        assert_eq!(found[0].commit, commit_resource.get_subject().as_str());
+
+        // Tampered Commits are rejected
+        let mut tampered = commit_resource.clone();
+        tampered
+            .set_propval_unsafe(urls::CREATED_AT.into(), Value::Timestamp(1))
+            .unwrap();
+        apply_mirrored_commit(&mirror, drive, tampered).unwrap_err();
+        // Commits outside the Drive are rejected, also when the Drive is a prefix of their subject
+        apply_mirrored_commit(&mirror, "https://origin.localhost/other", commit_resource.clone())
+            .unwrap_err();
+        apply_mirrored_commit(&mirror, "https://origin.localhost/dr", commit_resource)
+            .unwrap_err();
+    }
+}
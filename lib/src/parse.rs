@@ -1,6 +1,8 @@
 //! Parsing / deserialization / decoding
 
-use crate::{errors::AtomicResult, resources::PropVals, urls, Resource, Storelike, Value};
+use std::collections::BTreeMap;
+
+use crate::{datatype::{CustomDatatype, DataType}, errors::AtomicResult, resources::PropVals, urls, values::{LangString, SubResource}, Resource, Storelike, Value};
 
 pub const JSON_AD_MIME: &str = "application/ad+json";
 
@@ -103,34 +105,78 @@ pub fn parse_json_ad_map_to_propvals(
             // To fix this, we need to use an Enum for Value::ResourceArray(enum)
             continue;
         }
+        // Booleans and arrays don't need their Property to be parsed,
+        // so only the Properties that are already in the store are checked for custom Datatypes.
+        if val.is_boolean() || val.is_array() {
+            if let Some(custom) = local_custom_datatype(&prop, store) {
+                propvals.insert(prop.clone(), custom_value(custom, &val, &prop)?);
+                continue;
+            }
+        }
         let atomic_val = match val {
             serde_json::Value::Null => return Err("Null not allowed in JSON-AD".into()),
             serde_json::Value::Bool(bool) => Value::Boolean(bool),
             serde_json::Value::Number(num) => {
                 let property = store.get_property(&prop)?;
-                // Also converts numbers to strings, not sure what to think about this.
-                // Does not result in invalid atomic data, but does allow for weird inputs
-                Value::new(&num.to_string(), &property.data_type)?
+                match custom_datatype(&property.data_type) {
+                    Some(custom) => custom_value(custom, &serde_json::Value::Number(num), &prop)?,
+                    // Also converts numbers to strings, not sure what to think about this.
+                    // Does not result in invalid atomic data, but does allow for weird inputs
+                    None => Value::new(&num.to_string(), &property.data_type)?,
+                }
             }
             serde_json::Value::String(str) => {
                 let property = store.get_property(&prop)?;
-                Value::new(&str.to_string(), &property.data_type)?
+                match custom_datatype(&property.data_type) {
+                    Some(custom) => custom_value(custom, &serde_json::Value::String(str), &prop)?,
+                    None => Value::new(&str, &property.data_type)?,
+                }
             }
             // In Atomic Data, all arrays are Resource Arrays which are serialized JSON things.
             // Maybe this step could be simplified? Just serialize to string?
             serde_json::Value::Array(arr) => {
-                let mut newvec: Vec<String> = Vec::new();
-                for v in arr {
-                    match v {
-                        serde_json::Value::String(str) => newvec.push(str),
-                        _err => return Err("Found non-string item in resource array.".into()),
+                // Arrays of objects contain included Resources, which need an @id
+                if arr.iter().any(|v| v.is_object()) {
+                    let mut nested = Vec::new();
+                    for v in arr {
+                        match v {
+                            serde_json::Value::Object(map) => nested.push(SubResource {
+                                subject: get_id(map.clone())?,
+                                propvals: parse_json_ad_map_to_propvals(map, store)?,
+                            }),
+                            _err => return Err("Found non-object item in an array of nested resources.".into()),
+                        }
                     }
+                    Value::ResourceArrayNested(nested)
+                } else {
+                    let mut newvec: Vec<String> = Vec::new();
+                    for v in arr {
+                        match v {
+                            serde_json::Value::String(str) => newvec.push(str),
+                            _err => return Err("Found non-string item in resource array.".into()),
+                        }
+                    }
+                    Value::ResourceArray(newvec)
                 }
-                Value::ResourceArray(newvec)
-            }
-            serde_json::Value::Object(map) => {
-                Value::NestedResource(parse_json_ad_map_to_propvals(map, store)?)
             }
+            // Objects are language-tagged strings for String and Markdown Properties, and nested Resources otherwise
+            serde_json::Value::Object(map) => match store.get_property(&prop).map(|p| p.data_type) {
+                Ok(datatype @ (DataType::String | DataType::Markdown)) => {
+                    let mut texts = BTreeMap::new();
+                    for (language, text) in map {
+                        match text {
+                            serde_json::Value::String(text) => texts.insert(language, text),
+                            _err => return Err(format!("The translations of {} must be strings.", prop).into()),
+                        };
+                    }
+                    Value::LangString(LangString::new(datatype, texts)?)
+                }
+                Ok(datatype) if custom_datatype(&datatype).is_some() => {
+                    let custom = custom_datatype(&datatype).unwrap();
+                    custom_value(custom, &serde_json::Value::Object(map), &prop)?
+                }
+                _ => Value::NestedResource(parse_json_ad_map_to_propvals(map, store)?),
+            },
         };
         // Some of these values are _not correctly matched_ to the datatype.
         propvals.insert(prop, atomic_val);
@@ -138,6 +184,30 @@ pub fn parse_json_ad_map_to_propvals(
     Ok(propvals)
 }
 
+/// Returns the registered Datatype, if the DataType is one
+fn custom_datatype(datatype: &DataType) -> Option<CustomDatatype> {
+    match datatype {
+        DataType::Unsupported(url) => crate::datatype::get_custom_datatype(url),
+        _ => None,
+    }
+}
+
+/// Returns the registered Datatype of the Property, if it has one.
+/// Only looks up Properties that are already in the store, so unknown Properties are never fetched for this.
+fn local_custom_datatype(property: &str, store: &impl crate::Storelike) -> Option<CustomDatatype> {
+    if !crate::datatype::has_custom_datatypes() || !store.has_resource(property) {
+        return None;
+    }
+    custom_datatype(&store.get_property(property).ok()?.data_type)
+}
+
+/// Parses a JSON value using a registered Datatype
+fn custom_value(custom: CustomDatatype, json: &serde_json::Value, property: &str) -> AtomicResult<Value> {
+    let string = (custom.from_json)(json)
+        .map_err(|e| format!("Invalid value for {}: {}", property, e))?;
+    Value::new(&string, &DataType::Unsupported(custom.url))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
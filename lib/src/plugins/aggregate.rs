@@ -0,0 +1,333 @@
+//! Aggregations count the Resources that match some filters, or calculate the sum, minimum, maximum or average of a numeric Property.
+//! Resources can be grouped by the Value of a Property, e.g. count all Resources per Class.
+//! Useful for dashboards, so clients don't have to fetch whole Collections.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    collections::{filter_subjects, Filter},
+    comparison::Operator,
+    datatype::DataType,
+    endpoints::Endpoint,
+    errors::AtomicResult,
+    urls, Db, Resource, Storelike, Value,
+};
+
+pub fn aggregate_endpoint() -> Endpoint {
+    Endpoint {
+        path: "/aggregate".to_string(),
+        params: [
+            urls::COLLECTION_PROPERTY.to_string(),
+            urls::COLLECTION_VALUE.to_string(),
+            urls::COLLECTION_FILTERS.to_string(),
+            urls::AGGREGATE_FUNCTION.to_string(),
+            urls::AGGREGATE_OF.to_string(),
+            urls::AGGREGATE_GROUP_BY.to_string(),
+        ]
+        .into(),
+        description: "Counts the Resources that match the filters (`property`, `value` and `filter`, as in Collections), or calculates the `sum`, `min`, `max` or `avg` of a numeric Property (`of`). Use `group_by` to aggregate per Value of a Property.".to_string(),
+        shortname: "aggregate".to_string(),
+        handle: handle_aggregate_request,
+    }
+}
+
+/// How the Values of the matching Resources are combined
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Function {
+    fn from_string(function: &str) -> AtomicResult<Function> {
+        match function {
+            "count" => Ok(Function::Count),
+            "sum" => Ok(Function::Sum),
+            "min" => Ok(Function::Min),
+            "max" => Ok(Function::Max),
+            "avg" => Ok(Function::Avg),
+            other => Err(format!(
+                "Unknown aggregate function {}. Use count, sum, min, max or avg.",
+                other
+            )
+            .into()),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Function::Count => "count",
+            Function::Sum => "sum",
+            Function::Min => "min",
+            Function::Max => "max",
+            Function::Avg => "avg",
+        }
+    }
+}
+
+/// The running totals of a group
+#[derive(Default)]
+struct Totals {
+    /// Number of Resources in the group
+    members: usize,
+    /// Numbers of the `of` Property. Resources without it are counted as members, but are not part of the numbers.
+    numbers: Vec<f64>,
+}
+
+impl Totals {
+    fn result(&self, function: Function) -> Option<f64> {
+        let numbers = self.numbers.iter().cloned();
+        match function {
+            Function::Count => Some(self.members as f64),
+            Function::Sum => Some(numbers.sum()),
+            Function::Min => numbers.reduce(f64::min),
+            Function::Max => numbers.reduce(f64::max),
+            Function::Avg if self.numbers.is_empty() => None,
+            Function::Avg => Some(numbers.sum::<f64>() / self.numbers.len() as f64),
+        }
+    }
+}
+
+fn handle_aggregate_request(
+    url: url::Url,
+    store: &Db,
+    _for_agent: Option<&str>,
+) -> AtomicResult<Resource> {
+    let mut filters = Vec::new();
+    let mut match_any = false;
+    let mut function = Function::Count;
+    let mut of = None;
+    let mut group_by = None;
+    let mut property = None;
+    let mut value = None;
+    for (k, v) in url.query_pairs() {
+        match k.as_ref() {
+            "property" => property = Some(v.to_string()),
+            "value" => value = Some(v.to_string()),
+            "filter" => filters.push(Filter::from_query_param(&v)),
+            "match" => match_any = v == "any",
+            "aggregate" => function = Function::from_string(&v)?,
+            "of" => of = Some(v.to_string()),
+            "group_by" => group_by = Some(v.to_string()),
+            _ => {}
+        };
+    }
+    // Like in Collections, `match` only applies to the other filters
+    let base = property.map(|property| Filter {
+        property,
+        value,
+        operator: Operator::Equals,
+        value_end: None,
+    });
+    if base.is_none() && filters.is_empty() {
+        return aggregate_endpoint().to_resource(store);
+    }
+    if function != Function::Count {
+        let of = of.as_ref().ok_or(format!(
+            "The {} function requires an `of` Property",
+            function.as_str()
+        ))?;
+        match store.get_property(of)?.data_type {
+            DataType::Integer | DataType::Float | DataType::Timestamp => {}
+            other => {
+                return Err(format!(
+                    "The {} function requires a numeric Property, but {} has DataType {}",
+                    function.as_str(),
+                    of,
+                    other
+                )
+                .into())
+            }
+        }
+    }
+
+    let subjects = filter_subjects(store, base.as_ref(), &filters, match_any)?;
+    let mut total = Totals::default();
+    // Groups by the Value of the `group_by` Property, sorted by that Value
+    let mut groups: BTreeMap<String, Totals> = BTreeMap::new();
+    for subject in subjects.iter() {
+        let resource = store.get_resource(subject)?;
+        let number = of
+            .as_ref()
+            .and_then(|of| resource.get(of).ok())
+            .and_then(|value| value.as_number());
+        let add = |totals: &mut Totals| {
+            totals.members += 1;
+            if let Some(number) = number {
+                totals.numbers.push(number);
+            }
+        };
+        add(&mut total);
+        // Resources without the Property are not part of any group. For ResourceArrays, Resources are part of the group of every item.
+        if let Some(Ok(group_value)) = group_by.as_ref().map(|g| resource.get(g)) {
+            let keys = match group_value {
+                Value::ResourceArray(items) => items.clone(),
+                other => vec![other.to_string()],
+            };
+            for key in keys {
+                add(groups.entry(key).or_default());
+            }
+        }
+    }
+
+    let mut resource = Resource::new(url.to_string());
+    resource.set_propval_string(urls::AGGREGATE_FUNCTION.into(), function.as_str(), store)?;
+    resource.set_propval(
+        urls::COLLECTION_MEMBER_COUNT.into(),
+        subjects.len().into(),
+        store,
+    )?;
+    if let Some(result) = total.result(function) {
+        resource.set_propval(urls::AGGREGATE_RESULT.into(), Value::Float(result), store)?;
+    }
+    if let Some(of) = &of {
+        resource.set_propval_string(urls::AGGREGATE_OF.into(), of, store)?;
+    }
+    if let Some(group_by) = &group_by {
+        resource.set_propval_string(urls::AGGREGATE_GROUP_BY.into(), group_by, store)?;
+        let mut group_resources = Vec::new();
+        for (key, totals) in groups {
+            // Every group links to the aggregate of only its own members
+            let mut group_url = url.clone();
+            let query: Vec<(String, String)> = url
+                .query_pairs()
+                .filter(|(k, _v)| k != "group_by")
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            group_url
+                .query_pairs_mut()
+                .clear()
+                .extend_pairs(query)
+                .append_pair("filter", &format!("{}~eq~{}", group_by, key));
+            let mut group = Resource::new(group_url.to_string());
+            group.set_propval_unsafe(urls::AGGREGATE_GROUP_VALUE.into(), Value::String(key))?;
+            group.set_propval_unsafe(
+                urls::COLLECTION_MEMBER_COUNT.into(),
+                Value::Integer(totals.members as i64),
+            )?;
+            if let Some(result) = totals.result(function) {
+                group.set_propval_unsafe(urls::AGGREGATE_RESULT.into(), Value::Float(result))?;
+            }
+            group_resources.push(group);
+        }
+        resource.set_propval(urls::AGGREGATE_GROUPS.into(), group_resources.into(), store)?;
+    }
+    Ok(resource)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn init(name: &str) -> Db {
+        let tmp_dir_path = format!("tmp/{}", name);
+        let _try_remove_existing = std::fs::remove_dir_all(&tmp_dir_path);
+        let store = Db::init(&tmp_dir_path, "https://localhost".into()).unwrap();
+        store.populate().unwrap();
+        store
+    }
+
+    #[test]
+    fn aggregate_per_group() {
+        let store = init("aggregate_per_group");
+        for (i, (status, size)) in [
+            ("open", Some(10)),
+            ("open", Some(20)),
+            ("closed", Some(5)),
+            ("open", None),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut resource = Resource::new(format!("{}/aggregated/{}", store.get_base_url(), i));
+            resource
+                .set_propval_unsafe(urls::DESCRIPTION.into(), Value::Markdown((*status).into()))
+                .unwrap();
+            resource
+                .set_propval_unsafe(
+                    urls::IS_A.into(),
+                    vec!["https://localhost/Aggregated".to_string()].into(),
+                )
+                .unwrap();
+            if let Some(size) = size {
+                resource
+                    .set_propval_unsafe(urls::COLLECTION_PAGE_SIZE.into(), Value::Integer(*size))
+                    .unwrap();
+            }
+            store.add_resource_unsafe(&resource).unwrap();
+        }
+        let aggregate = |query: String| {
+            let url =
+                url::Url::parse(&format!("{}/aggregate?{}", store.get_base_url(), query)).unwrap();
+            handle_aggregate_request(url, &store, None).unwrap()
+        };
+        let filter = format!(
+            "property={}&value={}",
+            urlencoding::encode(urls::IS_A),
+            urlencoding::encode("https://localhost/Aggregated")
+        );
+        let count = aggregate(filter.clone());
+        assert_eq!(count.get(urls::AGGREGATE_RESULT).unwrap().to_string(), "4");
+
+        let avg = aggregate(format!(
+            "{}&aggregate=avg&of={}&group_by={}",
+            filter,
+            urlencoding::encode(urls::COLLECTION_PAGE_SIZE),
+            urlencoding::encode(urls::DESCRIPTION)
+        ));
+        assert_eq!(
+            avg.get(urls::AGGREGATE_RESULT).unwrap().to_string(),
+            "11.666666666666666"
+        );
+        let groups = match avg.get(urls::AGGREGATE_GROUPS).unwrap() {
+            Value::ResourceArrayNested(groups) => groups,
+            _ => panic!("Groups should be nested Resources"),
+        };
+        let results: Vec<(String, String, String)> = groups
+            .iter()
+            .map(|g| {
+                (
+                    g.propvals
+                        .get(urls::AGGREGATE_GROUP_VALUE)
+                        .unwrap()
+                        .to_string(),
+                    g.propvals
+                        .get(urls::COLLECTION_MEMBER_COUNT)
+                        .unwrap()
+                        .to_string(),
+                    g.propvals.get(urls::AGGREGATE_RESULT).unwrap().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                ("closed".into(), "1".into(), "5".into()),
+                ("open".into(), "3".into(), "15".into())
+            ]
+        );
+        // The subject of a group is the aggregate of only that group
+        let url = url::Url::parse(&groups[0].subject).unwrap();
+        let closed = handle_aggregate_request(url, &store, None).unwrap();
+        assert_eq!(
+            closed
+                .get(urls::COLLECTION_MEMBER_COUNT)
+                .unwrap()
+                .to_string(),
+            "1"
+        );
+
+        // Only numeric Properties can be summed
+        let url = url::Url::parse(&format!(
+            "{}/aggregate?{}&aggregate=sum&of={}",
+            store.get_base_url(),
+            filter,
+            urlencoding::encode(urls::DESCRIPTION)
+        ))
+        .unwrap();
+        assert!(handle_aggregate_request(url, &store, None).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! The audit log shows which Commits were signed by some Agent.
+//! Only Agents with write rights on the Drive can read its audit log.
+
+use crate::{
+    collections::{Collection, CollectionBuilder},
+    endpoints::Endpoint,
+    errors::AtomicResult,
+    urls, Db, Resource, Storelike,
+};
+
+pub fn audit_endpoint() -> Endpoint {
+    Endpoint {
+        path: "/audit".to_string(),
+        params: [
+            urls::SIGNER.to_string(),
+            urls::AUDIT_DRIVE.to_string(),
+            urls::AUDIT_FROM.to_string(),
+            urls::AUDIT_UNTIL.to_string(),
+        ]
+        .into(),
+        description: "Lists the Commits signed by some Agent, newest first. Can be filtered by Drive and by a time window. Follow the `next` URL for older Commits. Requires authentication by an Agent with write rights on the Drive.".to_string(),
+        shortname: "audit".to_string(),
+        handle: handle_audit_request,
+    }
+}
+
+fn handle_audit_request(
+    url: url::Url,
+    store: &Db,
+    for_agent: Option<&str>,
+) -> AtomicResult<Resource> {
+    let mut signer = None;
+    let mut drive = None;
+    let mut from = None;
+    let mut until = None;
+    let mut after = None;
+    let mut page_size = 30;
+    for (k, v) in url.query_pairs() {
+        match k.as_ref() {
+            "signer" => signer = Some(v.to_string()),
+            "drive" => drive = Some(v.to_string()),
+            "from" => from = Some(v.parse::<i64>()?),
+            "until" => until = Some(v.parse::<i64>()?),
+            "after" => after = Some(v.to_string()),
+            "page_size" => page_size = v.parse::<usize>()?,
+            _ => {}
+        };
+    }
+    let signer = match signer {
+        Some(signer) => signer,
+        None => return audit_endpoint().to_resource(store),
+    };
+    let drive = drive.unwrap_or_else(|| store.get_base_url().to_string());
+    let agent = for_agent.ok_or("The audit log requires authentication. Sign your request.")?;
+    let drive_resource = store.get_resource(&drive)?;
+    if !crate::hierarchy::check_write(store, &drive_resource, agent.into())? {
+        return Err(format!(
+            "Agent {} has no write rights on {}, so it can't read its audit log.",
+            agent, drive
+        )
+        .into());
+    }
+    if page_size < 1 {
+        return Err("Page size must be greater than 0".into());
+    }
+    let (commits, next) = store.get_commits_by_signer_page(
+        &signer,
+        from,
+        until,
+        Some(&drive),
+        after.as_deref(),
+        page_size,
+    )?;
+    let subjects: Vec<String> = commits.into_iter().map(|c| c.commit).collect();
+    let collection_builder = CollectionBuilder {
+        subject: url.to_string(),
+        property: Some(urls::SIGNER.into()),
+        value: Some(signer.clone()),
+        filters: Vec::new(),
+        match_any: false,
+        sort_by: Some(urls::CREATED_AT.into()),
+        sort_desc: true,
+        sort_then: Vec::new(),
+        nulls_first: false,
+        include: false,
+        include_nested: false,
+        materialized: false,
+        after: None,
+        before: None,
+        current_page: 0,
+        page_size,
+        name: Some(format!("Audit log of {}", signer)),
+    };
+    let mut collection = Collection::from_subjects(subjects, collection_builder)?;
+    collection.next = next;
+    let mut resource = collection.to_resource(store)?;
+    // Only the current page is read, so the totals are unknown
+    resource.remove_propval(urls::COLLECTION_CURRENT_PAGE);
+    resource.remove_propval(urls::COLLECTION_TOTAL_PAGES);
+    resource.remove_propval(urls::COLLECTION_MEMBER_COUNT);
+    resource.set_propval_string(urls::AUDIT_DRIVE.into(), &drive, store)?;
+    Ok(resource)
+}
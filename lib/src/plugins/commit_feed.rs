@@ -0,0 +1,80 @@
+//! The Commit feed lists Commits in the order in which they were added to the store, optionally only the ones created after some moment.
+//! Useful for incremental sync, backups and integrations.
+//! It extends the Commits Collection (`/commits`) with the `since`, `drive` and `cursor` query parameters.
+
+use crate::{errors::AtomicResult, urls, Db, Resource, Storelike, Value};
+
+const DEFAULT_PAGE_SIZE: usize = 30;
+
+/// Checks if the request is for a Commits Collection, and uses any of the feed query parameters.
+pub fn is_commit_feed_request(resource: &Resource, url: &url::Url) -> bool {
+    let is_commits_collection = match resource.get(urls::COLLECTION_VALUE) {
+        Ok(value) => value.to_string() == urls::COMMIT,
+        Err(_) => false,
+    };
+    is_commits_collection
+        && url
+            .query_pairs()
+            .any(|(k, _v)| k == "since" || k == "drive" || k == "cursor")
+}
+
+/// Adds the Commits (in order of arrival) to the Collection resource, and a `next` URL that continues after the last one.
+/// Clients can keep following `next` until they get an empty page, and store it to resume later on.
+/// Requires read rights on the Drive, which defaults to the base URL of the store.
+pub fn construct_commit_feed(
+    store: &Db,
+    url: &url::Url,
+    resource: &mut Resource,
+    for_agent: Option<&str>,
+) -> AtomicResult<Resource> {
+    let mut since = 0;
+    let mut drive = None;
+    let mut cursor = None;
+    let mut page_size = DEFAULT_PAGE_SIZE;
+    for (k, v) in url.query_pairs() {
+        match k.as_ref() {
+            "since" => since = v.parse::<i64>()?,
+            "drive" => drive = Some(v.to_string()),
+            "cursor" => cursor = Some(v.to_string()),
+            "page_size" => page_size = v.parse::<usize>()?,
+            _ => {}
+        };
+    }
+    if page_size < 1 {
+        return Err("Page size must be greater than 0".into());
+    }
+    let drive = drive.unwrap_or_else(|| store.get_base_url().to_string());
+    let drive_resource = store.get_resource(&drive)?;
+    if !crate::hierarchy::check_read(store, &drive_resource, for_agent)? {
+        return Err(format!(
+            "No read rights for {}, so its Commits can't be shown. Sign your request.",
+            drive
+        )
+        .into());
+    }
+    let (commits, next_cursor) =
+        store.get_commits_since(since, cursor.as_deref(), Some(&drive), page_size)?;
+    let members: Vec<String> = commits.into_iter().map(|c| c.commit).collect();
+    resource.set_propval(urls::COLLECTION_MEMBERS.into(), members.into(), store)?;
+    resource.set_propval(
+        urls::COLLECTION_PAGE_SIZE.into(),
+        Value::Integer(page_size as i64),
+        store,
+    )?;
+    // The page number and totals don't apply when using cursors
+    resource.remove_propval(urls::COLLECTION_CURRENT_PAGE);
+    if let Some(next_cursor) = next_cursor {
+        let mut next = url.clone();
+        next.query_pairs_mut()
+            .clear()
+            .append_pair("drive", &drive)
+            .append_pair("cursor", &next_cursor)
+            .append_pair("page_size", &page_size.to_string());
+        resource.set_propval(
+            urls::COLLECTION_NEXT.into(),
+            Value::AtomicUrl(next.to_string()),
+            store,
+        )?;
+    }
+    Ok(resource.to_owned())
+}
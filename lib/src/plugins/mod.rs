@@ -1,3 +1,6 @@
+pub mod aggregate;
+pub mod audit;
+pub mod commit_feed;
 pub mod path;
 pub mod versioning;
 pub mod invite;
@@ -1,16 +1,20 @@
-use crate::{endpoints::Endpoint, errors::AtomicResult, urls, Resource, Storelike};
+use crate::{endpoints::Endpoint, errors::AtomicResult, storelike::PathReturn, urls, Resource, Storelike};
 
 pub fn path_endpoint() -> Endpoint {
     Endpoint {
         path: "/path".to_string(),
         params: [urls::PATH.to_string()].into(),
-        description: "An Atomic Path is a string that starts with the URL of some Atomic Resource, followed by one or multiple other Property URLs or Property Shortnames. It resolves to one specific Resource or Value. At this moment, Values are not yet supported.".to_string(),
+        description: "An Atomic Path is a string that starts with the URL of some Atomic Resource, followed by one or multiple other Property URLs or Property Shortnames. It resolves to one specific Resource or Value. Use `~{property}` to follow incoming links (e.g. `~parent` for children) and `*` to select all items of an array. Paths with multiple results return a list of members.".to_string(),
         shortname: "path".to_string(),
         handle: handle_path_request,
     }
 }
 
-fn handle_path_request(url: url::Url, store: &impl Storelike) -> AtomicResult<Resource> {
+fn handle_path_request(
+    url: url::Url,
+    store: &impl Storelike,
+    _for_agent: Option<&str>,
+) -> AtomicResult<Resource> {
     let params = url.query_pairs();
     let mut path = None;
     for (k, v) in params {
@@ -21,10 +25,24 @@ fn handle_path_request(url: url::Url, store: &impl Storelike) -> AtomicResult<Re
     if path.is_none() {
         return path_endpoint().to_resource(store);
     }
-    let result = store.get_path(&path.unwrap(), None)?;
-    match result {
-        crate::storelike::PathReturn::Subject(subject) => store.get_resource(&subject),
-        crate::storelike::PathReturn::Atom(atom) => {
+    let mut results = store.get_path_all(&path.unwrap(), None)?;
+    if results.len() != 1 {
+        // Subjects of the resulting Resources, or the resulting Values
+        let members: Vec<String> = results
+            .into_iter()
+            .map(|result| match result {
+                PathReturn::Subject(subject) => subject,
+                PathReturn::Atom(atom) => atom.value.to_string(),
+            })
+            .collect();
+        let mut resource = Resource::new(url.into_string());
+        resource.set_propval(urls::COLLECTION_MEMBER_COUNT.into(), members.len().into(), store)?;
+        resource.set_propval(urls::COLLECTION_MEMBERS.into(), members.into(), store)?;
+        return Ok(resource);
+    }
+    match results.remove(0) {
+        PathReturn::Subject(subject) => store.get_resource(&subject),
+        PathReturn::Atom(atom) => {
             let mut resource = Resource::new(url.into_string());
             resource.set_propval_string(urls::ATOM_SUBJECT.into(), &atom.subject, store)?;
             resource.set_propval_string(
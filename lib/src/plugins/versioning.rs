@@ -1,4 +1,4 @@
-use crate::{Commit, Resource, Storelike, collections::CollectionBuilder, endpoints::Endpoint, errors::AtomicResult, urls};
+use crate::{Resource, Storelike, collections::CollectionBuilder, endpoints::Endpoint, errors::AtomicResult, urls};
 
 pub fn version_endpoint() -> Endpoint {
     Endpoint {
@@ -21,7 +21,11 @@ pub fn all_versions_endpoint() -> Endpoint {
     }
 }
 
-fn handle_version_request(url: url::Url, store: &impl Storelike) -> AtomicResult<Resource> {
+fn handle_version_request(
+    url: url::Url,
+    store: &impl Storelike,
+    _for_agent: Option<&str>,
+) -> AtomicResult<Resource> {
     let params = url.query_pairs();
     let mut commit_url = None;
     for (k, v) in params {
@@ -37,7 +41,11 @@ fn handle_version_request(url: url::Url, store: &impl Storelike) -> AtomicResult
     Ok(resource)
 }
 
-fn handle_all_versions_request(url: url::Url, store: &impl Storelike) -> AtomicResult<Resource> {
+fn handle_all_versions_request(
+    url: url::Url,
+    store: &impl Storelike,
+    _for_agent: Option<&str>,
+) -> AtomicResult<Resource> {
     let params = url.query_pairs();
     let mut target_subject = None;
     for (k, v) in params {
@@ -53,8 +61,17 @@ fn handle_all_versions_request(url: url::Url, store: &impl Storelike) -> AtomicR
         subject: url.to_string(),
         property: Some(urls::SUBJECT.into()),
         value: Some(target.clone()),
+        filters: Vec::new(),
+        match_any: false,
         sort_by: None,
         sort_desc: false,
+        sort_then: Vec::new(),
+        nulls_first: false,
+        include: false,
+        include_nested: false,
+        materialized: false,
+        after: None,
+        before: None,
         current_page: 0,
         page_size: 20,
         name: Some(format!("Versions of {}", target)),
@@ -69,38 +86,14 @@ fn handle_all_versions_request(url: url::Url, store: &impl Storelike) -> AtomicR
     collection.to_resource(store)
 }
 
-/// Searches the local store for all commits with this subject
-fn get_commits_for_resource(subject: &str, store: &impl Storelike) -> AtomicResult<Vec<Commit>> {
-    let commit_atoms = store.tpf(None, Some(urls::SUBJECT), Some(subject), false)?;
-    let mut commit_resources = Vec::new();
-    for atom in commit_atoms {
-        let commit = crate::Commit::from_resource(store.get_resource(&atom.subject)?)?;
-        commit_resources.push(commit)
-    }
-    Ok(commit_resources)
-}
-
-/// Constructs a Resource version for a specific Commit
+/// Constructs a Resource version for a specific Commit.
+/// Concurrent Commits are merged, in the same way as when they were applied.
 /// Only works if the current store has the required Commits
 pub fn construct_version(commit_url: &str, store: &impl Storelike) -> AtomicResult<Resource> {
     let commit = store.get_resource(commit_url)?;
     // Get all the commits for the subject of that Commit
     let subject = &commit.get(urls::SUBJECT)?.to_string();
-    let mut commits = get_commits_for_resource(subject, store)?;
-    // Sort all commits by date
-    commits.sort_by(|a, b| a.created_at.cmp(&b.created_at));
-    let mut version = Resource::new(subject.into());
-    for commit in commits {
-        if let Some(current_commit) = commit.url.clone() {
-            let updated = commit.apply_changes(version, store)?;
-            version = updated;
-            // Stop iterating when the target commit has been applied.
-            if current_commit == commit_url {
-                break;
-            }
-        }
-    }
-    Ok(version)
+    crate::merge::replay(subject, Some(commit_url), store)
 }
 
 /// Creates the versioning URL for some specific Commit
@@ -152,7 +145,7 @@ mod test {
             .set_propval_string(crate::urls::DESCRIPTION.into(), second_val, &store)
             .unwrap();
         let second_commit = resource.save_locally(&store).unwrap();
-        let commits = get_commits_for_resource(&subject, &store).unwrap();
+        let commits = crate::merge::get_commits_for_resource(subject, &store).unwrap();
         assert_eq!(commits.len(), 2);
 
         let first_version = construct_version(first_commit.get_subject(), &store).unwrap();
@@ -19,6 +19,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "shortname".into(),
         description: "A short name of something. It can only contain letters, numbers and dashes `-`. Use dashes to denote spaces between words. Not case sensitive - lowercase only. Useful in programming contexts where the user should be able to type something short to identify a specific thing.".into(),
         subject: urls::SHORTNAME.into(),
+        constraints: Default::default(),
     }.to_resource()?;
     store.add_resource_unsafe(&shortname)?;
 
@@ -28,6 +29,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "description".into(),
         description: "A textual description of something. When making a description, make sure that the first few words tell the most important part. Give examples. Since the text supports markdown, you're free to use links and more.".into(),
         subject: urls::DESCRIPTION.into(),
+        constraints: Default::default(),
     }.to_resource()?;
     store.add_resource_unsafe(&description)?;
 
@@ -37,6 +39,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "is-a".into(),
         description: "A list of Classes of which the thing is an instance of. The Classes of a Resource determine which Properties are recommended and required.".into(),
         subject: urls::IS_A.into(),
+        constraints: Default::default(),
     }.to_resource()?;
     store.add_resource_unsafe(&is_a)?;
 
@@ -46,6 +49,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "datatype".into(),
         description: "The Datatype of a property, such as String or Timestamp.".into(),
         subject: urls::DATATYPE_PROP.into(),
+        constraints: Default::default(),
     }
     .to_resource()?;
     store.add_resource_unsafe(&datatype)?;
@@ -58,6 +62,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
             "The class-type indicates that the Atomic URL should be an instance of this class."
                 .into(),
         subject: urls::CLASSTYPE_PROP.into(),
+        constraints: Default::default(),
     }
     .to_resource()?;
     store.add_resource_unsafe(&classtype)?;
@@ -68,6 +73,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "recommends".into(),
         description: "The Properties that are not required, but recommended for this Class.".into(),
         subject: urls::RECOMMENDS.into(),
+        constraints: Default::default(),
     }
     .to_resource()?;
     store.add_resource_unsafe(&recommends)?;
@@ -78,6 +84,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "requires".into(),
         description: "The Properties that are required for this Class.".into(),
         subject: urls::REQUIRES.into(),
+        constraints: Default::default(),
     }
     .to_resource()?;
     store.add_resource_unsafe(&requires)?;
@@ -88,6 +95,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "property".into(),
         description: "A Property is a single field in a Class. It's the thing that a property field in an Atom points to. An example is `birthdate`. An instance of Property requires various Properties, most notably a `datatype` (e.g. `string` or `integer`), a human readable `description` (such as the thing you're reading), and a `shortname`.".into(),
         subject: urls::PROPERTY.into(),
+        subclass_of: Vec::new(),
     }
     .to_resource()?;
     store.add_resource_unsafe(&property)?;
@@ -98,6 +106,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         shortname: "class".into(),
         description: "A Class describes an abstract concept, such as 'Person' or 'Blogpost'. It describes the data shape of data and explains what the thing represents. It is convention to use Uppercase in its URL. Note that in Atomic Data, a Resource can have several Classes - not just a single one.".into(),
         subject: urls::CLASS.into(),
+        subclass_of: Vec::new(),
     }
     .to_resource()?;
     store.add_resource_unsafe(&class)?;
@@ -109,6 +118,7 @@ pub fn populate_base_models(store: &impl Storelike) -> AtomicResult<()> {
         description:
             "A Datatype describes a possible type of value, such as 'string' or 'integer'.".into(),
         subject: urls::DATATYPE_CLASS.into(),
+        subclass_of: Vec::new(),
     }
     .to_resource()?;
     store.add_resource_unsafe(&datatype)?;
@@ -0,0 +1,204 @@
+//! A durable queue of signed Commits, for making changes while the server can't be reached.
+//! The Commits are stored as a JSON-AD array in a file, and sent in order using `CommitQueue::push`.
+//! Servers refuse Commits that are older than [MAX_COMMIT_AGE_MS] (24 hours),
+//! so queued Commits have to be pushed within a day after they were made.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    client::{try_post_commit, PostResult},
+    commit::MAX_COMMIT_AGE_MS,
+    errors::AtomicResult,
+    parse::parse_json_ad_commit_resource,
+    Commit, Storelike,
+};
+
+/// A file containing signed Commits that still have to be sent to their servers.
+#[derive(Clone, Debug)]
+pub struct CommitQueue {
+    path: PathBuf,
+}
+
+/// The result of sending the queued Commits
+#[derive(Debug, Default)]
+pub struct PushReport {
+    /// Commits that were accepted by the server
+    pub accepted: Vec<Commit>,
+    /// Commits that were refused by the server, with the reason. These are removed from the queue.
+    pub rejected: Vec<(Commit, String)>,
+    /// Amount of Commits that are still in the queue
+    pub remaining: usize,
+    /// Why sending stopped before the end of the queue, e.g. because the server could not be reached
+    pub stopped: Option<String>,
+}
+
+impl CommitQueue {
+    /// Uses the file at the path, which is created when the first Commit is added.
+    pub fn open(path: &Path) -> CommitQueue {
+        CommitQueue {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Returns the queued Commits, oldest first
+    pub fn list(&self, store: &impl Storelike) -> AtomicResult<Vec<Commit>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let string = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Could not read commit queue {:?}. {}", self.path, e))?;
+        let array: Vec<serde_json::Value> = serde_json::from_str(&string)
+            .map_err(|e| format!("Commit queue {:?} is corrupt. {}", self.path, e))?;
+        let mut commits = Vec::new();
+        for item in array {
+            let resource = parse_json_ad_commit_resource(&item.to_string(), store)?;
+            commits.push(Commit::from_resource(resource)?);
+        }
+        Ok(commits)
+    }
+
+    /// Returns true if there are no queued Commits
+    pub fn is_empty(&self, store: &impl Storelike) -> AtomicResult<bool> {
+        Ok(self.list(store)?.is_empty())
+    }
+
+    /// Adds a signed Commit to the end of the queue
+    pub fn add(&self, commit: &Commit, store: &impl Storelike) -> AtomicResult<()> {
+        let mut commits = self.list(store)?;
+        commits.push(commit.clone());
+        self.write(&commits, store)
+    }
+
+    /// Sends the queued Commits in order.
+    /// Stops at the first Commit that can be sent again later, e.g. because its server can't be reached
+    /// or is rate limiting, so the order is kept.
+    /// Also stops at Commits that are too old to be accepted, without removing them, so you can decide what to do with them.
+    /// Commits that the server refuses as invalid are removed from the queue, and listed in the report.
+    pub fn push(&self, store: &impl Storelike) -> AtomicResult<PushReport> {
+        let mut commits = self.list(store)?.into_iter();
+        let mut report = PushReport::default();
+        let mut remaining = Vec::new();
+        let now = crate::datetime_helpers::now();
+        for commit in commits.by_ref() {
+            if now - commit.created_at > MAX_COMMIT_AGE_MS {
+                report.stopped = Some(format!(
+                    "The Commit for {} was made more than 24 hours ago, so servers refuse it. Remove it from the queue at {:?}, and make the change again.",
+                    commit.get_subject(),
+                    self.path
+                ));
+                remaining.push(commit);
+                break;
+            }
+            match try_post_commit(&commit, store)? {
+                PostResult::Accepted => report.accepted.push(commit),
+                PostResult::Rejected(reason) => report.rejected.push((commit, reason)),
+                PostResult::Unreachable(reason) => {
+                    report.stopped = Some(reason);
+                    remaining.push(commit);
+                    break;
+                }
+            }
+        }
+        remaining.extend(commits);
+        report.remaining = remaining.len();
+        self.write(&remaining, store)?;
+        Ok(report)
+    }
+
+    /// Overwrites the queue. Writes to a temporary file first, so the queue is not lost if writing fails.
+    fn write(&self, commits: &[Commit], store: &impl Storelike) -> AtomicResult<()> {
+        let mut array = Vec::new();
+        for commit in commits {
+            let json = commit.clone().into_resource(store)?.to_json_ad()?;
+            array.push(serde_json::from_str::<serde_json::Value>(&json)?);
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&array)?)
+            .map_err(|e| format!("Could not write commit queue {:?}. {}", tmp_path, e))?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Posts the Commit to its server.
+/// Adds it to the queue of the store instead if that server can't be reached, or if earlier Commits are still queued.
+/// Returns an error if the server rejects the Commit, or if it can't be reached and the store has no queue.
+/// Returns true if the Commit was queued.
+pub fn post_or_queue(commit: &Commit, store: &impl Storelike) -> AtomicResult<bool> {
+    let queue = match store.get_commit_queue() {
+        Some(queue) => queue,
+        None => {
+            crate::client::post_commit(commit, store)?;
+            return Ok(false);
+        }
+    };
+    if !queue.is_empty(store)? {
+        queue.add(commit, store)?;
+        return Ok(true);
+    }
+    match try_post_commit(commit, store)? {
+        PostResult::Accepted => Ok(false),
+        PostResult::Rejected(reason) => Err(reason.into()),
+        PostResult::Unreachable(_reason) => {
+            queue.add(commit, store)?;
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{urls, Resource, Value};
+
+    #[test]
+    fn queue_offline_commits() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let agent = store.create_agent(Some("offline")).unwrap();
+        store.set_default_agent(agent);
+        let path = Path::new("tmp/queue_offline_commits.json");
+        let _try_remove_existing = std::fs::remove_file(path);
+        store.set_commit_queue(CommitQueue::open(path));
+
+        // Nothing listens on this port, so the Commits are queued
+        let subject = "http://localhost:1/offline";
+        let mut resource = Resource::new(subject.into());
+        resource
+            .set_propval(
+                urls::DESCRIPTION.into(),
+                Value::Markdown("first".into()),
+                &store,
+            )
+            .unwrap();
+        resource.save(&store).unwrap();
+        resource
+            .set_propval(
+                urls::DESCRIPTION.into(),
+                Value::Markdown("second".into()),
+                &store,
+            )
+            .unwrap();
+        resource.save(&store).unwrap();
+        // The changes are applied locally
+        let found = store.get_resource(subject).unwrap();
+        assert_eq!(found.get(urls::DESCRIPTION).unwrap().to_string(), "second");
+
+        let queue = store.get_commit_queue().unwrap();
+        let queued = queue.list(&store).unwrap();
+        assert_eq!(queued.len(), 2);
+        // The Commits are still signed correctly after reading them from the file
+        queued[0].validate_signature(&store).unwrap();
+        assert!(queued[0].created_at <= queued[1].created_at);
+
+        let report = queue.push(&store).unwrap();
+        assert!(report.accepted.is_empty());
+        assert!(report.rejected.is_empty());
+        assert_eq!(report.remaining, 2);
+        assert!(report.stopped.is_some());
+        assert_eq!(queue.list(&store).unwrap().len(), 2);
+    }
+}
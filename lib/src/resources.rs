@@ -61,6 +61,16 @@ impl Resource {
         &self.commit
     }
 
+    /// Returns the CommitBuilder with the changes to this Resource, based on its last known Commit.
+    /// Use this when signing the changes, so they can be merged with concurrent Commits.
+    pub fn get_commit_builder_with_base(&self) -> CommitBuilder {
+        let mut commitbuilder = self.commit.clone();
+        if let Ok(last) = self.get(crate::urls::LAST_COMMIT) {
+            commitbuilder.set_previous_commit(last.to_string());
+        }
+        commitbuilder
+    }
+
     /// Checks if the classes are there, if not, fetches them.
     /// Returns an empty vector if there are no classes found.
     pub fn get_classes(&self, store: &impl Storelike) -> AtomicResult<Vec<Class>> {
@@ -194,20 +204,22 @@ impl Resource {
     /// Saves the resource (with all the changes) to the store by creating a Commit.
     /// Uses default Agent to sign the Commit.
     /// Stores changes on the Subject's Server by sending a Commit.
+    /// If that server can't be reached and the store has a commit queue, the Commit is queued and applied locally.
     /// Returns the generated Commit.
     pub fn save(&mut self, store: &impl Storelike) -> AtomicResult<crate::Commit> {
         let agent = store.get_default_agent()?;
-        let commitbuilder = self.get_commit_builder().clone();
+        let commitbuilder = self.get_commit_builder_with_base();
         let commit = commitbuilder.sign(&agent, store)?;
         let should_post = store.get_self_url().is_none();
         if should_post {
             // First, post it to the store where the data must reside
-            crate::client::post_commit(&commit, store)?;
+            crate::queue::post_or_queue(&commit, store)?;
         }
         // If that succeeds, save it locally;
-        commit.apply(store)?;
+        let commit_resource = commit.apply(store)?;
         // then, reset the internal CommitBuiler.
         self.reset_commit_builder();
+        self.set_last_commit(commit_resource.get_subject());
         Ok(commit)
     }
 
@@ -218,17 +230,26 @@ impl Resource {
     /// Does not store these changes on the server of the Subject - the Commit will be lost, unless you handle it manually.
     pub fn save_locally(&mut self, store: &impl Storelike) -> AtomicResult<crate::Resource> {
         let agent = store.get_default_agent()?;
-        let commitbuilder = self.get_commit_builder().clone();
+        let commitbuilder = self.get_commit_builder_with_base();
         let commit = commitbuilder.sign(&agent, store)?;
-        commit.apply(store)?;
+        let resource = commit.apply(store)?;
         self.reset_commit_builder();
-        let resource = commit.into_resource(store)?;
+        self.set_last_commit(resource.get_subject());
         Ok(resource)
     }
 
+    /// Remembers the Commit that was just applied, so the next changes are based on it.
+    /// Does not add it to the CommitBuilder.
+    fn set_last_commit(&mut self, commit_url: &str) {
+        self.propvals.insert(
+            crate::urls::LAST_COMMIT.into(),
+            Value::AtomicUrl(commit_url.into()),
+        );
+    }
+
     /// Insert a Property/Value combination.
     /// Overwrites existing Property/Value.
-    /// Validates the datatype.
+    /// Validates the datatype and the Constraints of the Property.
     pub fn set_propval_string(
         &mut self,
         property_url: String,
@@ -244,11 +265,13 @@ impl Resource {
             )
         })?;
         let val = Value::new(value, &fullprop.data_type)?;
+        fullprop.check_value(&val)?;
         self.set_propval_unsafe(property_url, val)?;
         Ok(())
     }
 
     /// Inserts a Property/Value combination.
+    /// Checks the DataType and the Constraints (such as `minValue` or `pattern`) of the Property.
     /// Overwrites existing.
     /// Adds it to the commit builder.
     pub fn set_propval(
@@ -257,8 +280,10 @@ impl Resource {
         value: Value,
         store: &impl Storelike,
     ) -> AtomicResult<()> {
-        let required_datatype = store.get_property(&property)?.data_type;
+        let fullprop = store.get_property(&property)?;
+        let required_datatype = fullprop.data_type.clone();
         if required_datatype == value.datatype() {
+            fullprop.check_value(&value)?;
             self.set_propval_unsafe(property, value)
         } else {
             Err(format!("Datatype for subject '{}', property '{}', value '{}' did not match. Wanted '{}', got '{}'",
@@ -323,6 +348,23 @@ impl Resource {
         serde_json::to_string_pretty(&obj).map_err(|_| "Could not serialize to JSON".into())
     }
 
+    /// Converts Resource to plain JSON string.
+    /// Language-tagged strings use the text in the first available of the `languages` (e.g. from an `Accept-Language` header).
+    pub fn to_json_languages(
+        &self,
+        store: &impl Storelike,
+        languages: &[String],
+    ) -> AtomicResult<String> {
+        let obj = crate::serialize::propvals_to_json_ld_languages(
+            self.get_propvals(),
+            Some(self.get_subject().clone()),
+            store,
+            false,
+            languages,
+        )?;
+        serde_json::to_string_pretty(&obj).map_err(|_| "Could not serialize to JSON".into())
+    }
+
     /// Converts Resource to JSON-LD string, with @context object and RDF compatibility.
     pub fn to_json_ld(&self, store: &impl Storelike) -> AtomicResult<String> {
         let obj = crate::serialize::propvals_to_json_ld(
@@ -338,6 +380,19 @@ impl Resource {
     pub fn to_atoms(&self) -> AtomicResult<Vec<Atom>> {
         let mut atoms: Vec<Atom> = Vec::new();
         for (property, value) in self.propvals.iter() {
+            // Included Resources are added as Atoms of their own
+            if let Value::ResourceArrayNested(nested) = value {
+                atoms.push(Atom::new(
+                    self.subject.to_string(),
+                    property.clone(),
+                    value.to_subjects()?.into(),
+                ));
+                for sub in nested {
+                    let included = Resource::from_propvals(sub.propvals.clone(), sub.subject.clone());
+                    atoms.extend(included.to_atoms()?);
+                }
+                continue;
+            }
             let atom = Atom::new(
                 self.subject.to_string(),
                 property.clone(),
@@ -394,6 +449,49 @@ mod test {
         new_resource.check_required_props(&store).unwrap();
     }
 
+    #[test]
+    fn check_inherited_required_props() {
+        let store = init_store();
+        let class = |subject: &str, requires: &str, subclass_of: Vec<String>| {
+            let class = Class {
+                requires: vec![requires.into()],
+                recommends: Vec::new(),
+                shortname: "test-class".into(),
+                description: "A Class for testing inheritance".into(),
+                subject: subject.into(),
+                subclass_of,
+            };
+            store.add_resource_unsafe(&class.to_resource().unwrap()).unwrap();
+        };
+        let article = "https://localhost/classes/Article";
+        let blogpost = "https://localhost/classes/Blogpost";
+        class(article, urls::DESCRIPTION, Vec::new());
+        class(blogpost, urls::NAME, vec![article.into()]);
+        let resolved = store.get_class(blogpost).unwrap();
+        assert_eq!(resolved.requires, vec![urls::NAME.to_string(), urls::DESCRIPTION.into()]);
+
+        let mut post = Resource::new_instance(blogpost, &store).unwrap();
+        post.set_propval_string(urls::NAME.into(), "My post", &store).unwrap();
+        post.check_required_props(&store).unwrap_err();
+        post.set_propval_string(urls::DESCRIPTION.into(), "About posts", &store).unwrap();
+        post.check_required_props(&store).unwrap();
+        post.save_locally(&store).unwrap();
+
+        // Collections of a Class include the instances of its subclasses
+        let collection = crate::collections::CollectionBuilder::class_collection(article, "articles", &store)
+            .into_collection(&store)
+            .unwrap();
+        assert_eq!(collection.members, vec![post.get_subject().clone()]);
+
+        // Cycles are detected
+        let first = "https://localhost/classes/First";
+        let second = "https://localhost/classes/Second";
+        class(first, urls::NAME, vec![second.into()]);
+        class(second, urls::NAME, vec![first.into()]);
+        let err = store.get_class(first).unwrap_err();
+        assert!(err.to_string().contains("subclass of itself"));
+    }
+
     #[test]
     fn new_instance() {
         let store = init_store();
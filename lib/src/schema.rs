@@ -1,7 +1,10 @@
 //! Structs and models at the core of Atomic Schema (Class, Property, Datatype).
 
-use crate::{Resource, Value, datatype::DataType, errors::AtomicResult, urls};
+use crate::{Resource, Storelike, Value, datatype::DataType, errors::AtomicResult, urls};
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Mutex};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Property {
@@ -12,6 +15,8 @@ pub struct Property {
     pub shortname: String,
     pub subject: String,
     pub description: String,
+    /// Restrictions on the Values, besides the DataType
+    pub constraints: Constraints,
 }
 
 impl PartialEq for Property {
@@ -30,6 +35,7 @@ impl Property {
             Ok(classtype) => Some(classtype.to_string()),
             Err(_) => None,
         };
+        let constraints = Constraints::from_resource(&resource)?;
 
         Ok(Property {
             class_type,
@@ -37,6 +43,18 @@ impl Property {
             shortname,
             description,
             subject: resource.get_subject().into(),
+            constraints,
+        })
+    }
+
+    /// Checks if a Value meets the Constraints of the Property. Does not check the DataType.
+    pub fn check_value(&self, value: &Value) -> AtomicResult<()> {
+        self.constraints.check(value).map_err(|e| {
+            format!(
+                "Value '{}' is not allowed for property '{}' ({}). {}",
+                value, self.shortname, self.subject, e
+            )
+            .into()
         })
     }
 
@@ -50,11 +68,191 @@ impl Property {
         if let Some(classtype) = &self.class_type {
             resource.set_propval_unsafe(urls::CLASSTYPE_PROP.into(), Value::AtomicUrl(classtype.clone()))?;
         }
+        self.constraints.add_to_resource(&mut resource)?;
 
         Ok(resource)
     }
 }
 
+/// Restrictions on the Values of a Property, such as a numeric range or a list of allowed values.
+/// Defined using Properties on the Property Resource, such as `minValue` and `pattern`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Constraints {
+    /// Lowest allowed number, inclusive
+    pub min_value: Option<f64>,
+    /// Highest allowed number, inclusive
+    pub max_value: Option<f64>,
+    /// Minimum number of characters, or of items in a ResourceArray
+    pub min_length: Option<usize>,
+    /// Maximum number of characters, or of items in a ResourceArray
+    pub max_length: Option<usize>,
+    /// Regular expression that text must match. For ResourceArrays, every item must match.
+    pub pattern: Option<String>,
+    /// The only allowed Values. For ResourceArrays, every item must be one of these.
+    pub allowed_values: Option<Vec<String>>,
+}
+
+impl Constraints {
+    /// Reads the Constraints from a Property Resource
+    pub fn from_resource(resource: &Resource) -> AtomicResult<Constraints> {
+        let number = |prop: &str| -> AtomicResult<Option<f64>> {
+            match resource.get(prop) {
+                Ok(val) => Ok(Some(
+                    val.as_number()
+                        .ok_or(format!("{} must be a number, got {}", prop, val))?,
+                )),
+                Err(_) => Ok(None),
+            }
+        };
+        let length = |prop: &str| -> AtomicResult<Option<usize>> {
+            match resource.get(prop) {
+                Ok(val) => {
+                    let length = val.to_int()?;
+                    if length < 0 {
+                        return Err(format!("{} can't be negative, got {}", prop, length).into());
+                    }
+                    Ok(Some(length as usize))
+                }
+                Err(_) => Ok(None),
+            }
+        };
+        let pattern = match resource.get(urls::PATTERN) {
+            Ok(val) => {
+                let pattern = val.to_string();
+                compile_pattern(&pattern)?;
+                Some(pattern)
+            }
+            Err(_) => None,
+        };
+        Ok(Constraints {
+            min_value: number(urls::MIN_VALUE)?,
+            max_value: number(urls::MAX_VALUE)?,
+            min_length: length(urls::MIN_LENGTH)?,
+            max_length: length(urls::MAX_LENGTH)?,
+            pattern,
+            allowed_values: match resource.get(urls::ALLOWED_VALUES) {
+                Ok(val) => Some(val.to_vec()?.clone()),
+                Err(_) => None,
+            },
+        })
+    }
+
+    fn add_to_resource(&self, resource: &mut Resource) -> AtomicResult<()> {
+        if let Some(min) = self.min_value {
+            resource.set_propval_unsafe(urls::MIN_VALUE.into(), Value::Float(min))?;
+        }
+        if let Some(max) = self.max_value {
+            resource.set_propval_unsafe(urls::MAX_VALUE.into(), Value::Float(max))?;
+        }
+        if let Some(min) = self.min_length {
+            resource.set_propval_unsafe(urls::MIN_LENGTH.into(), Value::Integer(min as i64))?;
+        }
+        if let Some(max) = self.max_length {
+            resource.set_propval_unsafe(urls::MAX_LENGTH.into(), Value::Integer(max as i64))?;
+        }
+        if let Some(pattern) = &self.pattern {
+            resource.set_propval_unsafe(urls::PATTERN.into(), Value::String(pattern.clone()))?;
+        }
+        if let Some(allowed) = &self.allowed_values {
+            resource.set_propval_unsafe(urls::ALLOWED_VALUES.into(), Value::ResourceArray(allowed.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Checks if a Value meets all Constraints. Returns an error that describes the first one that is violated.
+    pub fn check(&self, value: &Value) -> AtomicResult<()> {
+        if let Some(number) = value.as_number() {
+            if let Some(min) = self.min_value {
+                if number < min {
+                    return Err(format!("It must be at least {}.", min).into());
+                }
+            }
+            if let Some(max) = self.max_value {
+                if number > max {
+                    return Err(format!("It must be at most {}.", max).into());
+                }
+            }
+        }
+        // Every translation is checked like a single text
+        if let Value::LangString(lang_string) = value {
+            for text in lang_string.texts().values() {
+                self.check(&Value::String(text.clone()))?;
+            }
+            return Ok(());
+        }
+        // Texts are checked as a whole, ResourceArrays by their items
+        let items = match value {
+            Value::ResourceArray(items) => items.clone(),
+            Value::Integer(_) | Value::Float(_) | Value::Timestamp(_) | Value::Boolean(_) => {
+                Vec::new()
+            }
+            Value::NestedResource(_)
+            | Value::ResourceArrayNested(_)
+            | Value::Unsupported(_) => return Ok(()),
+            text => vec![text.to_string()],
+        };
+        let length = match value {
+            Value::ResourceArray(items) => Some(items.len()),
+            _ if items.is_empty() => None,
+            _ => Some(items[0].chars().count()),
+        };
+        if let Some(length) = length {
+            let unit = match value {
+                Value::ResourceArray(_) => "items",
+                _ => "characters",
+            };
+            if let Some(min) = self.min_length {
+                if length < min {
+                    return Err(format!("It must have at least {} {}.", min, unit).into());
+                }
+            }
+            if let Some(max) = self.max_length {
+                if length > max {
+                    return Err(format!("It must have at most {} {}.", max, unit).into());
+                }
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            let re = compile_pattern(pattern)?;
+            if let Some(item) = items.iter().find(|item| !re.is_match(item)) {
+                return Err(format!("'{}' does not match the pattern {}.", item, pattern).into());
+            }
+        }
+        if let Some(allowed) = &self.allowed_values {
+            let not_allowed = match value {
+                Value::ResourceArray(_) => items.iter().find(|item| !allowed.contains(item)).cloned(),
+                other if !allowed.contains(&other.to_string()) => Some(other.to_string()),
+                _ => None,
+            };
+            if let Some(item) = not_allowed {
+                return Err(format!("'{}' is not one of the allowed values: {}.", item, allowed.join(", ")).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    /// Compiled `pattern` Constraints. Properties are read again for every Value that is checked, so their patterns are compiled only once here.
+    static ref PATTERNS: Mutex<BTreeMap<String, Regex>> = Mutex::new(BTreeMap::new());
+}
+/// The compiled patterns are cleared when there are more than this, so they can't use unlimited memory
+const MAX_PATTERNS: usize = 1000;
+
+/// Compiles a `pattern` Constraint, or returns it from the cache
+fn compile_pattern(pattern: &str) -> AtomicResult<Regex> {
+    let mut patterns = PATTERNS.lock().unwrap();
+    if let Some(re) = patterns.get(pattern) {
+        return Ok(re.clone());
+    }
+    let re = Regex::new(pattern).map_err(|e| format!("Invalid pattern {}. {}", pattern, e))?;
+    if patterns.len() >= MAX_PATTERNS {
+        patterns.clear();
+    }
+    patterns.insert(pattern.into(), re.clone());
+    Ok(re)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Class {
     pub requires: Vec<String>,
@@ -63,6 +261,8 @@ pub struct Class {
     pub description: String,
     /// URL
     pub subject: String,
+    /// URLs of the Classes that this Class is a subclass of
+    pub subclass_of: Vec<String>,
 }
 
 impl Class {
@@ -82,6 +282,13 @@ impl Class {
             }
         }
 
+        let mut subclass_of = Vec::new();
+        if let Ok(parents) = resource.get(urls::SUBCLASS_OF) {
+            for parent in parents.to_vec()? {
+                subclass_of.push(parent.clone())
+            }
+        }
+
         let shortname = resource.get(urls::SHORTNAME)?.to_string();
         let description = resource.get(urls::DESCRIPTION)?.to_string();
 
@@ -91,9 +298,36 @@ impl Class {
             shortname,
             subject: resource.get_subject().into(),
             description,
+            subclass_of,
         })
     }
 
+    /// Returns all Classes that this Class is (indirectly) a subclass of, nearest first.
+    /// Fails if a Class turns out to be a subclass of itself.
+    pub fn get_superclasses(&self, store: &impl Storelike) -> AtomicResult<Vec<Class>> {
+        let mut found = Vec::new();
+        let mut path = vec![self.subject.clone()];
+        collect_superclasses(self, store, &mut path, &mut found)?;
+        Ok(found)
+    }
+
+    /// Adds the required and recommended Properties of all superclasses (see `get_superclasses`) to this Class.
+    pub fn inherit(&mut self, store: &impl Storelike) -> AtomicResult<()> {
+        for superclass in self.get_superclasses(store)? {
+            for prop in superclass.requires {
+                if !self.requires.contains(&prop) {
+                    self.requires.push(prop);
+                }
+            }
+            for prop in superclass.recommends {
+                if !self.recommends.contains(&prop) && !self.requires.contains(&prop) {
+                    self.recommends.push(prop);
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Converts Class to a Resource
     pub fn to_resource(&self) -> AtomicResult<Resource> {
         let mut resource = Resource::new(self.subject.clone());
@@ -103,9 +337,61 @@ impl Class {
         if !self.requires.is_empty() {
             resource.set_propval_unsafe(urls::REQUIRES.into(), Value::ResourceArray(self.requires.clone()))?;
         }
-        if !self.requires.is_empty() {
+        if !self.recommends.is_empty() {
             resource.set_propval_unsafe(urls::RECOMMENDS.into(), Value::ResourceArray(self.recommends.clone()))?;
         }
+        if !self.subclass_of.is_empty() {
+            resource.set_propval_unsafe(urls::SUBCLASS_OF.into(), Value::ResourceArray(self.subclass_of.clone()))?;
+        }
         Ok(resource)
     }
 }
+
+/// Walks up the `subclass_of` tree. `path` contains the Classes between the original Class and this one, to detect cycles.
+fn collect_superclasses(
+    class: &Class,
+    store: &impl Storelike,
+    path: &mut Vec<String>,
+    found: &mut Vec<Class>,
+) -> AtomicResult<()> {
+    for parent in &class.subclass_of {
+        if path.contains(parent) {
+            return Err(format!(
+                "Class {} is a subclass of itself: {} -> {}",
+                parent,
+                path.join(" -> "),
+                parent
+            )
+            .into());
+        }
+        // Classes can be reached through multiple parents
+        if found.iter().any(|c| &c.subject == parent) {
+            continue;
+        }
+        let resource = store
+            .get_resource(parent)
+            .map_err(|e| format!("Failed getting superclass {} of {}. {}", parent, class.subject, e))?;
+        let superclass = Class::from_resource(resource)?;
+        found.push(superclass.clone());
+        path.push(parent.clone());
+        collect_superclasses(&superclass, store, path, found)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Returns the Class and all Classes that are (indirectly) a subclass of it.
+/// Used for finding instances of a Class, including those of its subclasses.
+pub fn get_class_and_subclasses(store: &impl Storelike, class: &str) -> AtomicResult<Vec<String>> {
+    let mut classes = vec![class.to_string()];
+    let mut i = 0;
+    while i < classes.len() {
+        for subclass in store.subjects_with_propval(urls::SUBCLASS_OF, Some(&classes[i]), true)? {
+            if !classes.contains(&subclass) {
+                classes.push(subclass);
+            }
+        }
+        i += 1;
+    }
+    Ok(classes)
+}
@@ -0,0 +1,864 @@
+//! Converts your own Rust types to Resources and back, using [serde](https://serde.rs).
+//! Fields are mapped to Properties by their shortname, or by their URL if you rename the field,
+//! e.g. `#[serde(rename = "https://atomicdata.dev/properties/description")]`.
+//! Shortnames are resolved using the required and recommended Properties of the Class.
+//! Values are checked against the DataType and Constraints of their Property.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Person {
+//!     shortname: String,
+//!     description: String,
+//! }
+//!
+//! let store = atomic_lib::Store::init().unwrap();
+//! let person = Person { shortname: "joep".into(), description: "Likes data".into() };
+//! let resource = atomic_lib::serde_resource::to_resource(
+//!     &person,
+//!     "https://example.com/joep",
+//!     atomic_lib::urls::CLASS,
+//!     &store,
+//! ).unwrap();
+//! let back: Person = atomic_lib::serde_resource::from_resource(&resource, &store).unwrap();
+//! assert_eq!(person, back);
+//! ```
+
+use std::{collections::HashMap, convert::TryFrom, fmt};
+
+use serde::{
+    de::{self, value::SeqDeserializer, DeserializeOwned, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Impossible},
+    Serialize,
+};
+
+use crate::{
+    datatype::DataType,
+    errors::AtomicResult,
+    mapping::is_url,
+    resources::PropVals,
+    schema::{Class, Property},
+    urls, Resource, Storelike, Value,
+};
+
+/// A field with this name contains the subject of the Resource when deserializing.
+/// It is ignored when serializing.
+pub const SUBJECT_FIELD: &str = "@id";
+
+/// Error returned by the Serializer and Deserializer
+#[derive(Debug)]
+pub struct SerdeError(String);
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError(msg.to_string())
+    }
+}
+
+/// Serializes a struct (or map) to a Resource that is an instance of `class`.
+/// The Propvals are also added to the CommitBuilder, so you can `save` the Resource.
+pub fn to_resource<T: Serialize>(
+    value: &T,
+    subject: &str,
+    class: &str,
+    store: &impl Storelike,
+) -> AtomicResult<Resource> {
+    let class = store.get_class(class)?;
+    let properties = class_properties(std::slice::from_ref(&class), store)?;
+    let propvals = value.serialize(ResourceSerializer {
+        properties: &properties,
+        store,
+    })?;
+    let mut resource = Resource::new(subject.into());
+    resource.set_propval_unsafe(urls::IS_A.into(), Value::ResourceArray(vec![class.subject]))?;
+    for (property, value) in propvals {
+        resource.set_propval_unsafe(property, value)?;
+    }
+    Ok(resource)
+}
+
+/// Deserializes a Resource to a struct (or map).
+/// Shortnames are resolved using the Classes of the Resource and the Properties it has.
+pub fn from_resource<T: DeserializeOwned>(
+    resource: &Resource,
+    store: &impl Storelike,
+) -> AtomicResult<T> {
+    let classes = resource.get_classes(store)?;
+    let mut properties = class_properties(&classes, store)?;
+    for property_url in resource.get_propvals().keys() {
+        if let Ok(property) = store.get_property(property_url) {
+            properties
+                .entry(property.shortname.clone())
+                .or_insert(property);
+        }
+    }
+    Ok(T::deserialize(ResourceDeserializer {
+        resource,
+        properties: &properties,
+    })?)
+}
+
+/// Maps the shortnames of the required and recommended Properties of the Classes to the Properties
+fn class_properties(
+    classes: &[Class],
+    store: &impl Storelike,
+) -> AtomicResult<HashMap<String, Property>> {
+    let mut properties = HashMap::new();
+    for class in classes {
+        for prop_subject in class.requires.iter().chain(class.recommends.iter()) {
+            let property = store.get_property(prop_subject)?;
+            properties
+                .entry(property.shortname.clone())
+                .or_insert(property);
+        }
+    }
+    Ok(properties)
+}
+
+/// Serializes structs and maps to PropVals
+pub struct ResourceSerializer<'a, S: Storelike> {
+    /// Properties by shortname
+    pub properties: &'a HashMap<String, Property>,
+    pub store: &'a S,
+}
+
+impl<'a, S: Storelike> Clone for ResourceSerializer<'a, S> {
+    fn clone(&self) -> Self {
+        ResourceSerializer {
+            properties: self.properties,
+            store: self.store,
+        }
+    }
+}
+
+impl<'a, S: Storelike> ResourceSerializer<'a, S> {
+    fn get_property(&self, field: &str) -> Result<Property, SerdeError> {
+        if is_url(field) {
+            return self
+                .store
+                .get_property(field)
+                .map_err(|e| SerdeError(e.to_string()));
+        }
+        self.properties.get(field).cloned().ok_or_else(|| {
+            SerdeError(format!(
+                "Field '{}' is not the shortname of a Property of the Class. Rename it to the URL of a Property.",
+                field
+            ))
+        })
+    }
+}
+
+fn not_a_struct() -> SerdeError {
+    SerdeError("Only structs and maps can be serialized to a Resource".into())
+}
+
+/// Implements the Serializer methods for primitives by returning an error
+macro_rules! unsupported_primitives {
+    ($error:expr; $($method:ident: $type:ty),*) => {
+        $(fn $method(self, _v: $type) -> Result<Self::Ok, Self::Error> {
+            Err($error)
+        })*
+    };
+}
+
+impl<'a, S: Storelike> ser::Serializer for ResourceSerializer<'a, S> {
+    type Ok = PropVals;
+    type Error = SerdeError;
+    type SerializeSeq = Impossible<PropVals, SerdeError>;
+    type SerializeTuple = Impossible<PropVals, SerdeError>;
+    type SerializeTupleStruct = Impossible<PropVals, SerdeError>;
+    type SerializeTupleVariant = Impossible<PropVals, SerdeError>;
+    type SerializeMap = PropValsSerializer<'a, S>;
+    type SerializeStruct = PropValsSerializer<'a, S>;
+    type SerializeStructVariant = Impossible<PropVals, SerdeError>;
+
+    unsupported_primitives!(not_a_struct();
+        serialize_bool: bool, serialize_i8: i8, serialize_i16: i16, serialize_i32: i32,
+        serialize_i64: i64, serialize_u8: u8, serialize_u16: u16, serialize_u32: u32,
+        serialize_u64: u64, serialize_f32: f32, serialize_f64: f64, serialize_char: char,
+        serialize_str: &str, serialize_bytes: &[u8], serialize_unit_struct: &'static str);
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(PropValsSerializer {
+            serializer: self,
+            propvals: PropVals::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(not_a_struct())
+    }
+}
+
+/// Collects the fields of a struct or map in PropVals
+pub struct PropValsSerializer<'a, S: Storelike> {
+    serializer: ResourceSerializer<'a, S>,
+    propvals: PropVals,
+    /// The last key of a map
+    key: Option<String>,
+}
+
+impl<'a, S: Storelike> PropValsSerializer<'a, S> {
+    fn insert<T: ?Sized + Serialize>(&mut self, field: &str, value: &T) -> Result<(), SerdeError> {
+        if field == SUBJECT_FIELD {
+            return Ok(());
+        }
+        let property = self.serializer.get_property(field)?;
+        if let Some(value) = value.serialize(ValueSerializer {
+            property: &property,
+        })? {
+            property
+                .check_value(&value)
+                .map_err(|e| SerdeError(e.to_string()))?;
+            self.propvals.insert(property.subject, value);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, S: Storelike> ser::SerializeStruct for PropValsSerializer<'a, S> {
+    type Ok = PropVals;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.propvals)
+    }
+}
+
+impl<'a, S: Storelike> ser::SerializeMap for PropValsSerializer<'a, S> {
+    type Ok = PropVals;
+    type Error = SerdeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
+        match key.serialize(serde_json::value::Serializer) {
+            Ok(serde_json::Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(SerdeError("Keys of maps must be strings".into())),
+        }
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerdeError("Value without a key".into()))?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.propvals)
+    }
+}
+
+/// Serializes a field to the Value of a Property.
+/// Returns `None` for `None` and `()`, so these fields are left out.
+pub struct ValueSerializer<'a> {
+    pub property: &'a Property,
+}
+
+impl<'a> ValueSerializer<'a> {
+    fn mismatch(&self, rust_type: &str) -> SerdeError {
+        SerdeError(format!(
+            "Property {} has datatype {}, which can't hold a {}",
+            self.property.subject, self.property.data_type, rust_type
+        ))
+    }
+
+    fn integer(self, int: i64) -> Result<Option<Value>, SerdeError> {
+        match self.property.data_type {
+            DataType::Integer => Ok(Some(Value::Integer(int))),
+            DataType::Timestamp => Ok(Some(Value::Timestamp(int))),
+            DataType::Float => Ok(Some(Value::Float(int as f64))),
+            _ => Err(self.mismatch("number")),
+        }
+    }
+
+    fn float(self, float: f64) -> Result<Option<Value>, SerdeError> {
+        match self.property.data_type {
+            DataType::Float => Ok(Some(Value::Float(float))),
+            _ => Err(self.mismatch("float")),
+        }
+    }
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = Option<Value>;
+    type Error = SerdeError;
+    type SerializeSeq = ArraySerializer;
+    type SerializeTuple = ArraySerializer;
+    type SerializeTupleStruct = Impossible<Option<Value>, SerdeError>;
+    type SerializeTupleVariant = Impossible<Option<Value>, SerdeError>;
+    type SerializeMap = Impossible<Option<Value>, SerdeError>;
+    type SerializeStruct = Impossible<Option<Value>, SerdeError>;
+    type SerializeStructVariant = Impossible<Option<Value>, SerdeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        match self.property.data_type {
+            DataType::Boolean => Ok(Some(Value::Boolean(v))),
+            _ => Err(self.mismatch("boolean")),
+        }
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.integer(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.integer(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.integer(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.integer(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.integer(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.integer(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        let int = i64::try_from(v).map_err(|e| SerdeError(e.to_string()))?;
+        self.integer(int)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.float(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(&v.to_string())
+    }
+
+    /// Strings are parsed with the DataType of the Property, so they are validated
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        match self.property.data_type {
+            DataType::ResourceArray => Err(self.mismatch("string")),
+            _ => Value::new(v, &self.property.data_type)
+                .map(Some)
+                .map_err(|e| SerdeError(e.to_string())),
+        }
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(self.mismatch("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    /// Enums without data are serialized as the name of the variant
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(self.mismatch("enum variant with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        match self.property.data_type {
+            DataType::ResourceArray => Ok(ArraySerializer {
+                subjects: Vec::new(),
+            }),
+            _ => Err(self.mismatch("sequence")),
+        }
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(self.mismatch("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(self.mismatch("enum variant with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(self.mismatch("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(self.mismatch("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(self.mismatch("enum variant with data"))
+    }
+}
+
+/// Serializes a sequence of strings to a ResourceArray
+pub struct ArraySerializer {
+    subjects: Vec<String>,
+}
+
+impl ArraySerializer {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        match value.serialize(serde_json::value::Serializer) {
+            Ok(serde_json::Value::String(subject)) => {
+                self.subjects.push(subject);
+                Ok(())
+            }
+            _ => Err(SerdeError(
+                "Items of a ResourceArray must be strings".into(),
+            )),
+        }
+    }
+}
+
+impl ser::SerializeSeq for ArraySerializer {
+    type Ok = Option<Value>;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::ResourceArray(self.subjects)))
+    }
+}
+
+impl ser::SerializeTuple for ArraySerializer {
+    type Ok = Option<Value>;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::ResourceArray(self.subjects)))
+    }
+}
+
+/// Deserializes a Resource to structs and maps.
+/// Maps get the shortnames of known Properties as keys, and URLs for the others.
+pub struct ResourceDeserializer<'a> {
+    pub resource: &'a Resource,
+    /// Properties by shortname
+    pub properties: &'a HashMap<String, Property>,
+}
+
+impl<'de, 'a> de::Deserializer<'de> for ResourceDeserializer<'a> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let shortnames: HashMap<&String, &String> = self
+            .properties
+            .values()
+            .map(|p| (&p.subject, &p.shortname))
+            .collect();
+        let entries = self
+            .resource
+            .get_propvals()
+            .iter()
+            .map(|(url, value)| {
+                let key = shortnames.get(url).copied().unwrap_or(url);
+                (key.clone(), value.clone())
+            })
+            .collect();
+        visitor.visit_map(EntriesAccess::new(entries))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let mut entries = Vec::new();
+        for field in fields {
+            if *field == SUBJECT_FIELD {
+                let subject = Value::AtomicUrl(self.resource.get_subject().clone());
+                entries.push((field.to_string(), subject));
+                continue;
+            }
+            let property_url = if is_url(field) {
+                Some(field.to_string())
+            } else {
+                self.properties.get(*field).map(|p| p.subject.clone())
+            };
+            if let Some(value) = property_url.and_then(|url| self.resource.get(&url).ok()) {
+                entries.push((field.to_string(), value.clone()));
+            }
+        }
+        visitor.visit_map(EntriesAccess::new(entries))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Passes keys and Values to a map or struct Visitor
+struct EntriesAccess {
+    entries: std::vec::IntoIter<(String, Value)>,
+    value: Option<Value>,
+}
+
+impl EntriesAccess {
+    fn new(entries: Vec<(String, Value)>) -> Self {
+        EntriesAccess {
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for EntriesAccess {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| SerdeError("Value without a key".into()))?;
+        seed.deserialize(ValueDeserializer(value))
+    }
+}
+
+/// Deserializes a single Value
+pub struct ValueDeserializer(pub Value);
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::AtomicUrl(s)
+            | Value::Date(s)
+            | Value::Markdown(s)
+            | Value::Slug(s)
+            | Value::String(s) => visitor.visit_string(s),
+            Value::Unsupported(unsupported) => visitor.visit_string(unsupported.value),
+            Value::Integer(int) | Value::Timestamp(int) => visitor.visit_i64(int),
+            Value::Float(float) => visitor.visit_f64(float),
+            Value::Boolean(bool) => visitor.visit_bool(bool),
+            Value::ResourceArray(subjects) => {
+                SeqDeserializer::new(subjects.into_iter()).deserialize_any(visitor)
+            }
+            Value::ResourceArrayNested(nested) => {
+                SeqDeserializer::new(nested.into_iter().map(|sub| sub.subject))
+                    .deserialize_any(visitor)
+            }
+            Value::NestedResource(propvals) => {
+                visitor.visit_map(EntriesAccess::new(propvals.into_iter().collect()))
+            }
+            Value::LangString(lang_string) => {
+                de::value::MapDeserializer::new(lang_string.into_texts().into_iter())
+                    .deserialize_any(visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Enums without data are deserialized from the name of the variant
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.0.to_string().into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Store;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct MyProperty {
+        #[serde(rename = "@id", default)]
+        subject: String,
+        shortname: String,
+        description: String,
+        datatype: String,
+        #[serde(rename = "https://atomicdata.dev/properties/classtype")]
+        class_type: Option<String>,
+    }
+
+    #[test]
+    fn serialize_deserialize_roundtrip() {
+        let store = Store::init().unwrap();
+        store.populate().unwrap();
+        let subject = "https://localhost/properties/test";
+        let property = MyProperty {
+            subject: subject.into(),
+            shortname: "test".into(),
+            description: "A **test** Property".into(),
+            datatype: urls::STRING.into(),
+            class_type: None,
+        };
+        let resource = to_resource(&property, subject, urls::PROPERTY, &store).unwrap();
+        assert!(matches!(
+            resource.get(urls::SHORTNAME).unwrap(),
+            Value::Slug(_)
+        ));
+        assert!(resource.get(urls::CLASSTYPE_PROP).is_err());
+        store.add_resource(&resource).unwrap();
+        assert_eq!(
+            store.get_property(subject).unwrap().data_type,
+            DataType::String
+        );
+
+        let back: MyProperty = from_resource(&resource, &store).unwrap();
+        assert_eq!(back, property);
+        let map: HashMap<String, serde_json::Value> = from_resource(&resource, &store).unwrap();
+        assert_eq!(map.get("shortname").unwrap(), "test");
+
+        // Values are checked against the DataType
+        let invalid = MyProperty {
+            shortname: "Not a slug".into(),
+            ..property
+        };
+        assert!(to_resource(&invalid, subject, urls::PROPERTY, &store).is_err());
+
+        #[derive(Serialize)]
+        struct WrongType {
+            description: i64,
+        }
+        let err = to_resource(
+            &WrongType { description: 1 },
+            subject,
+            urls::PROPERTY,
+            &store,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("can't hold a number"), "{}", err);
+
+        #[derive(Serialize)]
+        struct UnknownField {
+            color: String,
+        }
+        let unknown = UnknownField {
+            color: "red".into(),
+        };
+        assert!(to_resource(&unknown, subject, urls::PROPERTY, &store).is_err());
+    }
+}
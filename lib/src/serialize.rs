@@ -37,10 +37,26 @@ fn val_to_serde(value: Value) -> AtomicResult<SerdeValue> {
         Value::Slug(val) => SerdeValue::String(val),
         Value::String(val) => SerdeValue::String(val),
         Value::Timestamp(val) => SerdeValue::Number(val.into()),
-        Value::Unsupported(val) => SerdeValue::String(val.value),
+        Value::Unsupported(val) => match crate::datatype::get_custom_datatype(&val.datatype) {
+            Some(custom) => (custom.to_json)(&val.value),
+            None => SerdeValue::String(val.value),
+        },
         Value::Boolean(val) => SerdeValue::Bool(val),
+        Value::LangString(val) => SerdeValue::Object(
+            val.into_texts()
+                .into_iter()
+                .map(|(language, text)| (language, SerdeValue::String(text)))
+                .collect(),
+        ),
         // TODO: fix this for nested resources in json and json-ld serialization, because this will cause them to fall back to json-ad
         Value::NestedResource(res) => propvals_to_json_map(&res, None)?,
+        Value::ResourceArrayNested(nested) => {
+            let mut items = Vec::new();
+            for sub in nested {
+                items.push(propvals_to_json_map(&sub.propvals, Some(sub.subject))?);
+            }
+            SerdeValue::Array(items)
+        }
     };
     Ok(json_val)
 }
@@ -67,6 +83,19 @@ pub fn propvals_to_json_ld(
     subject: Option<String>,
     store: &impl Storelike,
     json_ld: bool,
+) -> AtomicResult<serde_json::Value> {
+    propvals_to_json_ld_languages(propvals, subject, store, json_ld, &[])
+}
+
+/// Serializes a Resource to a Serde JSON Map.
+/// In plain JSON, language-tagged strings are serialized as the text in the first available of the `languages`.
+/// JSON-LD uses language maps instead.
+pub fn propvals_to_json_ld_languages(
+    propvals: &PropVals,
+    subject: Option<String>,
+    store: &impl Storelike,
+    json_ld: bool,
+    languages: &[String],
 ) -> AtomicResult<serde_json::Value> {
     // Initiate JSON object
     let mut root = Map::new();
@@ -80,6 +109,13 @@ pub fn propvals_to_json_ld(
             // In JSON-LD, the value of a Context Item can be a string or an object.
             // This object can contain information about the translation or datatype of the value
             let ctx_value: SerdeValue = match value.datatype() {
+                // Language-tagged strings are JSON-LD language maps
+                _ if matches!(value, Value::LangString(_)) => {
+                    let mut obj = Map::new();
+                    obj.insert("@id".into(), prop_url.as_str().into());
+                    obj.insert("@container".into(), "@language".into());
+                    obj.into()
+                }
                 DataType::AtomicUrl => {
                     let mut obj = Map::new();
                     obj.insert("@id".into(), prop_url.as_str().into());
@@ -119,7 +155,27 @@ pub fn propvals_to_json_ld(
         }
         let key = property.shortname;
 
-        root.insert(key, val_to_serde(value.clone())?);
+        let json_val = match value {
+            // Included Resources use shortnames as well
+            Value::ResourceArrayNested(nested) => {
+                let mut items = Vec::new();
+                for sub in nested {
+                    items.push(propvals_to_json_ld_languages(
+                        &sub.propvals,
+                        Some(sub.subject.clone()),
+                        store,
+                        json_ld,
+                        languages,
+                    )?);
+                }
+                SerdeValue::Array(items)
+            }
+            Value::LangString(lang_string) if !json_ld => {
+                SerdeValue::String(lang_string.select(languages).1.clone())
+            }
+            other => val_to_serde(other.clone())?,
+        };
+        root.insert(key, json_val);
     }
 
     if let Some(sub) = subject {
@@ -139,6 +195,19 @@ pub fn serialize_json_array(items: &[String]) -> AtomicResult<String> {
     Ok(string)
 }
 
+/// The datatype of RDF literals. Registered Datatypes can use a different one, such as an XSD datatype.
+#[cfg(feature = "rdf")]
+fn rdf_datatype_url(datatype: &DataType) -> String {
+    if let DataType::Unsupported(url) = datatype {
+        if let Some(rdf_datatype) =
+            crate::datatype::get_custom_datatype(url).and_then(|custom| custom.rdf_datatype)
+        {
+            return rdf_datatype;
+        }
+    }
+    datatype.to_string()
+}
+
 #[cfg(feature = "rdf")]
 /// Serializes Atoms to Ntriples (which is also valid Turtle / Notation3).
 pub fn atoms_to_ntriples(atoms: Vec<Atom>, store: &impl Storelike) -> AtomicResult<String> {
@@ -152,9 +221,19 @@ pub fn atoms_to_ntriples(atoms: Vec<Atom>, store: &impl Storelike) -> AtomicResu
         let predicate = NamedNode {
             iri: &atom.property,
         };
+        if let Value::LangString(lang_string) = &atom.value {
+            for (language, value) in lang_string.texts() {
+                formatter.format(&Triple {
+                    subject,
+                    predicate,
+                    object: Literal::LanguageTaggedString { value, language }.into(),
+                })?
+            }
+            continue;
+        }
         let datatype = store.get_property(&atom.property)?.data_type;
         let value = &atom.value.to_string();
-        let datatype_url = datatype.to_string();
+        let datatype_url = rdf_datatype_url(&datatype);
         let object: Term = match &datatype {
             DataType::AtomicUrl => NamedNode { iri: value }.into(),
             // Maybe these should be converted to RDF collections / lists?
@@ -190,9 +269,19 @@ pub fn atoms_to_turtle(atoms: Vec<Atom>, store: &impl Storelike) -> AtomicResult
         let predicate = NamedNode {
             iri: &atom.property,
         };
+        if let Value::LangString(lang_string) = &atom.value {
+            for (language, value) in lang_string.texts() {
+                formatter.format(&Triple {
+                    subject,
+                    predicate,
+                    object: Literal::LanguageTaggedString { value, language }.into(),
+                })?
+            }
+            continue;
+        }
         let datatype = store.get_property(&atom.property)?.data_type;
         let value = &atom.value.to_string();
-        let datatype_url = datatype.to_string();
+        let datatype_url = rdf_datatype_url(&datatype);
         let object: Term = match &datatype {
             DataType::AtomicUrl => NamedNode { iri: value }.into(),
             // Maybe these should be converted to RDF collections / lists?
@@ -350,6 +439,60 @@ mod test {
         assert_eq!(our_value, correct_value)
     }
 
+    #[test]
+    fn serialize_lang_strings() {
+        let store = crate::Store::init().unwrap();
+        store.populate().unwrap();
+        let json_ad = r#"{
+            "@id": "https://localhost/bilingual",
+            "https://atomicdata.dev/properties/shortname": "bilingual",
+            "https://atomicdata.dev/properties/description": {"en": "Hello", "nl": "Hallo"}
+        }"#;
+        let resource = crate::parse::parse_json_ad_resource(json_ad, &store).unwrap();
+        let description = resource.get(crate::urls::DESCRIPTION).unwrap();
+        assert!(matches!(description, Value::LangString(_)));
+        assert_eq!(description.datatype(), DataType::Markdown);
+
+        let json_ad: SerdeValue = serde_json::from_str(&resource.to_json_ad().unwrap()).unwrap();
+        assert_eq!(json_ad[crate::urls::DESCRIPTION]["nl"], "Hallo");
+        let json = |languages: &[&str]| {
+            let languages: Vec<String> = languages.iter().map(|l| l.to_string()).collect();
+            let json = resource.to_json_languages(&store, &languages).unwrap();
+            serde_json::from_str::<SerdeValue>(&json).unwrap()["description"].clone()
+        };
+        assert_eq!(json(&["nl"]), "Hallo");
+        assert_eq!(json(&["fr", "en-US"]), "Hello");
+        assert_eq!(json(&[]), "Hello");
+        let json_ld: SerdeValue = serde_json::from_str(&resource.to_json_ld(&store).unwrap()).unwrap();
+        assert_eq!(json_ld["@context"]["description"]["@container"], "@language");
+        assert_eq!(json_ld["description"]["en"], "Hello");
+
+        #[cfg(feature = "rdf")]
+        {
+            let ntriples = atoms_to_ntriples(resource.to_atoms().unwrap(), &store).unwrap();
+            assert!(ntriples.contains(r#""Hallo"@nl"#), "{}", ntriples);
+            assert!(ntriples.contains(r#""Hello"@en"#));
+        }
+
+        let valid = r#"{"@id": "https://localhost/valid", "https://atomicdata.dev/properties/description": {"en": "Hello"}}"#;
+        assert!(crate::parse::parse_json_ad_resource(valid, &store).is_ok());
+        let invalid_tag = r#"{"@id": "https://localhost/invalid", "https://atomicdata.dev/properties/description": {"not a tag": "Hello"}}"#;
+        assert!(crate::parse::parse_json_ad_resource(invalid_tag, &store).is_err());
+        let not_a_string = r#"{"@id": "https://localhost/invalid", "https://atomicdata.dev/properties/description": {"en": 1}}"#;
+        assert!(crate::parse::parse_json_ad_resource(not_a_string, &store).is_err());
+
+        // Deserialized LangStrings are checked as well, e.g. when they are read from a Db
+        let lang_string = match description {
+            Value::LangString(lang_string) => lang_string,
+            _ => unreachable!(),
+        };
+        let mut serialized = serde_json::to_value(lang_string).unwrap();
+        let parsed: crate::values::LangString = serde_json::from_value(serialized.clone()).unwrap();
+        assert_eq!(&parsed, lang_string);
+        serialized["texts"] = serde_json::json!({});
+        assert!(serde_json::from_value::<crate::values::LangString>(serialized).is_err());
+    }
+
     #[test]
     #[cfg(feature = "rdf")]
     fn serialize_ntriples() {
@@ -14,6 +14,7 @@ pub struct Store {
     // The store currently holds two stores - that is not ideal
     hashmap: Arc<Mutex<HashMap<String, Resource>>>,
     default_agent: Arc<Mutex<Option<crate::agents::Agent>>>,
+    commit_queue: Arc<Mutex<Option<crate::queue::CommitQueue>>>,
 }
 
 impl Store {
@@ -23,10 +24,17 @@ impl Store {
         let store = Store {
             hashmap: Arc::new(Mutex::new(HashMap::new())),
             default_agent: Arc::new(Mutex::new(None)),
+            commit_queue: Arc::new(Mutex::new(None)),
         };
         crate::populate::populate_base_models(&store)?;
         Ok(store)
     }
+
+    /// Commits that can't be sent to their server by `Resource::save` are added to this queue.
+    /// Send them later using `CommitQueue::push`.
+    pub fn set_commit_queue(&self, queue: crate::queue::CommitQueue) {
+        self.commit_queue.lock().unwrap().replace(queue);
+    }
 }
 
 impl Storelike for Store {
@@ -91,6 +99,14 @@ impl Storelike for Store {
         }
     }
 
+    fn get_commit_queue(&self) -> Option<crate::queue::CommitQueue> {
+        self.commit_queue.lock().unwrap().clone()
+    }
+
+    fn has_resource(&self, subject: &str) -> bool {
+        self.hashmap.lock().unwrap().contains_key(subject)
+    }
+
     fn get_resource(&self, subject: &str) -> AtomicResult<Resource> {
         if let Some(resource) = self.hashmap.lock().unwrap().get(subject) {
             return Ok(resource.clone());
@@ -211,6 +227,60 @@ mod test {
         }
     }
 
+    #[test]
+    fn path_multiple() {
+        let store = init_store();
+        let path = "https://atomicdata.dev/classes/Class requires * shortname";
+        // A single result is expected by get_path
+        assert!(store.get_path(path, None).is_err());
+        let shortnames: Vec<String> = store
+            .get_path_all(path, None)
+            .unwrap()
+            .into_iter()
+            .map(|result| match result {
+                crate::storelike::PathReturn::Atom(atom) => atom.value.to_string(),
+                crate::storelike::PathReturn::Subject(_) => panic!("Should be an Atom"),
+            })
+            .collect();
+        assert_eq!(shortnames, vec!["shortname", "description"]);
+
+        // Incoming links, and following a link to a single Resource
+        for child in ["https://localhost/child1", "https://localhost/child2"].iter() {
+            let mut resource = Resource::new(child.to_string());
+            resource
+                .set_propval_unsafe(urls::PARENT.into(), crate::Value::AtomicUrl(urls::CLASS.into()))
+                .unwrap();
+            store.add_resource_unsafe(&resource).unwrap();
+        }
+        let mut children: Vec<String> = store
+            .get_path_all(&format!("{} ~parent", urls::CLASS), None)
+            .unwrap()
+            .into_iter()
+            .map(|result| match result {
+                crate::storelike::PathReturn::Subject(subject) => subject,
+                crate::storelike::PathReturn::Atom(_) => panic!("Should be a Subject"),
+            })
+            .collect();
+        children.sort();
+        assert_eq!(children, vec!["https://localhost/child1", "https://localhost/child2"]);
+        // Children without the Property are skipped, other errors are not
+        let mut described = Resource::new("https://localhost/child1".into());
+        described
+            .set_propval_unsafe(urls::PARENT.into(), crate::Value::AtomicUrl(urls::CLASS.into()))
+            .unwrap();
+        described
+            .set_propval_unsafe(urls::DESCRIPTION.into(), crate::Value::Markdown("first".into()))
+            .unwrap();
+        store.add_resource_unsafe(&described).unwrap();
+        let path = format!("{} ~parent description", urls::CLASS);
+        assert_eq!(store.get_path_all(&path, None).unwrap().len(), 1);
+        assert!(store.get_path_all(&format!("{} ~parent 0", urls::CLASS), None).is_err());
+        match store.get_path("https://localhost/child1 parent shortname", None).unwrap() {
+            crate::storelike::PathReturn::Atom(atom) => assert_eq!(atom.value.to_string(), "class"),
+            crate::storelike::PathReturn::Subject(_) => panic!("Should be an Atom"),
+        }
+    }
+
     #[test]
     fn get_external_resource() {
         let store = Store::init().unwrap();
@@ -226,11 +296,11 @@ mod test {
         let subject = "https://atomicdata.dev/collections/class?current_page=2";
         // Should throw, because page 2 is out of bounds for default page size
         let _wrong_resource = store
-            .get_resource_extended(subject)
+            .get_resource_extended(subject, None)
             .unwrap_err();
         let subject = "https://atomicdata.dev/collections/class?current_page=2&page_size=1";
         let resource = store
-            .get_resource_extended(subject)
+            .get_resource_extended(subject, None)
             .unwrap();
         let cur_page = resource.get(urls::COLLECTION_CURRENT_PAGE).unwrap().to_int().unwrap();
         assert_eq!(cur_page, 2);
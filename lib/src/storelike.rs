@@ -40,6 +40,15 @@ pub trait Storelike: Sized {
     /// WARNING: This could be very expensive!
     fn all_resources(&self, include_external: bool) -> ResourceCollection;
 
+    /// Iterates over all Resources, like `all_resources`.
+    /// Stores that can read their Resources one by one override this, so they are not all loaded in memory at once.
+    fn iter_resources<'a>(
+        &'a self,
+        include_external: bool,
+    ) -> Box<dyn Iterator<Item = Resource> + 'a> {
+        Box::new(self.all_resources(include_external).into_iter())
+    }
+
     /// Returns the root URL where the default store is.
     /// E.g. `https://example.com`
     /// This is where deltas should be sent to.
@@ -76,9 +85,13 @@ pub trait Storelike: Sized {
 
     /// Fetches a resource, makes sure its subject matches.
     /// Save to the store.
+    /// Included Resources (e.g. the members of a Collection) are only saved if they are hosted on the same server, and are valid.
     fn fetch_resource(&self, subject: &str) -> AtomicResult<Resource> {
         let resource: Resource = crate::client::fetch_resource(subject, self)?;
         self.add_resource_unsafe(&resource)?;
+        for included in crate::client::included_resources(&resource, self) {
+            self.add_resource_unsafe(&included)?;
+        }
         Ok(resource)
     }
 
@@ -87,12 +100,21 @@ pub trait Storelike: Sized {
     /// If you're not sure what to use, use `get_resource_extended`.
     fn get_resource(&self, subject: &str) -> AtomicResult<Resource>;
 
-    /// Retrieves a Class from the store by subject URL and converts it into a Class useful for forms
+    /// Returns true if the Resource is in the store. Unlike `get_resource`, it never fetches it.
+    /// Returns false by default.
+    fn has_resource(&self, _subject: &str) -> bool {
+        false
+    }
+
+    /// Retrieves a Class from the store by subject URL and converts it into a Class useful for forms.
+    /// Includes the required and recommended Properties that it inherits from the Classes it is a subclass of.
     fn get_class(&self, subject: &str) -> AtomicResult<Class> {
         let resource = self
             .get_resource(subject)
             .map_err(|e| format!("Failed getting class {}. {}", subject, e))?;
-        Class::from_resource(resource)
+        let mut class = Class::from_resource(resource)?;
+        class.inherit(self)?;
+        Ok(class)
     }
 
     /// Finds all classes (isA) for any subject.
@@ -110,9 +132,21 @@ pub trait Storelike: Sized {
         Property::from_resource(prop)
     }
 
+    /// Is called after a Commit has been applied to the store.
+    /// Does nothing by default. The Db calls its `on_commit` handler, which the server uses for WebSocket subscriptions.
+    fn handle_commit(&self, _commit_response: &crate::commit::CommitResponse) {}
+
+    /// Returns the queue for Commits that can't be sent to their server yet, if the store has one.
+    /// Used by `Resource::save`.
+    fn get_commit_queue(&self) -> Option<crate::queue::CommitQueue> {
+        None
+    }
+
     /// Get's the resource, parses the Query parameters and calculates dynamic properties.
     /// Defaults to get_resource if store doesn't support extended resources
-    fn get_resource_extended(&self, subject: &str) -> AtomicResult<Resource> {
+    /// `for_agent` is the subject of the authenticated Agent that requests the resource.
+    /// Some dynamic resources (such as the `/audit` endpoint) are only available to Agents with the required rights.
+    fn get_resource_extended(&self, subject: &str, _for_agent: Option<&str>) -> AtomicResult<Resource> {
         self.get_resource(subject)
     }
 
@@ -164,7 +198,7 @@ pub trait Storelike: Sized {
     ///     Some("[\"https://atomicdata.dev/classes/Class\"]"),
     ///     true
     /// ).unwrap();
-    /// assert_eq!(atoms.len(), 11)
+    /// assert_eq!(atoms.len(), 12)
     /// ```
     // Very costly, slow implementation.
     // Does not assume any indexing.
@@ -251,11 +285,85 @@ pub trait Storelike: Sized {
         }
     }
 
+    /// Returns the subjects of all Resources that have the Property, and (if passed) the Value.
+    /// For ResourceArrays, the Value only has to be one of the items.
+    /// Uses `tpf` by default. Stores with a property / value index (such as Db) override this.
+    fn subjects_with_propval(
+        &self,
+        property: &str,
+        value: Option<&str>,
+        include_external: bool,
+    ) -> AtomicResult<Vec<String>> {
+        Ok(self
+            .tpf(None, Some(property), value, include_external)?
+            .into_iter()
+            .map(|atom| atom.subject)
+            .collect())
+    }
+
+    /// Returns the Atoms of the Property of which the Value meets the Comparison, such as greater than or starts with.
+    /// Use `Comparison::new` to validate the query values against the DataType of the Property.
+    /// Uses `tpf` by default. Stores with ordered indexes (such as Db) override this.
+    fn tpf_compare(
+        &self,
+        property: &str,
+        comparison: &crate::comparison::Comparison,
+        include_external: bool,
+    ) -> AtomicResult<Vec<Atom>> {
+        Ok(self
+            .tpf(None, Some(property), None, include_external)?
+            .into_iter()
+            .filter(|atom| comparison.matches(&atom.value))
+            .collect())
+    }
+
+    /// How strictly the `classtype` of Properties is checked when applying Commits.
+    /// Lenient by default, stores can make this configurable (such as `Db::set_classtype_mode`).
+    fn get_classtype_mode(&self) -> crate::validate::ClasstypeMode {
+        crate::validate::ClasstypeMode::Lenient
+    }
+
+    /// Returns the stored members of a materialized Collection, by the subject of the Collection (without query parameters).
+    /// Returns None by default, in which case Collections run their query on every request.
+    fn get_materialized(
+        &self,
+        _collection: &str,
+    ) -> AtomicResult<Option<crate::collections::Materialized>> {
+        Ok(None)
+    }
+
+    /// Stores the members of a materialized Collection. Stores that persist them (such as Db) must keep them up to date when Resources change.
+    /// Does nothing by default.
+    fn set_materialized(
+        &self,
+        _collection: &str,
+        _materialized: &crate::collections::Materialized,
+    ) -> AtomicResult<()> {
+        Ok(())
+    }
+
     /// Accepts an Atomic Path string, returns the result value (resource or property value)
     /// E.g. `https://example.com description` or `thing isa 0`
     /// https://docs.atomicdata.dev/core/paths.html
+    /// Fails if the path returns multiple results, use `get_path_all` for those.
     //  Todo: return something more useful, give more context.
     fn get_path(&self, atomic_path: &str, mapping: Option<&Mapping>) -> AtomicResult<PathReturn> {
+        let mut results = self.get_path_all(atomic_path, mapping)?;
+        match results.len() {
+            1 => Ok(results.remove(0)),
+            0 => Err(format!("Path {} has no results", atomic_path).into()),
+            n => Err(format!("Path {} has {} results, but only one was expected", atomic_path, n).into()),
+        }
+    }
+
+    /// Accepts an Atomic Path string, returns all the results (resources or property values).
+    /// Besides Property shortnames or URLs and array indexes, a path can contain:
+    /// - `~{property}` to follow incoming links, e.g. `~parent` for the children of a Resource
+    /// - `*` to fan out over all items of an array
+    ///
+    /// Values that link to a single Resource are followed, e.g. `thing parent name`.
+    /// After a path has fanned out, Resources that don't have the next Property are skipped instead of returning an error.
+    fn get_path_all(&self, atomic_path: &str, mapping: Option<&Mapping>) -> AtomicResult<Vec<PathReturn>> {
         // The first item of the path represents the starting Resource, the following ones are traversing the graph / selecting properties.
         let path_items: Vec<&str> = atomic_path.split(' ').collect();
         let first_item = String::from(path_items[0]);
@@ -267,67 +375,21 @@ pub trait Storelike: Sized {
                 .try_mapping_or_url(&id_url)
                 .ok_or(&*format!("No url found for {}", path_items[0]))?;
         }
-        if path_items.len() == 1 {
-            return Ok(PathReturn::Subject(id_url));
-        }
-        // The URL of the next resource
-        let mut subject = id_url;
-        // Set the currently selectred resource parent, which starts as the root of the search
-        let mut resource = self.get_resource_extended(&subject)?;
-        // During each of the iterations of the loop, the scope changes.
-        // Try using pathreturn...
-        let mut current: PathReturn = PathReturn::Subject(subject.clone());
+        let mut current: Vec<PathReturn> = vec![PathReturn::Subject(id_url)];
+        let mut fanned_out = false;
         // Loops over every item in the list, traverses the graph
         // Skip the first one, for that is the subject (i.e. first parent) and not a property
         for item in path_items[1..].iter().cloned() {
-            // In every iteration, the subject, property_url and current should be set.
             // Ignore double spaces
             if item.is_empty() {
                 continue;
             }
-            // If the item is a number, assume its indexing some array
-            if let Ok(i) = item.parse::<u32>() {
-                match current {
-                    PathReturn::Atom(atom) => {
-                        let vector = match resource.get(&atom.property)? {
-                            Value::ResourceArray(vec) => vec,
-                            _ => return Err("Should be Vector!".into()),
-                        };
-                        let url: String = vector
-                            .get(i as usize)
-                            .ok_or(format!(
-                                "Too high index {} for array with length {}, max is {}",
-                                i,
-                                vector.len(),
-                                vector.len() - 1
-                            ))?
-                            .into();
-                        subject = url;
-                        resource = self.get_resource_extended(&subject)?;
-                        current = PathReturn::Subject(subject.clone());
-                        continue;
-                    }
-                    PathReturn::Subject(_) => {
-                        return Err("You can't do an index on a resource, only on arrays.".into())
-                    }
-                }
-            }
-            // Since the selector isn't an array index, we can assume it's a property URL
-            match current {
-                PathReturn::Subject(_) => {}
-                PathReturn::Atom(_) => {
-                    return Err("No more linked resources down this path.".into())
-                }
+            let mut next = Vec::new();
+            for result in current {
+                next.extend(path_step(self, result, item, fanned_out)?);
             }
-            // Set the parent for the next loop equal to the next node.
-            // TODO: skip this step if the current iteration is the last one
-            let value = resource.get_shortname(&item, self)?.clone();
-            let property = resource.resolve_shortname_to_property(item, self)?;
-            current = PathReturn::Atom(Box::new(Atom::new(
-                subject.clone(),
-                property.subject,
-                value,
-            )))
+            fanned_out = fanned_out || item == "*" || item.starts_with('~');
+            current = next;
         }
         Ok(current)
     }
@@ -346,3 +408,111 @@ pub trait Storelike: Sized {
         crate::validate::validate_store(self, false)
     }
 }
+
+/// Applies one item of an Atomic Path to one of the results of the previous items.
+/// With `skip_missing`, Resources that don't have the Property return no results, instead of an error.
+fn path_step(
+    store: &impl Storelike,
+    current: PathReturn,
+    item: &str,
+    skip_missing: bool,
+) -> AtomicResult<Vec<PathReturn>> {
+    // If the item is a number, assume its indexing some array
+    if let Ok(i) = item.parse::<u32>() {
+        return match current {
+            PathReturn::Atom(atom) => {
+                let vector = match atom.value {
+                    Value::ResourceArray(vec) => vec,
+                    _ => return Err("Should be Vector!".into()),
+                };
+                let url: String = vector
+                    .get(i as usize)
+                    .ok_or(format!(
+                        "Too high index {} for array with length {}, max is {}",
+                        i,
+                        vector.len(),
+                        vector.len() as i64 - 1
+                    ))?
+                    .into();
+                Ok(vec![PathReturn::Subject(url)])
+            }
+            PathReturn::Subject(_) => {
+                Err("You can't do an index on a resource, only on arrays.".into())
+            }
+        };
+    }
+    if item == "*" {
+        return match current {
+            PathReturn::Atom(atom) => match atom.value {
+                Value::ResourceArray(vec) => Ok(vec.into_iter().map(PathReturn::Subject).collect()),
+                _ => Err("The * wildcard can only be used on arrays.".into()),
+            },
+            PathReturn::Subject(_) => {
+                Err("The * wildcard can only be used on arrays, not on resources.".into())
+            }
+        };
+    }
+    // Since the selector isn't an array index, we can assume it's a property URL
+    let subject = match current {
+        PathReturn::Subject(subject) => subject,
+        PathReturn::Atom(atom) => match atom.value {
+            Value::AtomicUrl(url) => url,
+            _ => return Err("No more linked resources down this path.".into()),
+        },
+    };
+    if let Some(reverse) = item.strip_prefix('~') {
+        let property = property_by_shortname(store, reverse)?;
+        return Ok(store
+            .subjects_with_propval(&property, Some(&subject), true)?
+            .into_iter()
+            .map(PathReturn::Subject)
+            .collect());
+    }
+    let resource = store.get_resource_extended(&subject, None)?;
+    if skip_missing && !has_property(&resource, item, store) {
+        return Ok(Vec::new());
+    }
+    let value = resource.get_shortname(item, store)?.clone();
+    let property = resource.resolve_shortname_to_property(item, store)?;
+    Ok(vec![PathReturn::Atom(Box::new(Atom::new(
+        subject,
+        property.subject,
+        value,
+    )))])
+}
+
+/// Checks if the Resource has a Value for the Property, which can be a URL or a shortname
+fn has_property(resource: &Resource, property: &str, store: &impl Storelike) -> bool {
+    resource.get_propvals().keys().any(|url| {
+        url == property
+            || store
+                .get_property(url)
+                .map(|p| p.shortname == property)
+                .unwrap_or(false)
+    })
+}
+
+/// Finds the Property with this shortname in the store, or returns the URL if it already is one.
+/// Used for incoming links in paths, for which there is no Resource to resolve the shortname with.
+fn property_by_shortname(store: &impl Storelike, shortname: &str) -> AtomicResult<String> {
+    if crate::mapping::is_url(shortname) {
+        return Ok(shortname.into());
+    }
+    let mut properties: Vec<String> = store
+        .subjects_with_propval(crate::urls::SHORTNAME, Some(shortname), true)?
+        .into_iter()
+        .filter(|subject| store.get_property(subject).is_ok())
+        .collect();
+    properties.sort();
+    properties.dedup();
+    match properties.len() {
+        1 => Ok(properties.remove(0)),
+        0 => Err(format!("No Property found with shortname {}", shortname).into()),
+        _ => Err(format!(
+            "Multiple Properties have the shortname {}: {}. Use a URL instead.",
+            shortname,
+            properties.join(", ")
+        )
+        .into()),
+    }
+}
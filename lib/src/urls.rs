@@ -12,6 +12,7 @@ pub const DRIVE: &str = "https://atomicdata.dev/classes/Drive";
 pub const INVITE: &str = "https://atomicdata.dev/classes/Invite";
 pub const REDIRECT: &str = "https://atomicdata.dev/classes/Redirect";
 pub const ATOM: &str = "https://atomicdata.dev/classes/Atom";
+pub const FILTER: &str = "https://atomicdata.dev/classes/Filter";
 
 // Properties
 pub const SHORTNAME: &str = "https://atomicdata.dev/properties/shortname";
@@ -20,9 +21,16 @@ pub const DESCRIPTION: &str = "https://atomicdata.dev/properties/description";
 pub const IS_A: &str = "https://atomicdata.dev/properties/isA";
 pub const DATATYPE_PROP: &str = "https://atomicdata.dev/properties/datatype";
 pub const CLASSTYPE_PROP: &str = "https://atomicdata.dev/properties/classtype";
+pub const MIN_VALUE: &str = "https://atomicdata.dev/properties/minValue";
+pub const MAX_VALUE: &str = "https://atomicdata.dev/properties/maxValue";
+pub const MIN_LENGTH: &str = "https://atomicdata.dev/properties/minLength";
+pub const MAX_LENGTH: &str = "https://atomicdata.dev/properties/maxLength";
+pub const PATTERN: &str = "https://atomicdata.dev/properties/pattern";
+pub const ALLOWED_VALUES: &str = "https://atomicdata.dev/properties/allowedValues";
 // ... for Classes
 pub const REQUIRES: &str = "https://atomicdata.dev/properties/requires";
 pub const RECOMMENDS: &str = "https://atomicdata.dev/properties/recommends";
+pub const SUBCLASS_OF: &str = "https://atomicdata.dev/properties/subClassOf";
 // ... for Commits
 pub const SUBJECT: &str = "https://atomicdata.dev/properties/subject";
 pub const SET: &str = "https://atomicdata.dev/properties/set";
@@ -31,6 +39,9 @@ pub const DESTROY: &str = "https://atomicdata.dev/properties/destroy";
 pub const SIGNER: &str = "https://atomicdata.dev/properties/signer";
 pub const CREATED_AT: &str = "https://atomicdata.dev/properties/createdAt";
 pub const SIGNATURE: &str = "https://atomicdata.dev/properties/signature";
+pub const PREVIOUS_COMMIT: &str = "https://atomicdata.dev/properties/previousCommit";
+pub const LAST_COMMIT: &str = "https://atomicdata.dev/properties/lastCommit";
+pub const CONFLICTS: &str = "https://atomicdata.dev/properties/conflicts";
 // ... for Agents
 pub const PUBLIC_KEY: &str = "https://atomicdata.dev/properties/publicKey";
 pub const NAME: &str = "https://atomicdata.dev/properties/name";
@@ -44,6 +55,17 @@ pub const COLLECTION_MEMBERS: &str = "https://atomicdata.dev/properties/collecti
 pub const COLLECTION_PAGE_SIZE: &str = "https://atomicdata.dev/properties/collection/pageSize";
 pub const COLLECTION_SORT_BY: &str = "https://atomicdata.dev/properties/collection/sortBy";
 pub const COLLECTION_SORT_DESC: &str = "https://atomicdata.dev/properties/collection/sortDesc";
+pub const COLLECTION_NULLS_FIRST: &str = "https://atomicdata.dev/properties/collection/nullsFirst";
+pub const COLLECTION_INCLUDE: &str = "https://atomicdata.dev/properties/collection/include";
+pub const COLLECTION_INCLUDE_NESTED: &str = "https://atomicdata.dev/properties/collection/includeNested";
+pub const COLLECTION_INCLUDED: &str = "https://atomicdata.dev/properties/collection/included";
+pub const COLLECTION_NEXT: &str = "https://atomicdata.dev/properties/collection/next";
+pub const COLLECTION_PREVIOUS: &str = "https://atomicdata.dev/properties/collection/previous";
+pub const COLLECTION_FILTERS: &str = "https://atomicdata.dev/properties/collection/filters";
+pub const COLLECTION_OPERATOR: &str = "https://atomicdata.dev/properties/collection/operator";
+pub const COLLECTION_VALUE_END: &str = "https://atomicdata.dev/properties/collection/valueEnd";
+pub const COLLECTION_MATCH_ANY: &str = "https://atomicdata.dev/properties/collection/matchAny";
+pub const COLLECTION_MATERIALIZED: &str = "https://atomicdata.dev/properties/collection/materialized";
 // ... for Endpoints
 pub const ENDPOINT_PARAMETERS: &str = "https://atomicdata.dev/properties/endpoint/parameters";
 pub const PATH: &str = "https://atomicdata.dev/properties/path";
@@ -61,11 +83,25 @@ pub const WRITE_BOOL: &str = "https://atomicdata.dev/properties/invite/write";
 pub const INVITE_PUBKEY: &str = "https://atomicdata.dev/properties/invite/publicKey";
 pub const INVITE_AGENT: &str = "https://atomicdata.dev/properties/invite/agent";
 pub const REDIRECT_AGENT: &str = "https://atomicdata.dev/properties/invite/redirectAgent";
+// ... for the Audit log
+pub const AUDIT_DRIVE: &str = "https://atomicdata.dev/properties/audit/drive";
+pub const AUDIT_FROM: &str = "https://atomicdata.dev/properties/audit/from";
+pub const AUDIT_UNTIL: &str = "https://atomicdata.dev/properties/audit/until";
+pub const AGGREGATE_FUNCTION: &str = "https://atomicdata.dev/properties/aggregate/function";
+pub const AGGREGATE_OF: &str = "https://atomicdata.dev/properties/aggregate/of";
+pub const AGGREGATE_GROUP_BY: &str = "https://atomicdata.dev/properties/aggregate/groupBy";
+pub const AGGREGATE_GROUPS: &str = "https://atomicdata.dev/properties/aggregate/groups";
+pub const AGGREGATE_GROUP_VALUE: &str = "https://atomicdata.dev/properties/aggregate/groupValue";
+pub const AGGREGATE_RESULT: &str = "https://atomicdata.dev/properties/aggregate/result";
 // ... for Atoms
 pub const ATOM_SUBJECT: &str = "https://atomicdata.dev/properties/atom/subject";
 pub const ATOM_PROPERTY: &str = "https://atomicdata.dev/properties/atom/property";
 pub const ATOM_VALUE: &str = "https://atomicdata.dev/properties/atom/value";
 
+// Agents
+/// Add this Agent to the `read` rights of a Resource to make it public
+pub const PUBLIC_AGENT: &str = "https://atomicdata.dev/agents/publicAgent";
+
 // Datatypes
 pub const STRING: &str = "https://atomicdata.dev/datatypes/string";
 pub const MARKDOWN: &str = "https://atomicdata.dev/datatypes/markdown";
@@ -1,150 +1,376 @@
 //! Validate the Store and create a ValidationReport.
-//! Might be deprecated soon, as Validation hasn't been necessary since parsing has built-in data validation.
+//! Parsing already validates Values, but the Store can still contain Resources that miss required Properties,
+//! refer to Resources of the wrong `classtype`, or refer to local Resources that were removed.
 
-/// Checks Atomic Data in the store for validity.
-/// Returns an Error if it is not valid.
-///
-/// Validates:
-///
-/// - [X] If the Values can be parsed using their Datatype (e.g. if Integers are integers)
-/// - [X] If all required fields of the class are present
-/// - [X] If the URLs are publicly accessible
-/// - [ ] ..and return the right type of data?
-/// - [X] Returns a report, instead of throwing an error
-#[allow(dead_code, unreachable_code)]
-pub fn validate_store(
-    store: &impl crate::Storelike,
-    fetch_items: bool,
-) -> crate::validate::ValidationReport {
-    type Error = String;
-    let mut resource_count: u8 = 0;
-    let mut atom_count: u8 = 0;
-    let mut unfetchable: Vec<(String, Error)> = Vec::new();
-    let mut invalid_value: Vec<(crate::Atom, Error)> = Vec::new();
-    let mut unfetchable_props: Vec<(String, Error)> = Vec::new();
-    let mut unfetchable_classes: Vec<(String, Error)> = Vec::new();
-    // subject, property, class
-    let mut missing_props: Vec<(String, String, String)> = Vec::new();
-    for resource in store.all_resources(true) {
-        let subject = resource.get_subject();
-        let propvals = resource.get_propvals();
-        println!("Subject: {:?}", subject);
-        println!("Resource: {:?}", propvals);
-        resource_count += 1;
-
-        if fetch_items {
-            match crate::client::fetch_resource(&subject, store) {
-                Ok(_) => {}
-                Err(e) => unfetchable.push((subject.clone(), e.to_string())),
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::AtomicResult,
+    schema::{get_class_and_subclasses, Class, Property},
+    urls, Resource, Storelike, Value,
+};
+
+/// How strictly the `classtype` of Properties is checked, e.g. if the `signer` of a Commit is an Agent.
+/// Set it for a Db using `Db::set_classtype_mode`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClasstypeMode {
+    /// Referenced Resources must be in the store, and must be instances of the classtype.
+    /// Use `missing_references` to find the ones that have to be fetched before applying a Commit.
+    Strict,
+    /// Referenced Resources that cannot be found are accepted, but the ones that are found must be instances of the classtype.
+    Lenient,
+    /// Classtypes are not checked.
+    Off,
+}
+
+// Deriving Default for enums requires Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for ClasstypeMode {
+    fn default() -> Self {
+        ClasstypeMode::Lenient
+    }
+}
+
+impl ClasstypeMode {
+    /// Parses `strict`, `lenient` or `off`
+    pub fn from_string(mode: &str) -> AtomicResult<ClasstypeMode> {
+        match mode {
+            "strict" => Ok(ClasstypeMode::Strict),
+            "lenient" => Ok(ClasstypeMode::Lenient),
+            "off" => Ok(ClasstypeMode::Off),
+            other => Err(format!(
+                "Unknown classtype mode {}. Use strict, lenient or off.",
+                other
+            )
+            .into()),
+        }
+    }
+}
+
+/// Checks if the Resources that an AtomicUrl or ResourceArray Value refers to are instances of the `classtype` of the Property.
+/// Instances of subclasses of the classtype are accepted too.
+/// Does nothing if the Property has no classtype.
+/// Only looks in the store, it never fetches Resources.
+pub fn check_classtype(
+    store: &impl Storelike,
+    property: &Property,
+    value: &Value,
+    mode: ClasstypeMode,
+) -> AtomicResult<()> {
+    let classtype = match &property.class_type {
+        Some(classtype) if mode != ClasstypeMode::Off => classtype,
+        _ => return Ok(()),
+    };
+    let subjects = match value {
+        Value::AtomicUrl(subject) => vec![subject.clone()],
+        Value::ResourceArray(items) => items.clone(),
+        _ => return Ok(()),
+    };
+    // Only looked up when a Resource is not a direct instance of the classtype
+    let mut classes: Option<Vec<String>> = None;
+    for subject in subjects {
+        if !store.has_resource(&subject) {
+            if mode == ClasstypeMode::Strict {
+                return Err(format!(
+                    "The value '{}' of property '{}' should be an instance of {}, but it is not in the store.",
+                    subject, property.shortname, classtype
+                )
+                .into());
             }
+            continue;
+        }
+        let resource = store.get_resource(&subject)?;
+        let is_a = match resource.get(urls::IS_A) {
+            Ok(is_a) => is_a.to_vec()?.clone(),
+            Err(_) => Vec::new(),
+        };
+        if is_a.contains(classtype) {
+            continue;
+        }
+        if classes.is_none() {
+            classes = Some(get_class_and_subclasses(store, classtype)?);
+        }
+        let classes = classes.as_ref().unwrap();
+        if !is_a.iter().any(|class| classes.contains(class)) {
+            return Err(format!(
+                "The value '{}' of property '{}' should be an instance of {}, but it is not.",
+                subject, property.shortname, classtype
+            )
+            .into());
         }
+    }
+    Ok(())
+}
 
-        let mut found_props: Vec<String> = Vec::new();
+/// Returns the Resources that a Commit refers to, which are not in the store.
+/// In Strict mode, these have to be fetched before the Commit is applied, since `check_classtype` does not fetch.
+pub fn missing_references(store: &impl Storelike, commit: &crate::Commit) -> Vec<String> {
+    let mut missing = commit.referenced_subjects();
+    missing.sort();
+    missing.dedup();
+    missing.retain(|subject| !store.has_resource(subject));
+    missing
+}
 
-        for (prop_url, value) in propvals.to_owned() {
-            atom_count += 1;
+/// The kind of problem that a ValidationIssue describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueKind {
+    /// The Value can't be parsed using the DataType of its Property
+    InvalidValue,
+    /// A required Property of one of the Classes of the Resource is missing
+    MissingProperty,
+    /// The Value refers to a Resource that is not an instance of the `classtype` of the Property
+    InvalidClasstype,
+    /// The Value refers to a local Resource that does not exist
+    DanglingReference,
+    /// The Property can't be found
+    UnknownProperty,
+    /// One of the Classes (`is-a`) of the Resource can't be found
+    UnknownClass,
+    /// The Resource can't be fetched from its subject URL. Only checked when `fetch_items` is enabled.
+    Unfetchable,
+}
+
+impl std::fmt::Display for IssueKind {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let string = match self {
+            IssueKind::InvalidValue => "Invalid value",
+            IssueKind::MissingProperty => "Missing property",
+            IssueKind::InvalidClasstype => "Wrong classtype",
+            IssueKind::DanglingReference => "Dangling reference",
+            IssueKind::UnknownProperty => "Unknown property",
+            IssueKind::UnknownClass => "Unknown class",
+            IssueKind::Unfetchable => "Cannot fetch",
+        };
+        fmt.write_str(string)
+    }
+}
 
-            let property = match store.get_property(&prop_url) {
-                Ok(prop) => prop,
+/// A single problem in a Resource
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub kind: IssueKind,
+    pub subject: String,
+    /// The Property of the Atom with the problem, if the problem is caused by a single Atom
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub property: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.property {
+            Some(property) => write!(
+                fmt,
+                "{} in {} ({}): {}",
+                self.kind, self.subject, property, self.message
+            ),
+            None => write!(fmt, "{} in {}: {}", self.kind, self.subject, self.message),
+        }
+    }
+}
+
+/// Checks Resources one by one.
+/// Properties and Classes are only looked up once, so it can be used for big stores.
+pub struct Validator<'a, S: Storelike> {
+    store: &'a S,
+    fetch_items: bool,
+    classtype_mode: ClasstypeMode,
+    /// Subjects of Resources that start with this URL should exist in the store
+    self_url: Option<String>,
+    properties: HashMap<String, Result<Property, String>>,
+    classes: HashMap<String, Result<Class, String>>,
+}
+
+impl<'a, S: Storelike> Validator<'a, S> {
+    /// Uses the `ClasstypeMode` of the store.
+    /// With `fetch_items`, it also checks if every Resource can be fetched from its subject URL.
+    pub fn new(store: &'a S, fetch_items: bool) -> Self {
+        Validator {
+            store,
+            fetch_items,
+            classtype_mode: store.get_classtype_mode(),
+            self_url: store.get_self_url(),
+            properties: HashMap::new(),
+            classes: HashMap::new(),
+        }
+    }
+
+    /// Returns the problems of a single Resource
+    pub fn validate_resource(&mut self, resource: &Resource) -> Vec<ValidationIssue> {
+        let subject = resource.get_subject();
+        let mut issues = Vec::new();
+        let mut issue = |kind: IssueKind, property: Option<&String>, message: String| {
+            issues.push(ValidationIssue {
+                kind,
+                subject: subject.clone(),
+                property: property.cloned(),
+                message,
+            })
+        };
+
+        if self.fetch_items {
+            if let Err(e) = crate::client::fetch_resource(subject, self.store) {
+                issue(IssueKind::Unfetchable, None, e.to_string());
+            }
+        }
+
+        for (prop_url, value) in resource.get_propvals() {
+            let store = self.store;
+            let property = match self
+                .properties
+                .entry(prop_url.clone())
+                .or_insert_with(|| store.get_property(prop_url).map_err(|e| e.to_string()))
+            {
+                Ok(property) => property,
                 Err(e) => {
-                    unfetchable_props.push((prop_url, e.to_string()));
-                    break;
+                    issue(IssueKind::UnknownProperty, Some(prop_url), e.clone());
+                    continue;
                 }
             };
-
             // Maybe this is no longer needed, because no store uses strings anymore
-            match crate::Value::new(&value.to_string(), &property.data_type) {
-                Ok(_) => {}
-                Err(e) => invalid_value.push((
-                    crate::Atom::new(subject.clone(), prop_url.clone(), value),
-                    e.to_string(),
-                )),
-            };
-            found_props.push(prop_url.clone());
-        }
-        let classes = match store.get_classes_for_subject(&subject) {
-            Ok(classes) => classes,
-            Err(e) => {
-                unfetchable_classes.push((subject.clone(), e.to_string()));
-                break;
+            if let Err(e) = Value::new(&value.to_string(), &property.data_type) {
+                issue(IssueKind::InvalidValue, Some(prop_url), e.to_string());
             }
-        };
-        for class in classes {
-            println!("Class: {:?}", class.shortname);
-            println!("Found: {:?}", found_props);
-            for required_prop_subject in class.requires {
-                match store.get_property(&required_prop_subject) {
-                    Ok(required_prop) => {
-                        println!("Required: {:?}", required_prop.shortname);
-                        if !found_props.contains(&required_prop.subject) {
-                            missing_props.push((
-                                subject.clone(),
-                                required_prop.subject.clone(),
-                                class.subject.clone(),
-                            ));
-                        }
-                    }
-                    Err(e) => {
-                        unfetchable.push((required_prop_subject, e.to_string()))
+            if let Err(e) = check_classtype(self.store, property, value, self.classtype_mode) {
+                issue(IssueKind::InvalidClasstype, Some(prop_url), e.to_string());
+            }
+            if let Some(self_url) = &self.self_url {
+                let references = match value {
+                    Value::AtomicUrl(reference) => vec![reference.clone()],
+                    Value::ResourceArray(references) => references.clone(),
+                    _ => Vec::new(),
+                };
+                for reference in references {
+                    // Resources with query parameters are generated by Endpoints and Collections
+                    if reference.starts_with(self_url)
+                        && !reference.contains('?')
+                        && self.store.get_resource(&reference).is_err()
+                    {
+                        issue(
+                            IssueKind::DanglingReference,
+                            Some(prop_url),
+                            format!("{} does not exist", reference),
+                        );
                     }
                 }
             }
         }
-        println!("{:?} Valid", subject);
+
+        let class_subjects = match resource.get(urls::IS_A) {
+            Ok(is_a) => is_a.to_subjects().unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        for class_subject in class_subjects {
+            let store = self.store;
+            let class = match self
+                .classes
+                .entry(class_subject.clone())
+                .or_insert_with(|| store.get_class(&class_subject).map_err(|e| e.to_string()))
+            {
+                Ok(class) => class,
+                Err(e) => {
+                    issue(IssueKind::UnknownClass, Some(&urls::IS_A.to_string()), e.clone());
+                    continue;
+                }
+            };
+            for required in &class.requires {
+                if resource.get(required).is_err() {
+                    issue(
+                        IssueKind::MissingProperty,
+                        Some(required),
+                        format!("Required by Class {}", class.subject),
+                    );
+                }
+            }
+        }
+        issues
     }
-    crate::validate::ValidationReport {
-        unfetchable,
-        unfetchable_classes,
-        unfetchable_props,
-        invalid_value,
-        resource_count,
-        atom_count,
+}
+
+/// Checks all Atomic Data in the store for validity, and returns a report with all the problems.
+/// With `fetch_items`, it also checks if every Resource can be fetched from its subject URL.
+///
+/// Validates:
+///
+/// - If the Values can be parsed using their Datatype (e.g. if Integers are integers)
+/// - If all required Properties of the Classes are present
+/// - If referenced Resources are instances of the `classtype` of the Property, using the `ClasstypeMode` of the store
+/// - If referenced local Resources exist
+/// - If the Properties and Classes can be found
+pub fn validate_store(store: &impl Storelike, fetch_items: bool) -> ValidationReport {
+    let mut issues = Vec::new();
+    let mut report = validate_store_streaming(store, fetch_items, |issue| issues.push(issue));
+    report.issues = issues;
+    report
+}
+
+/// Like `validate_store`, but passes every issue to `on_issue` as soon as it is found, instead of keeping them.
+/// The returned report only has the counts.
+pub fn validate_store_streaming(
+    store: &impl Storelike,
+    fetch_items: bool,
+    mut on_issue: impl FnMut(ValidationIssue),
+) -> ValidationReport {
+    let mut validator = Validator::new(store, fetch_items);
+    let mut report = ValidationReport::default();
+    for resource in store.iter_resources(true) {
+        report.resource_count += 1;
+        report.atom_count += resource.get_propvals().len();
+        for issue in validator.validate_resource(&resource) {
+            *report.issue_counts.entry(issue.kind).or_insert(0) += 1;
+            on_issue(issue);
+        }
     }
+    report
 }
 
+/// The result of `validate_store`. Can be serialized to JSON.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ValidationReport {
-    pub resource_count: u8,
-    pub atom_count: u8,
-    pub unfetchable: Vec<(String, String)>,
-    pub invalid_value: Vec<(crate::Atom, String)>,
-    pub unfetchable_props: Vec<(String, String)>,
-    pub unfetchable_classes: Vec<(String, String)>,
+    pub resource_count: usize,
+    pub atom_count: usize,
+    /// Number of issues of each kind
+    pub issue_counts: BTreeMap<IssueKind, usize>,
+    /// Empty if the issues were streamed
+    pub issues: Vec<ValidationIssue>,
 }
 
 impl ValidationReport {
     pub fn is_valid(&self) -> bool {
-        self.unfetchable.is_empty()
-            && self.unfetchable_classes.is_empty()
-            && self.unfetchable_props.is_empty()
-            && self.invalid_value.is_empty()
+        self.issue_counts.is_empty()
     }
 }
 
 impl std::fmt::Display for ValidationReport {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if self.is_valid() {
-            fmt.write_str("Valid!")?;
-            return Ok(());
-        }
-        for (subject, error) in &self.unfetchable {
-            fmt.write_str(&*format!("Cannot fetch Resource {}: {} \n", subject, error))?;
-        }
-        for (subject, error) in &self.unfetchable_classes {
-            fmt.write_str(&*format!("Cannot fetch Class {}: {} \n", subject, error))?;
-        }
-        for (subject, error) in &self.unfetchable_props {
-            fmt.write_str(&*format!("Cannot fetch Property {}: {} \n", subject, error))?;
+        for issue in &self.issues {
+            writeln!(fmt, "{}", issue)?;
         }
-        for (atom, error) in &self.invalid_value {
-            fmt.write_str(&*format!("Invalid value {:?}: {} \n", atom, error))?;
+        write!(
+            fmt,
+            "Checked {} resources and {} atoms. ",
+            self.resource_count, self.atom_count
+        )?;
+        if self.is_valid() {
+            return fmt.write_str("Valid!");
         }
-        Ok(())
+        let counts: Vec<String> = self
+            .issue_counts
+            .iter()
+            .map(|(kind, count)| format!("{}: {}", kind, count))
+            .collect();
+        write!(fmt, "Found issues. {}", counts.join(", "))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{Store, Storelike};
+    use super::*;
+    use crate::Store;
 
     #[test]
     fn validate_populated() {
@@ -155,4 +381,103 @@ mod test {
         // assert!(report.resource_count > 5);
         // assert!(report.is_valid());
     }
+
+    #[test]
+    fn validate_issues() {
+        let store = Store::init().unwrap();
+        store.populate().unwrap();
+        let mut resource = Resource::new("https://localhost/invalid".into());
+        resource
+            .set_propval_unsafe(urls::IS_A.into(), vec![urls::CLASS.to_string()].into())
+            .unwrap();
+        resource
+            .set_propval_unsafe(urls::SHORTNAME.into(), Value::String("Not a slug".into()))
+            .unwrap();
+        let mut validator = Validator::new(&store, false);
+        let issues = validator.validate_resource(&resource);
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert_eq!(issues[0].kind, IssueKind::InvalidValue);
+        assert_eq!(issues[0].property.as_deref(), Some(urls::SHORTNAME));
+        assert_eq!(issues[1].kind, IssueKind::MissingProperty);
+        assert_eq!(issues[1].property.as_deref(), Some(urls::DESCRIPTION));
+
+        // Every Resource is checked, and the counts match the issues
+        store.add_resource_unsafe(&resource).unwrap();
+        let report = validate_store(&store, false);
+        assert!(!report.is_valid());
+        assert!(report.resource_count > 10);
+        assert_eq!(
+            report.issue_counts.values().sum::<usize>(),
+            report.issues.len()
+        );
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"kind\":\"missing-property\""));
+        let mut streamed = 0;
+        let streamed_report = validate_store_streaming(&store, false, |_issue| streamed += 1);
+        assert_eq!(streamed, report.issues.len());
+        assert!(streamed_report.issues.is_empty());
+    }
+
+    #[test]
+    fn check_classtypes() {
+        let store = Store::init().unwrap();
+        store.populate().unwrap();
+        let signer = store.get_property(urls::SIGNER).unwrap();
+        let check = |subject: &str, mode: ClasstypeMode| {
+            check_classtype(&store, &signer, &Value::AtomicUrl(subject.into()), mode)
+        };
+        let agent = store.create_agent(Some("classtype")).unwrap();
+        check(&agent.subject, ClasstypeMode::Strict).unwrap();
+        // Instances of subclasses are accepted
+        let bot = Class {
+            requires: Vec::new(),
+            recommends: Vec::new(),
+            shortname: "bot".into(),
+            description: "An automated Agent".into(),
+            subject: "https://localhost/classes/Bot".into(),
+            subclass_of: vec![urls::AGENT.into()],
+        };
+        store.add_resource_unsafe(&bot.to_resource().unwrap()).unwrap();
+        let mut instance = Resource::new("https://localhost/bot".into());
+        instance
+            .set_propval_unsafe(urls::IS_A.into(), vec![bot.subject.clone()].into())
+            .unwrap();
+        store.add_resource_unsafe(&instance).unwrap();
+        check("https://localhost/bot", ClasstypeMode::Strict).unwrap();
+
+        // A Class is not an Agent
+        check(urls::CLASS, ClasstypeMode::Lenient).unwrap_err();
+        check(urls::CLASS, ClasstypeMode::Off).unwrap();
+        // Resources that can't be found are only accepted in lenient mode
+        check("https://localhost/missing", ClasstypeMode::Lenient).unwrap();
+        check("https://localhost/missing", ClasstypeMode::Strict).unwrap_err();
+
+        // Commits are checked when they are applied
+        let mut commitbuilder = crate::commit::CommitBuilder::new("https://localhost/wrong".into());
+        commitbuilder.set(urls::SIGNER.into(), Value::AtomicUrl(urls::CLASS.into()));
+        let commit = commitbuilder.sign(&agent, &store).unwrap();
+        assert!(commit.apply(&store).is_err());
+        // Also the fields of the Commit itself
+        let mut commitbuilder = crate::commit::CommitBuilder::new("https://localhost/wrong".into());
+        commitbuilder.set(urls::DESCRIPTION.into(), Value::Markdown("valid".into()));
+        let mut commit = commitbuilder.sign(&agent, &store).unwrap();
+        commit.previous_commit = Some(agent.subject.clone());
+        let err = commit.apply_opts(&store, true, false, false, false).unwrap_err();
+        assert!(err.to_string().contains("previous-commit"), "{}", err);
+        commit.previous_commit = Some("https://example.com/commits/missing".into());
+        assert_eq!(
+            missing_references(&store, &commit),
+            vec!["https://example.com/commits/missing".to_string()]
+        );
+
+        let mut wrong = Resource::new("https://localhost/wrong".into());
+        wrong
+            .set_propval_unsafe(urls::SIGNER.into(), Value::AtomicUrl(urls::CLASS.into()))
+            .unwrap();
+        store.add_resource_unsafe(&wrong).unwrap();
+        let report = validate_store(&store, false);
+        assert!(report.issues.iter().any(|issue| {
+            issue.kind == IssueKind::InvalidClasstype && issue.subject == "https://localhost/wrong"
+        }));
+    }
 }
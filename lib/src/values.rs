@@ -1,5 +1,7 @@
 //! A value is the part of an Atom that contains the actual information.
 
+use std::collections::BTreeMap;
+
 use crate::{datatype::DataType, datatype::match_datatype, errors::AtomicResult, resources::PropVals, url_helpers::check_valid_url};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -20,9 +22,22 @@ pub enum Value {
     NestedResource(PropVals),
     Boolean(bool),
     Unsupported(UnsupportedValue),
+    /// A ResourceArray that includes the Resources themselves, instead of only their subjects.
+    /// Used for embedding Resources in responses, such as the members of a Collection.
+    ResourceArrayNested(Vec<SubResource>),
+    /// A String or Markdown Value in several languages
+    LangString(LangString),
+}
+
+/// A Resource that is included in a `Value::ResourceArrayNested`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubResource {
+    pub subject: String,
+    pub propvals: PropVals,
 }
 
-/// When the Datatype of a Value is not handled by this library
+/// When the Datatype of a Value is not built into this library.
+/// Validated if the Datatype is registered, see `datatype::register_datatype`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UnsupportedValue {
     pub value: String,
@@ -30,6 +45,85 @@ pub struct UnsupportedValue {
     pub datatype: String,
 }
 
+/// Texts of a String or Markdown Value by language tag (e.g. `en` or `nl-BE`).
+/// Serialized as a JSON object in JSON-AD, and as language-tagged literals in RDF.
+/// Always has at least one text, also when it is deserialized.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "LangStringData")]
+pub struct LangString {
+    /// `DataType::String` or `DataType::Markdown`
+    datatype: DataType,
+    texts: BTreeMap<String, String>,
+}
+
+/// The serialized fields of a LangString, which are checked by `LangString::new` when deserializing
+#[derive(Deserialize)]
+struct LangStringData {
+    datatype: DataType,
+    texts: BTreeMap<String, String>,
+}
+
+impl std::convert::TryFrom<LangStringData> for LangString {
+    type Error = String;
+
+    fn try_from(data: LangStringData) -> Result<Self, Self::Error> {
+        LangString::new(data.datatype, data.texts).map_err(|e| e.to_string())
+    }
+}
+
+impl LangString {
+    /// Checks the DataType and the language tags
+    pub fn new(datatype: DataType, texts: BTreeMap<String, String>) -> AtomicResult<LangString> {
+        if datatype != DataType::String && datatype != DataType::Markdown {
+            return Err(format!("Only Strings and Markdown can have languages, not {}", datatype).into());
+        }
+        if texts.is_empty() {
+            return Err("A language-tagged string needs at least one text".into());
+        }
+        let re = Regex::new(LANGUAGE_TAG_REGEX).unwrap();
+        if let Some(tag) = texts.keys().find(|tag| !re.is_match(tag)) {
+            return Err(format!("Not a valid language tag: {}. Use tags such as en or nl-BE.", tag).into());
+        }
+        Ok(LangString { datatype, texts })
+    }
+
+    /// `DataType::String` or `DataType::Markdown`
+    pub fn datatype(&self) -> &DataType {
+        &self.datatype
+    }
+
+    /// The texts by language tag
+    pub fn texts(&self) -> &BTreeMap<String, String> {
+        &self.texts
+    }
+
+    pub fn into_texts(self) -> BTreeMap<String, String> {
+        self.texts
+    }
+
+    /// Returns the language and text for the first of the `languages` that is available.
+    /// `en` matches `en-US`, and the other way around, and `*` matches any language.
+    /// Falls back to the first text if none of the languages are available.
+    pub fn select(&self, languages: &[String]) -> (&String, &String) {
+        let primary = |tag: &str| tag.split('-').next().unwrap_or_default().to_lowercase();
+        for language in languages {
+            if let Some(found) = self.texts.iter().find(|(tag, _)| tag.eq_ignore_ascii_case(language)) {
+                return found;
+            }
+            if let Some(found) = self.texts.iter().find(|(tag, _)| primary(tag) == primary(language)) {
+                return found;
+            }
+            if language == "*" {
+                break;
+            }
+        }
+        // `new` makes sure there is at least one text
+        self.texts.iter().next().expect("LangString without texts")
+    }
+}
+
+/// Language tags such as `en`, `nl-BE` or `zh-Hant-TW`. A simplified version of BCP 47.
+pub const LANGUAGE_TAG_REGEX: &str = r"^[a-zA-Z]{1,8}(-[a-zA-Z0-9]{1,8})*$";
 /// Only alphanumeric characters, no spaces
 pub const SLUG_REGEX: &str = r"^[a-z0-9]+(?:-[a-z0-9]+)*$";
 /// YYYY-MM-DD
@@ -52,6 +146,36 @@ impl Value {
             Value::NestedResource(_) => DataType::AtomicUrl,
             Value::Boolean(_) => DataType::Boolean,
             Value::Unsupported(s) => DataType::Unsupported(s.datatype.clone()),
+            Value::ResourceArrayNested(_) => DataType::ResourceArray,
+            Value::LangString(l) => l.datatype().clone(),
+        }
+    }
+
+    /// Compares two Values according to their DataType.
+    /// Numbers (Integers, Floats and Timestamps) are compared numerically, Booleans as `false < true`, and Dates chronologically.
+    /// Values of other or different DataTypes are compared by their string representation.
+    /// Floats use IEEE 754 total ordering, so NaN sorts after all other numbers.
+    pub fn compare(&self, other: &Value) -> std::cmp::Ordering {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) | (Value::Timestamp(a), Value::Timestamp(b)) => {
+                a.cmp(b)
+            }
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                // YYYY-MM-DD sorts chronologically as a string
+                _ => self.to_string().cmp(&other.to_string()),
+            },
+        }
+    }
+
+    /// Returns the numeric value of Integers, Floats and Timestamps
+    pub(crate) fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            Value::Timestamp(t) => Some(*t as f64),
+            _ => None,
         }
     }
 
@@ -103,10 +227,18 @@ impl Value {
                     .map_err(|e| return format!("Not a valid Timestamp: {}. {}", value, e))?;
                 Ok(Value::Timestamp(val))
             }
-            DataType::Unsupported(unsup_url) => Ok(Value::Unsupported(UnsupportedValue {
-                value: value.into(),
-                datatype: unsup_url.into(),
-            })),
+            DataType::Unsupported(unsup_url) => {
+                let value = match crate::datatype::get_custom_datatype(unsup_url) {
+                    Some(custom) => (custom.parse)(value).map_err(|e| {
+                        format!("Not a valid {}: {}. {}", custom.shortname, value, e)
+                    })?,
+                    None => value.into(),
+                };
+                Ok(Value::Unsupported(UnsupportedValue {
+                    value,
+                    datatype: unsup_url.into(),
+                }))
+            }
             DataType::Boolean => {
                 let bool = match value {
                     "true" => true,
@@ -129,6 +261,17 @@ impl Value {
         Value::new(value, &match_datatype(datatype))
     }
 
+    /// Returns the subjects of a ResourceArray, including those of a nested one
+    pub fn to_subjects(&self) -> AtomicResult<Vec<String>> {
+        match self {
+            Value::ResourceArray(arr) => Ok(arr.clone()),
+            Value::ResourceArrayNested(nested) => {
+                Ok(nested.iter().map(|sub| sub.subject.clone()).collect())
+            }
+            _ => Err(format!("Value {} is not a Resource Array", self).into()),
+        }
+    }
+
     /// Returns a Vector, if the Value is one
     pub fn to_vec(&self) -> AtomicResult<&Vec<String>> {
         if let Value::ResourceArray(arr) = self {
@@ -154,6 +297,15 @@ impl Value {
         }
     }
 
+    /// Returns a Float, if the Atom is one. Integers are converted.
+    pub fn to_float(&self) -> AtomicResult<f64> {
+        match self {
+            Value::Float(float) => Ok(float.to_owned()),
+            Value::Integer(int) => Ok(*int as f64),
+            _ => Err(format!("Value {} is not a Float", self).into()),
+        }
+    }
+
     /// Returns a PropVals Hashmap, if the Atom is a NestedResource
     pub fn to_nested(&self) -> AtomicResult<&PropVals> {
         if let Value::NestedResource(nested) = self {
@@ -200,6 +352,19 @@ impl From<PropVals> for Value {
     }
 }
 
+impl From<Vec<crate::Resource>> for Value {
+    fn from(val: Vec<crate::Resource>) -> Self {
+        Value::ResourceArrayNested(
+            val.into_iter()
+                .map(|resource| SubResource {
+                    subject: resource.get_subject().clone(),
+                    propvals: resource.get_propvals().clone(),
+                })
+                .collect(),
+        )
+    }
+}
+
 impl From<bool> for Value {
     fn from(val: bool) -> Self {
         Value::Boolean(val)
@@ -226,6 +391,16 @@ impl fmt::Display for Value {
             Value::NestedResource(n) => write!(f, "{:?}", n),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Unsupported(u) => write!(f, "{}", u.value),
+            Value::ResourceArrayNested(_) => {
+                let subjects = self.to_subjects().unwrap_or_default();
+                let s = crate::serialize::serialize_json_array(&subjects)
+                    .unwrap_or_else(|_e| format!("[Could not serialize resource array: {:?}", subjects));
+                write!(f, "{}", s)
+            }
+            Value::LangString(l) => {
+                let s = serde_json::to_string(l.texts()).unwrap_or_default();
+                write!(f, "{}", s)
+            }
         }
     }
 }
@@ -257,4 +432,21 @@ mod test {
         Value::new("12000-02-02", &DataType::Date).unwrap_err();
         Value::new("a", &DataType::Float).unwrap_err();
     }
+
+    #[test]
+    fn compares_by_datatype() {
+        use std::cmp::Ordering;
+        assert_eq!(Value::Integer(9).compare(&Value::Integer(10)), Ordering::Less);
+        assert_eq!(Value::Float(9.5).compare(&Value::Integer(10)), Ordering::Less);
+        assert_eq!(Value::Timestamp(100).compare(&Value::Timestamp(20)), Ordering::Greater);
+        assert_eq!(Value::Boolean(false).compare(&Value::Boolean(true)), Ordering::Less);
+        assert_eq!(
+            Value::Date("2020-01-02".into()).compare(&Value::Date("2021-01-01".into())),
+            Ordering::Less
+        );
+        assert_eq!(Value::String("b".into()).compare(&Value::String("a".into())), Ordering::Greater);
+        // NaN has a fixed place, so sorting stays consistent
+        assert_eq!(Value::Float(f64::NAN).compare(&Value::Float(1.0)), Ordering::Greater);
+        assert_eq!(Value::Float(1.0).compare(&Value::Float(f64::NAN)), Ordering::Less);
+    }
 }
@@ -0,0 +1,44 @@
+//! Compiles the code that `atomic_lib::codegen` generates for Classes with names that aren't valid Rust identifiers.
+//! If the generated code changes, replace `codegen/generated.rs` with the new output.
+
+use std::convert::TryFrom;
+
+use atomic_lib::Storelike;
+
+#[allow(dead_code)]
+mod generated {
+    include!("codegen/generated.rs");
+}
+
+fn generate() -> String {
+    let store = atomic_lib::Store::init().unwrap();
+    store.populate().unwrap();
+    atomic_lib::codegen::json_ad_to_rust(include_str!("codegen/models.json"), &store).unwrap()
+}
+
+#[test]
+fn generated_code_is_up_to_date() {
+    let code = generate();
+    assert!(
+        code == include_str!("codegen/generated.rs"),
+        "The generated code has changed:\n{}",
+        code
+    );
+}
+
+#[test]
+fn generated_code_converts_resources() {
+    let model = generated::_3dModel {
+        subject: "https://example.com/model".into(),
+        subject2: "https://example.com/other".into(),
+        type_: 1.5,
+        type_2: vec!["https://example.com/a".into()],
+        _2nd: None,
+    };
+    let resource = model.clone().into_resource();
+    assert_eq!(
+        resource.get(atomic_lib::urls::IS_A).unwrap().to_string(),
+        r#"["https://example.com/classes/3d-model"]"#
+    );
+    assert_eq!(generated::_3dModel::try_from(resource).unwrap(), model);
+}
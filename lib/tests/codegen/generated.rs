@@ -0,0 +1,188 @@
+// Generated from Atomic Classes by atomic_lib::codegen. Do not edit by hand.
+
+/// Class named like a keyword
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfClass {
+    pub subject: String,
+    /// Shortname that is a keyword
+    pub self_: String,
+    /// Can't be a raw identifier
+    pub crate_: i64,
+    /// Reserved in the 2018 edition
+    pub try_: Option<bool>,
+}
+
+impl SelfClass {
+    /// URL of the Class
+    pub const CLASS: &'static str = "https://example.com/classes/self";
+
+    /// Converts the struct to a Resource that is an instance of the Class
+    pub fn into_resource(self) -> atomic_lib::Resource {
+        let mut propvals = atomic_lib::resources::PropVals::new();
+        propvals.insert("https://atomicdata.dev/properties/isA".into(), atomic_lib::Value::ResourceArray(vec![Self::CLASS.into()]));
+        let val = self.self_;
+        propvals.insert("https://example.com/properties/self".into(), atomic_lib::Value::String(val));
+        let val = self.crate_;
+        propvals.insert("https://example.com/properties/crate".into(), atomic_lib::Value::Integer(val));
+        if let Some(val) = self.try_ {
+            propvals.insert("https://example.com/properties/try".into(), atomic_lib::Value::Boolean(val));
+        }
+        atomic_lib::Resource::from_propvals(propvals, self.subject)
+    }
+}
+
+impl std::convert::TryFrom<atomic_lib::Resource> for SelfClass {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(resource: atomic_lib::Resource) -> Result<Self, Self::Error> {
+        Ok(SelfClass {
+            subject: resource.get_subject().clone(),
+            self_: {
+                let val = resource.get("https://example.com/properties/self")?;
+                val.to_string()
+            },
+            crate_: {
+                let val = resource.get("https://example.com/properties/crate")?;
+                val.to_int()?
+            },
+            try_: match resource.get("https://example.com/properties/try") {
+                Ok(val) => Some(val.to_bool()?),
+                Err(_) => None,
+            },
+        })
+    }
+}
+
+/// Class that starts with a digit
+#[derive(Clone, Debug, PartialEq)]
+pub struct _3dModel {
+    pub subject: String,
+    /// Same name as the subject field
+    pub subject2: String,
+    /// First Property named type
+    pub type_: f64,
+    /// Second Property named type
+    pub type_2: Vec<String>,
+    /// Starts with a digit
+    pub _2nd: Option<i64>,
+}
+
+impl _3dModel {
+    /// URL of the Class
+    pub const CLASS: &'static str = "https://example.com/classes/3d-model";
+
+    /// Converts the struct to a Resource that is an instance of the Class
+    pub fn into_resource(self) -> atomic_lib::Resource {
+        let mut propvals = atomic_lib::resources::PropVals::new();
+        propvals.insert("https://atomicdata.dev/properties/isA".into(), atomic_lib::Value::ResourceArray(vec![Self::CLASS.into()]));
+        let val = self.subject2;
+        propvals.insert("https://example.com/properties/subject".into(), atomic_lib::Value::AtomicUrl(val));
+        let val = self.type_;
+        propvals.insert("https://example.com/properties/type".into(), atomic_lib::Value::Float(val));
+        let val = self.type_2;
+        propvals.insert("https://example.com/properties/other-type".into(), atomic_lib::Value::ResourceArray(val));
+        if let Some(val) = self._2nd {
+            propvals.insert("https://example.com/properties/2nd".into(), atomic_lib::Value::Timestamp(val));
+        }
+        atomic_lib::Resource::from_propvals(propvals, self.subject)
+    }
+}
+
+impl std::convert::TryFrom<atomic_lib::Resource> for _3dModel {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(resource: atomic_lib::Resource) -> Result<Self, Self::Error> {
+        Ok(_3dModel {
+            subject: resource.get_subject().clone(),
+            subject2: {
+                let val = resource.get("https://example.com/properties/subject")?;
+                val.to_string()
+            },
+            type_: {
+                let val = resource.get("https://example.com/properties/type")?;
+                val.to_float()?
+            },
+            type_2: {
+                let val = resource.get("https://example.com/properties/other-type")?;
+                val.to_subjects()?
+            },
+            _2nd: match resource.get("https://example.com/properties/2nd") {
+                Ok(val) => Some(val.to_int()?),
+                Err(_) => None,
+            },
+        })
+    }
+}
+
+/// Class named like a type that the generated code uses
+#[derive(Clone, Debug, PartialEq)]
+pub struct StringClass {
+    pub subject: String,
+    /// Plain field
+    pub note: String,
+}
+
+impl StringClass {
+    /// URL of the Class
+    pub const CLASS: &'static str = "https://example.com/classes/string";
+
+    /// Converts the struct to a Resource that is an instance of the Class
+    pub fn into_resource(self) -> atomic_lib::Resource {
+        let mut propvals = atomic_lib::resources::PropVals::new();
+        propvals.insert("https://atomicdata.dev/properties/isA".into(), atomic_lib::Value::ResourceArray(vec![Self::CLASS.into()]));
+        let val = self.note;
+        propvals.insert("https://example.com/properties/note".into(), atomic_lib::Value::Markdown(val));
+        atomic_lib::Resource::from_propvals(propvals, self.subject)
+    }
+}
+
+impl std::convert::TryFrom<atomic_lib::Resource> for StringClass {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(resource: atomic_lib::Resource) -> Result<Self, Self::Error> {
+        Ok(StringClass {
+            subject: resource.get_subject().clone(),
+            note: {
+                let val = resource.get("https://example.com/properties/note")?;
+                val.to_string()
+            },
+        })
+    }
+}
+
+/// Second Class with the same shortname
+#[derive(Clone, Debug, PartialEq)]
+pub struct StringClass2 {
+    pub subject: String,
+    /// Plain field
+    pub note: Option<String>,
+}
+
+impl StringClass2 {
+    /// URL of the Class
+    pub const CLASS: &'static str = "https://example.com/classes/string-2";
+
+    /// Converts the struct to a Resource that is an instance of the Class
+    pub fn into_resource(self) -> atomic_lib::Resource {
+        let mut propvals = atomic_lib::resources::PropVals::new();
+        propvals.insert("https://atomicdata.dev/properties/isA".into(), atomic_lib::Value::ResourceArray(vec![Self::CLASS.into()]));
+        if let Some(val) = self.note {
+            propvals.insert("https://example.com/properties/note".into(), atomic_lib::Value::Markdown(val));
+        }
+        atomic_lib::Resource::from_propvals(propvals, self.subject)
+    }
+}
+
+impl std::convert::TryFrom<atomic_lib::Resource> for StringClass2 {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(resource: atomic_lib::Resource) -> Result<Self, Self::Error> {
+        Ok(StringClass2 {
+            subject: resource.get_subject().clone(),
+            note: match resource.get("https://example.com/properties/note") {
+                Ok(val) => Some(val.to_string()),
+                Err(_) => None,
+            },
+        })
+    }
+}
@@ -0,0 +1,40 @@
+//! The messages that are sent between the WebSocket actors.
+//! See `commit_monitor.rs` and `handlers/web_sockets.rs`.
+
+use crate::handlers::web_sockets::WebSocketConnection;
+use actix::{prelude::Message, Addr};
+use atomic_lib::commit::CommitResponse;
+
+/// Subscribes a WebSocketConnection to a subject.
+/// Includes the children of the subject, and the members if it's a Collection.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Subscribe {
+    pub addr: Addr<WebSocketConnection>,
+    pub subject: String,
+    /// The authenticated Agent of the connection, used to check read rights
+    pub agent: Option<String>,
+}
+
+/// Stops sending Commits for a subject to a WebSocketConnection
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Unsubscribe {
+    pub addr: Addr<WebSocketConnection>,
+    pub subject: String,
+}
+
+/// Removes all subscriptions of a WebSocketConnection, when it closes
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Disconnect {
+    pub addr: Addr<WebSocketConnection>,
+}
+
+/// A Commit that has been applied.
+/// Sent from the Db to the CommitMonitor, and from the CommitMonitor to the subscribed WebSocketConnections.
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct CommitMessage {
+    pub commit_response: CommitResponse,
+}
@@ -1,6 +1,11 @@
 //! App state, which is accessible from handlers
-use crate::{config::Config, errors::BetterResult};
+use crate::{
+    actor_messages::CommitMessage, commit_monitor::CommitMonitor, config::Config,
+    errors::BetterResult, limits::Limits,
+};
+use actix::{Actor, Addr};
 use atomic_lib::{Storelike, agents::{Agent, generate_public_key}, mapping::Mapping};
+use std::sync::{Arc, Mutex};
 
 /// Context for the server (not an individual request)
 #[derive(Clone)]
@@ -11,19 +16,30 @@ pub struct AppState {
     pub mapping: Mapping,
     /// App Configuration
     pub config: Config,
+    /// Rate limits and quotas for Commits. Shared between all workers.
+    pub limits: Arc<Mutex<Limits>>,
+    /// Sends applied Commits to WebSocket subscribers
+    pub commit_monitor: Addr<CommitMonitor>,
 }
 
 /// Creates the server context.
 /// Initializes a store.
 pub fn init(config: Config) -> BetterResult<AppState> {
     // Opens or creates the database
-    let store = atomic_lib::Db::init(&config.store_path, config.local_base_url.clone())?;
+    let mut store = atomic_lib::Db::init(&config.store_path, config.local_base_url.clone())?;
+    store.set_classtype_mode(config.classtype_mode);
     // Maybe running populate every time is too much
     store.populate()?;
     // This may no longer be needed
     let mapping = Mapping::init();
     // Create a new agent if it does not yet exist.
-    let ag_cfg: atomic_lib::config::Config = match atomic_lib::config::read_config(&config.config_file_path) {
+    // The passphrase is read from ATOMIC_PASSPHRASE, or prompted if the private key is encrypted.
+    let read_config = atomic_lib::config::read_config_with(&config.config_file_path, || {
+        Ok(rpassword::read_password_from_tty(Some(
+            "Passphrase for the private key in your config: ",
+        ))?)
+    });
+    let ag_cfg: atomic_lib::config::Config = match read_config {
         Ok(agent_config) => {
             match store.get_resource(&agent_config.agent) {
                 Ok(_) => {
@@ -51,6 +67,10 @@ pub fn init(config: Config) -> BetterResult<AppState> {
                 }
             }
         }
+        // Don't overwrite existing configs that can't be read, e.g. because of a wrong passphrase
+        Err(e) if config.config_file_path.exists() => {
+            return Err(format!("Could not read config at {:?}. {}", config.config_file_path, e).into());
+        }
         Err(_) => {
             let agent = store.create_agent(Some("root"))?;
             let cfg = atomic_lib::config::Config {
@@ -78,10 +98,23 @@ pub fn init(config: Config) -> BetterResult<AppState> {
     log::info!("Setting default Agent {}...", &agent.subject);
     store.set_default_agent(agent);
 
+    let limits = Arc::new(Mutex::new(Limits::new(&config, &store)?));
+
+    // Every applied Commit is sent to the CommitMonitor, which notifies the WebSocket subscribers
+    let commit_monitor = CommitMonitor::new(store.clone()).start();
+    let monitor = commit_monitor.clone();
+    store.set_handle_commit(Box::new(move |commit_response| {
+        monitor.do_send(CommitMessage {
+            commit_response: commit_response.clone(),
+        })
+    }));
+
     Ok(AppState {
         store,
         config,
         mapping,
+        limits,
+        commit_monitor,
     })
 }
 
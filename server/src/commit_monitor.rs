@@ -0,0 +1,164 @@
+//! The CommitMonitor keeps track of WebSocket subscriptions, and sends applied Commits to the subscribers.
+
+use crate::{
+    actor_messages::{CommitMessage, Disconnect, Subscribe, Unsubscribe},
+    handlers::web_sockets::WebSocketConnection,
+};
+use actix::prelude::{Actor, Addr, Context, Handler};
+use atomic_lib::{
+    collections::{local_collection_query, Materialized},
+    hierarchy::check_read,
+    urls, Db, Resource, Storelike,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Receives every applied Commit from the Db, and sends it to the WebSocketConnections that subscribed to the Resource, one of its parents, or a Collection that contains it.
+/// Only sends Commits to connections whose Agent has read rights.
+pub struct CommitMonitor {
+    /// Per subscribed subject: the connections and their authenticated Agents
+    subscriptions: HashMap<String, HashMap<Addr<WebSocketConnection>, Option<String>>>,
+    /// The queries of subscribed Collections, used to check which Resources are members
+    collections: HashMap<String, Materialized>,
+    store: Db,
+}
+
+impl CommitMonitor {
+    pub fn new(store: Db) -> CommitMonitor {
+        CommitMonitor {
+            subscriptions: HashMap::new(),
+            collections: HashMap::new(),
+            store,
+        }
+    }
+
+    /// Returns the subjects that a change to this Resource is relevant for: the Resource itself and its parents.
+    fn affected_subjects(&self, resource: &Resource) -> HashSet<String> {
+        let mut subjects = HashSet::new();
+        subjects.insert(resource.get_subject().clone());
+        let mut current = resource.get(urls::PARENT).ok().map(|v| v.to_string());
+        while let Some(parent) = current {
+            // Prevents endless loops in circular hierarchies
+            if !subjects.insert(parent.clone()) {
+                break;
+            }
+            current = self
+                .store
+                .get_resource(&parent)
+                .ok()
+                .and_then(|r| r.get(urls::PARENT).ok().map(|v| v.to_string()));
+        }
+        subjects
+    }
+}
+
+impl Actor for CommitMonitor {
+    type Context = Context<Self>;
+}
+
+impl Handler<Subscribe> for CommitMonitor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Subscribe, _ctx: &mut Context<Self>) {
+        // Collections are calculated, so their query is stored to check new Commits against
+        match local_collection_query(&self.store, &msg.subject) {
+            Ok(Some(query)) => {
+                self.collections.insert(msg.subject.clone(), query);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Could not read the query of Collection {}: {}", msg.subject, e),
+        }
+        log::info!("Subscribing to {}", msg.subject);
+        self.subscriptions
+            .entry(msg.subject)
+            .or_default()
+            .insert(msg.addr, msg.agent);
+    }
+}
+
+impl Handler<Unsubscribe> for CommitMonitor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _ctx: &mut Context<Self>) {
+        if let Some(subscribers) = self.subscriptions.get_mut(&msg.subject) {
+            subscribers.remove(&msg.addr);
+            if subscribers.is_empty() {
+                self.subscriptions.remove(&msg.subject);
+                self.collections.remove(&msg.subject);
+            }
+        }
+    }
+}
+
+impl Handler<Disconnect> for CommitMonitor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnect, _ctx: &mut Context<Self>) {
+        for subscribers in self.subscriptions.values_mut() {
+            subscribers.remove(&msg.addr);
+        }
+        let collections = &mut self.collections;
+        self.subscriptions.retain(|subject, subscribers| {
+            if subscribers.is_empty() {
+                collections.remove(subject);
+            }
+            !subscribers.is_empty()
+        });
+    }
+}
+
+impl Handler<CommitMessage> for CommitMonitor {
+    type Result = ();
+
+    fn handle(&mut self, msg: CommitMessage, _ctx: &mut Context<Self>) {
+        let response = &msg.commit_response;
+        // Destroyed Resources are checked using their last version
+        let resource = match response
+            .resource_new
+            .as_ref()
+            .or(response.resource_old.as_ref())
+        {
+            Some(resource) => resource,
+            None => return,
+        };
+        let affected = self.affected_subjects(resource);
+        let mut receivers: Vec<Addr<WebSocketConnection>> = Vec::new();
+        for (subject, subscribers) in self.subscriptions.iter() {
+            let is_member = match self.collections.get(subject) {
+                Some(query) => response
+                    .resource_new
+                    .iter()
+                    .chain(response.resource_old.iter())
+                    .any(|r| match query.matches(r, &self.store) {
+                        Ok(matches) => matches,
+                        Err(e) => {
+                            log::warn!("Could not check if {} is in {}: {}", r.get_subject(), subject, e);
+                            false
+                        }
+                    }),
+                None => false,
+            };
+            if !affected.contains(subject) && !is_member {
+                continue;
+            }
+            for (addr, agent) in subscribers {
+                if receivers.contains(addr) {
+                    continue;
+                }
+                match check_read(&self.store, resource, agent.as_deref()) {
+                    Ok(true) => {
+                        receivers.push(addr.clone());
+                    }
+                    Ok(false) => {}
+                    Err(e) => log::warn!(
+                        "Could not check read rights for {}: {}",
+                        resource.get_subject(),
+                        e
+                    ),
+                }
+            }
+        }
+        for addr in receivers {
+            addr.do_send(msg.clone());
+        }
+    }
+}
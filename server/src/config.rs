@@ -43,6 +43,25 @@ pub struct Config {
     pub static_path: PathBuf,
     /// Path to where the store is located. (defaults to `~/.config/atomic/db`)
     pub store_path: PathBuf,
+    // ===  LIMITS  ===
+    /// Maximum amount of Commits per minute for a single Agent. (defaults to 120, 0 is unlimited)
+    pub rate_limit_agent: Option<u32>,
+    /// Maximum amount of Commits per minute from a single IP address. (defaults to 300, 0 is unlimited)
+    pub rate_limit_ip: Option<u32>,
+    /// IP addresses of reverse proxies, whose Forwarded headers are used to find the IP of the client. (empty by default)
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Maximum amount of Resources (including Commits) per Drive. (unlimited by default)
+    pub quota_resources: Option<i64>,
+    /// Maximum amount of bytes per Drive, measured as serialized JSON-AD. (unlimited by default)
+    pub quota_bytes: Option<i64>,
+    // ===  MIRROR  ===
+    /// URL of a Drive on another server, which is mirrored read-only. (disabled by default)
+    pub mirror: Option<String>,
+    /// Seconds between pulling new Commits from the mirrored Drive. (defaults to 10)
+    pub mirror_interval: u64,
+    // ===  VALIDATION  ===
+    /// How strictly the classtype of Properties is checked when applying Commits: strict, lenient or off. (defaults to lenient)
+    pub classtype_mode: atomic_lib::validate::ClasstypeMode,
 }
 
 /// Creates the server config, reads .env values and sets defaults
@@ -65,6 +84,14 @@ pub fn init() -> BetterResult<Config> {
     let mut key_path = config_dir.clone();
     key_path.push("https/key.pem");
     let mut email = None;
+    let mut rate_limit_agent = Some(120);
+    let mut rate_limit_ip = Some(300);
+    let mut trusted_proxies = Vec::new();
+    let mut quota_resources = None;
+    let mut quota_bytes = None;
+    let mut mirror = None;
+    let mut mirror_interval = 10;
+    let mut classtype_mode = atomic_lib::validate::ClasstypeMode::default();
     for (key, value) in env::vars() {
         match &*key {
             "ATOMIC_CONFIG_PATH" => {
@@ -107,6 +134,38 @@ pub fn init() -> BetterResult<Config> {
             "ATOMIC_HTTPS" => {
                 https = value.parse().expect("ATOMIC_HTTPS is not a boolean");
             }
+            "ATOMIC_RATE_LIMIT_AGENT" => {
+                rate_limit_agent = zero_to_none(value.parse().expect("ATOMIC_RATE_LIMIT_AGENT is not a number"));
+            }
+            "ATOMIC_RATE_LIMIT_IP" => {
+                rate_limit_ip = zero_to_none(value.parse().expect("ATOMIC_RATE_LIMIT_IP is not a number"));
+            }
+            "ATOMIC_TRUSTED_PROXIES" => {
+                for proxy in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                    trusted_proxies.push(proxy.parse().map_err(|e| {
+                        format!(
+                            "Could not parse ATOMIC_TRUSTED_PROXIES. Is {} a valid IP address? {}",
+                            proxy, e
+                        )
+                    })?);
+                }
+            }
+            "ATOMIC_QUOTA_RESOURCES" => {
+                quota_resources = zero_to_none(value.parse().expect("ATOMIC_QUOTA_RESOURCES is not a number"));
+            }
+            "ATOMIC_QUOTA_BYTES" => {
+                quota_bytes = zero_to_none(value.parse().expect("ATOMIC_QUOTA_BYTES is not a number"));
+            }
+            "ATOMIC_MIRROR" => {
+                mirror = Some(value.trim_end_matches('/').to_string());
+            }
+            "ATOMIC_MIRROR_INTERVAL" => {
+                mirror_interval = value.parse().expect("ATOMIC_MIRROR_INTERVAL is not a number");
+            }
+            "ATOMIC_CLASSTYPE_VALIDATION" => {
+                classtype_mode = atomic_lib::validate::ClasstypeMode::from_string(&value)
+                    .expect("ATOMIC_CLASSTYPE_VALIDATION should be strict, lenient or off");
+            }
             _ => {}
         }
     }
@@ -137,5 +196,22 @@ pub fn init() -> BetterResult<Config> {
         local_base_url,
         store_path,
         static_path,
+        rate_limit_agent,
+        rate_limit_ip,
+        trusted_proxies,
+        quota_resources,
+        quota_bytes,
+        mirror,
+        mirror_interval,
+        classtype_mode,
     })
 }
+
+/// Limits that are set to 0 are disabled
+fn zero_to_none<T: Default + PartialEq>(value: T) -> Option<T> {
+    if value == T::default() {
+        None
+    } else {
+        Some(value)
+    }
+}
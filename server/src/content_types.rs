@@ -89,6 +89,39 @@ pub fn parse_accept_header(header: &str) -> ContentType {
     ContentType::HTML
 }
 
+/// Returns the languages of the Accept-Language header, most preferred first.
+/// Used for picking a translation of language-tagged strings in plain JSON.
+pub fn get_accept_language(map: &HeaderMap) -> Vec<String> {
+    match map.get("Accept-Language") {
+        Some(header) => parse_accept_language(header.to_str().unwrap_or("")),
+        None => Vec::new(),
+    }
+}
+
+/// Parses an HTTP Accept-Language header, such as `nl-BE, nl;q=0.9, en;q=0.8`.
+/// Sorts the languages by their q value, and leaves out the ones with q=0.
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut languages: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let language = pieces.next()?.trim();
+            if language.is_empty() {
+                return None;
+            }
+            let quality = pieces
+                .filter_map(|piece| piece.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((language.to_string(), quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // Stable, so languages with the same q value keep their order
+    languages.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    languages.into_iter().map(|(language, _)| language).collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -105,4 +138,13 @@ mod test {
         assert!(parse_accept_header("application/ad+json ; ") == ContentType::JSONAD);
         assert!(parse_accept_header(" application/ad+json ; ") == ContentType::JSONAD);
     }
+
+    #[test]
+    fn parse_languages() {
+        assert_eq!(
+            parse_accept_language("en;q=0.8, nl-BE, nl;q=0.9, fr;q=0"),
+            vec!["nl-BE", "nl", "en"]
+        );
+        assert!(parse_accept_language("").is_empty());
+    }
 }
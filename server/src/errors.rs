@@ -8,6 +8,10 @@ pub type BetterResult<T> = std::result::Result<T, AppError>;
 #[derive(Debug)]
 pub enum AppErrorType {
     NotFoundError,
+    UnauthorizedError,
+    BadRequestError,
+    TooManyRequestsError,
+    PayloadTooLargeError,
     OtherError,
 }
 
@@ -27,6 +31,35 @@ impl AppError {
         }
     }
 
+    pub fn unauthorized(message: String) -> AppError {
+        AppError {
+            message: format!("Unauthorized. {}", message),
+            error_type: AppErrorType::UnauthorizedError
+        }
+    }
+
+    /// For requests that will keep failing when they are sent again, e.g. invalid Commits
+    pub fn bad_request(message: impl ToString) -> AppError {
+        AppError {
+            message: message.to_string(),
+            error_type: AppErrorType::BadRequestError
+        }
+    }
+
+    pub fn too_many_requests(message: String) -> AppError {
+        AppError {
+            message,
+            error_type: AppErrorType::TooManyRequestsError
+        }
+    }
+
+    pub fn payload_too_large(message: String) -> AppError {
+        AppError {
+            message,
+            error_type: AppErrorType::PayloadTooLargeError
+        }
+    }
+
     pub fn other_error(message: String) -> AppError {
         AppError {
             message,
@@ -46,6 +79,10 @@ impl ResponseError for AppError {
     fn status_code(&self) -> StatusCode {
         match self.error_type {
             AppErrorType::NotFoundError => StatusCode::NOT_FOUND,
+            AppErrorType::UnauthorizedError => StatusCode::UNAUTHORIZED,
+            AppErrorType::BadRequestError => StatusCode::BAD_REQUEST,
+            AppErrorType::TooManyRequestsError => StatusCode::TOO_MANY_REQUESTS,
+            AppErrorType::PayloadTooLargeError => StatusCode::PAYLOAD_TOO_LARGE,
             AppErrorType::OtherError => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -0,0 +1,104 @@
+use crate::{
+    appstate::AppState,
+    errors::{AppError, BetterResult},
+    helpers::get_auth_headers,
+    limits::Usage,
+};
+use actix_web::{web, HttpResponse};
+use atomic_lib::{authentication::get_agent_from_auth_values_and_check, Storelike};
+use serde::Serialize;
+use std::{collections::HashMap, sync::Mutex};
+
+#[derive(Serialize)]
+struct LimitsReport<'a> {
+    rate_limit_agent: Option<u32>,
+    rate_limit_ip: Option<u32>,
+    quota_resources: Option<i64>,
+    quota_bytes: Option<i64>,
+    /// Commits per Agent in the current minute
+    agents: HashMap<String, u32>,
+    /// Commits per IP address in the current minute
+    ips: HashMap<String, u32>,
+    /// Resources and bytes per Drive. Only calculated when quotas are enabled.
+    drives: Option<&'a HashMap<String, Usage>>,
+}
+
+/// Checks if the request is signed by an Agent with write rights on the server's Drive
+fn check_admin(context: &AppState, req: &actix_web::HttpRequest, path: &str) -> BetterResult<()> {
+    let store = &context.store;
+    let subject = format!("{}{}", context.config.local_base_url, path);
+    let auth_header_values = get_auth_headers(req.headers(), subject)
+        .map_err(|e| AppError::unauthorized(e.to_string()))?;
+    let agent = get_agent_from_auth_values_and_check(auth_header_values, store)
+        .map_err(|e| AppError::unauthorized(e.to_string()))?
+        .ok_or_else(|| AppError::unauthorized(format!("Sign your request to see {}.", path)))?;
+    let drive = store.get_resource(store.get_base_url())?;
+    if !atomic_lib::hierarchy::check_write(store, &drive, agent.clone())? {
+        return Err(AppError::unauthorized(format!(
+            "Agent {} has no write rights on the Drive.",
+            agent
+        )));
+    }
+    Ok(())
+}
+
+/// Shows the configured rate limits and quotas, and the current usage.
+/// Requires authentication by an Agent with write rights on the server's Drive.
+pub async fn get_limits(
+    data: web::Data<Mutex<AppState>>,
+    req: actix_web::HttpRequest,
+) -> BetterResult<HttpResponse> {
+    let context = data.lock().unwrap();
+    check_admin(&context, &req, "/admin/limits")?;
+    let mut limits = context.limits.lock()?;
+    let now = atomic_lib::datetime_helpers::now();
+    let agents = limits.agents.current(now);
+    let ips = limits.ips.current(now);
+    let rate_limit_agent = context.config.rate_limit_agent;
+    let rate_limit_ip = context.config.rate_limit_ip;
+    let quota_resources = limits.quota_resources;
+    let quota_bytes = limits.quota_bytes;
+    let drives = if quota_resources.is_some() || quota_bytes.is_some() {
+        Some(limits.drive_usage())
+    } else {
+        None
+    };
+    let report = LimitsReport {
+        rate_limit_agent,
+        rate_limit_ip,
+        quota_resources,
+        quota_bytes,
+        agents,
+        ips,
+        drives,
+    };
+    let body = serde_json::to_string_pretty(&report)
+        .map_err(|e| AppError::other_error(e.to_string()))?;
+    Ok(HttpResponse::Ok()
+        .header("Content-Type", "application/json")
+        .body(body))
+}
+
+/// Validates the whole store, and returns the report as JSON.
+/// Requires authentication by an Agent with write rights on the server's Drive.
+pub async fn get_validate(
+    data: web::Data<Mutex<AppState>>,
+    req: actix_web::HttpRequest,
+) -> BetterResult<HttpResponse> {
+    let store = {
+        let context = data.lock().unwrap();
+        check_admin(&context, &req, "/admin/validate")?;
+        context.store.clone()
+    };
+    // Validation can take long, so it runs on another thread, without locking the server.
+    let report = web::block(move || -> Result<_, String> {
+        Ok(atomic_lib::validate::validate_store(&store, false))
+    })
+    .await
+    .map_err(|e| AppError::other_error(e.to_string()))?;
+    let body = serde_json::to_string_pretty(&report)
+        .map_err(|e| AppError::other_error(e.to_string()))?;
+    Ok(HttpResponse::Ok()
+        .header("Content-Type", "application/json")
+        .body(body))
+}
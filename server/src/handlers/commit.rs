@@ -1,38 +1,122 @@
-use crate::{appstate::AppState, errors::BetterResult};
+use crate::{
+    appstate::AppState,
+    errors::{AppError, BetterResult},
+    limits::{client_ip, commit_usage},
+};
 use actix_web::{web, HttpResponse};
-use atomic_lib::{parse::parse_json_ad_commit_resource, Commit, Storelike};
+use atomic_lib::{
+    client::fetch_body,
+    commit::MAX_COMMIT_AGE_MS,
+    parse::{parse_json_ad_commit_resource, parse_json_ad_resource, JSON_AD_MIME},
+    validate::{missing_references, ClasstypeMode},
+    Commit, Storelike,
+};
 use std::sync::Mutex;
 
 /// Send and process a Commit.
 /// Currently only accepts JSON-AD
+/// Returns a 429 if the Agent or IP exceeds the rate limit, and a 413 if the Drive exceeds its quota.
+/// Returns a 400 if the Commit is invalid.
 pub async fn post_commit(
     data: web::Data<Mutex<AppState>>,
     body: String,
+    req: actix_web::HttpRequest,
 ) -> BetterResult<HttpResponse> {
-    let mut context = data.lock().unwrap();
-    let store = &mut context.store;
     let mut builder = HttpResponse::Ok();
-    let incoming_commit_resource = parse_json_ad_commit_resource(&body, store)?;
-    let incoming_commit = Commit::from_resource(incoming_commit_resource)?;
     let now = atomic_lib::datetime_helpers::now();
-    // 86,400,000 is 24 hrs
-    let acceptable_milliseconds = 86_400_000;
-    let time_ago = now - incoming_commit.created_at;
-    if time_ago > acceptable_milliseconds {
-        return Err(format!(
-            "Commit was was createdAt {}ms ago, which is more than the maximum of {}ms.",
-            time_ago, acceptable_milliseconds
-        )
-        .into());
+    let (incoming_commit, missing) = {
+        let context = data.lock().unwrap();
+        let store = &context.store;
+        let mut limits = context.limits.lock()?;
+        // Uses the address of the peer, or the Forwarded headers if the peer is a trusted reverse proxy
+        let ip = client_ip(
+            req.peer_addr().map(|addr| addr.ip()),
+            req.connection_info().realip_remote_addr(),
+            &context.config.trusted_proxies,
+        );
+        if let Some(ip) = ip {
+            limits
+                .ips
+                .check(&ip, now)
+                .map_err(AppError::too_many_requests)?;
+        }
+        let incoming_commit_resource =
+            parse_json_ad_commit_resource(&body, store).map_err(AppError::bad_request)?;
+        let incoming_commit =
+            Commit::from_resource(incoming_commit_resource).map_err(AppError::bad_request)?;
+        let time_ago = now - incoming_commit.created_at;
+        if time_ago > MAX_COMMIT_AGE_MS {
+            return Err(AppError::bad_request(format!(
+                "Commit was was createdAt {}ms ago, which is more than the maximum of {}ms.",
+                time_ago, MAX_COMMIT_AGE_MS
+            )));
+        }
+        if let Some(mirror) = &context.config.mirror {
+            if atomic_lib::hierarchy::is_inside(&incoming_commit.subject, mirror) {
+                return Err(AppError::bad_request(format!(
+                    "{} is part of a read-only mirror. Send the Commit to the server of {}.",
+                    incoming_commit.subject, mirror
+                )));
+            }
+        }
+        if !incoming_commit.subject.contains(
+            &store
+                .get_self_url()
+                .ok_or("Cannot apply commits to this store. No self_url is set.")?,
+        ) {
+            return Err(AppError::bad_request("Subject of commit should be sent to other domain - this store can not own this resource."));
+        }
+        // Check the signature first, so others can't use up the limit of some Agent
+        incoming_commit
+            .validate_signature(store)
+            .map_err(AppError::bad_request)?;
+        limits
+            .agents
+            .check(&incoming_commit.signer, now)
+            .map_err(AppError::too_many_requests)?;
+        let missing = if store.get_classtype_mode() == ClasstypeMode::Strict {
+            missing_references(store, &incoming_commit)
+        } else {
+            Vec::new()
+        };
+        (incoming_commit, missing)
+    };
+    // Strict classtype validation needs the referenced Resources in the store.
+    // Fetching them can take a while, so it happens on another thread, without locking the server.
+    let fetched = if missing.is_empty() {
+        Vec::new()
+    } else {
+        web::block(move || -> Result<_, String> {
+            let mut fetched = Vec::new();
+            for subject in missing {
+                match fetch_body(&subject, JSON_AD_MIME) {
+                    Ok(body) => fetched.push((subject, body)),
+                    Err(e) => log::info!("Could not fetch {} for classtype validation: {}", subject, e),
+                }
+            }
+            Ok(fetched)
+        })
+        .await
+        .map_err(|e| AppError::other_error(e.to_string()))?
+    };
+    let context = data.lock().unwrap();
+    let store = &context.store;
+    for (subject, body) in fetched {
+        match parse_json_ad_resource(&body, store) {
+            Ok(resource) if resource.get_subject() == &subject => {
+                store.add_resource_unsafe(&resource)?
+            }
+            _ => log::info!("Fetched Resource {} is invalid", subject),
+        }
     }
-    if !incoming_commit.subject.contains(
-        &store
-            .get_self_url()
-            .ok_or("Cannot apply commits to this store. No self_url is set.")?,
-    ) {
-        return Err("Subject of commit should be sent to other domain - this store can not own this resource.".into());
-    }
-    let saved_commit_resource = incoming_commit.apply_opts(store, true, true, true, true)?;
+    let mut limits = context.limits.lock()?;
+    let (drive, usage) = commit_usage(store, &incoming_commit)?;
+    limits.check_quota(&drive, &usage)?;
+    // Invalid Commits get a 4xx status, so clients know that sending them again won't help
+    let saved_commit_resource = incoming_commit
+        .apply_opts(store, true, true, true, true)
+        .map_err(AppError::bad_request)?;
+    limits.add_usage(&drive, &usage);
     // TODO: better response
     let message = format!(
         "Commit succesfully applied. Can be seen at {}",
@@ -1,4 +1,6 @@
+pub mod admin;
 pub mod commit;
 pub mod resource;
 pub mod single_page_app;
 pub mod tpf;
+pub mod web_sockets;
@@ -1,6 +1,6 @@
-use crate::{appstate::AppState, content_types::ContentType, content_types::get_accept, errors::{AppError, BetterResult}};
+use crate::{appstate::AppState, config::Config, content_types::ContentType, content_types::{get_accept, get_accept_language}, errors::{AppError, BetterResult}, helpers::get_auth_headers};
 use actix_web::{web, HttpResponse};
-use atomic_lib::{Storelike};
+use atomic_lib::{Storelike, authentication::get_agent_from_auth_values_and_check};
 use std::{
     sync::{Mutex},
 };
@@ -47,13 +47,24 @@ pub async fn get_resource(
     // This prevents the browser from displaying the JSON response upon re-opening a closed tab
     // https://github.com/joepio/atomic-data-rust/issues/137
     builder.header("Cache-Control", "no-store, no-cache, must-revalidate, private");
-    let resource = store
-        .get_resource_extended(&subject)
-        // TODO: Don't always return 404 - only when it's actually not found!
-        .map_err(|e| AppError::other_error(e.to_string()))?;
+    let auth_header_values = get_auth_headers(req.headers(), subject.clone())
+        .map_err(|e| AppError::unauthorized(e.to_string()))?;
+    let for_agent = get_agent_from_auth_values_and_check(auth_header_values, store)
+        .map_err(|e| AppError::unauthorized(e.to_string()))?;
+    let resource = match store.get_resource_extended(&subject, for_agent.as_deref()) {
+        Ok(resource) => resource,
+        // Mirrored Resources are served using their original URLs
+        Err(e) => match mirrored_subject(&context.config, &subject) {
+            Some(mirrored) => store
+                .get_resource_extended(&mirrored, for_agent.as_deref())
+                .map_err(|e| AppError::other_error(e.to_string()))?,
+            // TODO: Don't always return 404 - only when it's actually not found!
+            None => return Err(AppError::other_error(e.to_string())),
+        },
+    };
     match content_type {
         ContentType::JSON => {
-            let body = resource.to_json(store)?;
+            let body = resource.to_json_languages(store, &get_accept_language(req.headers()))?;
             Ok(builder.body(body))
         }
         ContentType::JSONLD => {
@@ -76,6 +87,20 @@ pub async fn get_resource(
     }
 }
 
+/// Converts a local subject to the original URL of a mirrored Resource.
+/// Returns None if it's not part of the mirrored Drive.
+fn mirrored_subject(config: &Config, subject: &str) -> Option<String> {
+    let drive = config.mirror.as_ref()?;
+    let origin = atomic_lib::mirror::drive_origin(drive).ok()?;
+    let path = subject.strip_prefix(&config.local_base_url)?;
+    let mirrored = format!("{}{}", origin, path);
+    if atomic_lib::hierarchy::is_inside(&mirrored, drive) {
+        Some(mirrored)
+    } else {
+        None
+    }
+}
+
 /// Finds the extension
 fn try_extension(path: &str) -> Option<(ContentType, &str)> {
     let items: Vec<&str> = path.split('.').collect();
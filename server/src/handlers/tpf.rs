@@ -11,10 +11,15 @@ pub struct TPFQuery {
     pub subject: Option<String>,
     pub property: Option<String>,
     pub value: Option<String>,
+    /// Compares the value in another way than equality, e.g. `gt` or `prefix`. Requires a property.
+    pub operator: Option<String>,
+    /// The upper bound of the `between` operator
+    pub value_end: Option<String>,
 }
 
 /// Triple Pattern Fragment handler.
 /// Reads optional 'subject' 'property' 'value' from query params, searches the store, return triples.
+/// The optional 'operator' and 'value_end' params select ranges, prefixes or substrings instead of equal values.
 pub async fn tpf(
     data: web::Data<Mutex<AppState>>,
     req: actix_web::HttpRequest,
@@ -28,8 +33,25 @@ pub async fn tpf(
     let subject = empty_to_nothing(query.subject.clone());
     let property = empty_to_nothing(query.property.clone());
     let value = empty_to_nothing(query.value.clone());
-    let atoms = store
-        .tpf(subject.as_deref(), property.as_deref(), value.as_deref(), true)?;
+    let operator = empty_to_nothing(query.operator.clone());
+    let atoms = match operator {
+        Some(operator) => {
+            let property = property.ok_or("The operator query param requires a property")?;
+            let datatype = store.get_property(&property)?.data_type;
+            let comparison = atomic_lib::comparison::Comparison::new(
+                atomic_lib::comparison::Operator::from_string(&operator)?,
+                &value.ok_or("The operator query param requires a value")?,
+                empty_to_nothing(query.value_end.clone()).as_deref(),
+                &datatype,
+            )?;
+            store
+                .tpf_compare(&property, &comparison, true)?
+                .into_iter()
+                .filter(|atom| subject.as_ref().map(|s| s == &atom.subject).unwrap_or(true))
+                .collect()
+        }
+        None => store.tpf(subject.as_deref(), property.as_deref(), value.as_deref(), true)?,
+    };
     log::info!("TPF query: {:?}", query);
     builder.header("Content-Type", content_type.to_mime());
     match content_type {
@@ -0,0 +1,140 @@
+//! WebSocket connections, which clients use to subscribe to changes.
+//! Clients send `SUBSCRIBE {subject}` and `UNSUBSCRIBE {subject}` messages.
+//! The server sends `COMMIT {json-ad}` for every applied Commit that is relevant for the subscriptions, and `ERROR {message}` for invalid messages.
+
+use crate::{
+    actor_messages::{CommitMessage, Disconnect, Subscribe, Unsubscribe},
+    appstate::AppState,
+    commit_monitor::CommitMonitor,
+    errors::{AppError, BetterResult},
+    helpers::get_auth_headers,
+};
+use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use atomic_lib::authentication::get_agent_from_auth_values_and_check;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How often heartbeat pings are sent
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long before lack of client response causes a timeout
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Opens a WebSocket connection.
+/// Authenticate by signing the `/ws` URL, using the same headers as for other requests.
+pub async fn web_socket_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    data: web::Data<Mutex<AppState>>,
+) -> BetterResult<HttpResponse> {
+    let context = data.lock().unwrap();
+    let subject = format!("{}/ws", context.config.local_base_url);
+    let auth_header_values = get_auth_headers(req.headers(), subject)
+        .map_err(|e| AppError::unauthorized(e.to_string()))?;
+    let agent = get_agent_from_auth_values_and_check(auth_header_values, &context.store)
+        .map_err(|e| AppError::unauthorized(e.to_string()))?;
+    let connection = WebSocketConnection::new(context.commit_monitor.clone(), agent);
+    ws::start(connection, &req, stream).map_err(|e| AppError::other_error(e.to_string()))
+}
+
+/// A single WebSocket connection with a client
+pub struct WebSocketConnection {
+    /// Client must send a ping at least once every 10 seconds (CLIENT_TIMEOUT), otherwise the connection is dropped
+    heartbeat: Instant,
+    commit_monitor: Addr<CommitMonitor>,
+    /// The authenticated Agent, if any
+    agent: Option<String>,
+}
+
+impl WebSocketConnection {
+    pub fn new(commit_monitor: Addr<CommitMonitor>, agent: Option<String>) -> WebSocketConnection {
+        WebSocketConnection {
+            heartbeat: Instant::now(),
+            commit_monitor,
+            agent,
+        }
+    }
+
+    /// Sends pings and closes the connection if the client stops responding
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.heartbeat) > CLIENT_TIMEOUT {
+                log::info!("WebSocket client heartbeat failed, disconnecting");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    fn handle_text(&mut self, text: &str, ctx: &mut ws::WebsocketContext<Self>) {
+        let addr = ctx.address();
+        match text.split_once(' ') {
+            Some(("SUBSCRIBE", subject)) => self.commit_monitor.do_send(Subscribe {
+                addr,
+                subject: subject.into(),
+                agent: self.agent.clone(),
+            }),
+            Some(("UNSUBSCRIBE", subject)) => self.commit_monitor.do_send(Unsubscribe {
+                addr,
+                subject: subject.into(),
+            }),
+            _ => ctx.text(format!(
+                "ERROR Unknown message: {}. Use SUBSCRIBE or UNSUBSCRIBE followed by a subject.",
+                text
+            )),
+        }
+    }
+}
+
+impl Actor for WebSocketConnection {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.heartbeat(ctx);
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        self.commit_monitor.do_send(Disconnect {
+            addr: ctx.address(),
+        });
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketConnection {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.heartbeat = Instant::now();
+            }
+            Ok(ws::Message::Text(text)) => self.handle_text(&text, ctx),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("WebSocket protocol error: {}", e);
+                ctx.stop();
+            }
+        }
+    }
+}
+
+impl Handler<CommitMessage> for WebSocketConnection {
+    type Result = ();
+
+    fn handle(&mut self, msg: CommitMessage, ctx: &mut Self::Context) {
+        match msg.commit_response.commit_resource.to_json_ad() {
+            Ok(json) => ctx.text(format!("COMMIT {}", json)),
+            Err(e) => log::error!("Could not serialize Commit: {}", e),
+        }
+    }
+}
@@ -1,5 +1,11 @@
 //! Functions useful in the server
 
+use actix_web::http::HeaderMap;
+use atomic_lib::authentication::{
+  AuthValues, HEADER_AGENT, HEADER_PUBLIC_KEY, HEADER_SIGNATURE, HEADER_TIMESTAMP,
+};
+use atomic_lib::errors::AtomicResult;
+
 // Returns None if the string is empty.
 // Useful for parsing form inputs.
 pub fn empty_to_nothing(string: Option<String>) -> Option<String> {
@@ -14,3 +20,27 @@ pub fn empty_to_nothing(string: Option<String>) -> Option<String> {
       None => None
   }
 }
+
+/// Reads the authentication headers of a request.
+/// Returns None if the request has no authentication headers.
+/// Does not check the signature, use `atomic_lib::authentication::get_agent_from_auth_values_and_check` for that.
+pub fn get_auth_headers(
+  map: &HeaderMap,
+  requested_subject: String,
+) -> AtomicResult<Option<AuthValues>> {
+  let public_key = map.get(HEADER_PUBLIC_KEY);
+  let signature = map.get(HEADER_SIGNATURE);
+  let timestamp = map.get(HEADER_TIMESTAMP);
+  let agent_subject = map.get(HEADER_AGENT);
+  match (public_key, signature, timestamp, agent_subject) {
+    (Some(pk), Some(sig), Some(ts), Some(agent)) => Ok(Some(AuthValues {
+      public_key: pk.to_str()?.to_string(),
+      signature: sig.to_str()?.to_string(),
+      timestamp: ts.to_str()?.parse::<i64>()?,
+      agent_subject: agent.to_str()?.to_string(),
+      requested_subject,
+    })),
+    (None, None, None, None) => Ok(None),
+    _ => Err("Missing authentication headers. You need `x-atomic-public-key`, `x-atomic-signature`, `x-atomic-timestamp` and `x-atomic-agent` for authentication checks.".into()),
+  }
+}
@@ -0,0 +1,280 @@
+//! Rate limits and quotas for the `/commit` endpoint.
+//! Rate limits are counted per Agent and per IP address, in windows of one minute.
+//! Quotas limit the amount of Resources and bytes that are stored per Drive.
+
+use crate::{config::Config, errors::AppError, errors::BetterResult};
+use atomic_lib::{
+    errors::AtomicResult, hierarchy::find_drive, urls, Commit, Db, Resource, Storelike, Value,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Length of a rate limit window in milliseconds
+const WINDOW_MS: i64 = 60_000;
+
+/// Returns the IP address that the rate limit of a request is counted for.
+/// Anyone can set the Forwarded headers, so these are only used if the request comes from a trusted proxy.
+pub fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded: Option<&str>,
+    trusted_proxies: &[IpAddr],
+) -> Option<String> {
+    let peer = peer?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer.to_string());
+    }
+    match forwarded {
+        // The forwarded address can include a port
+        Some(addr) => match addr.parse::<SocketAddr>() {
+            Ok(socket) => Some(socket.ip().to_string()),
+            Err(_) => Some(addr.to_string()),
+        },
+        None => Some(peer.to_string()),
+    }
+}
+
+/// Amount of Resources and bytes that are stored in some Drive.
+/// Can also represent the change in usage caused by a Commit, which is why the values are signed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Usage {
+    pub resources: i64,
+    /// Size of the JSON-AD serialization of the Resources
+    pub bytes: i64,
+}
+
+/// Counts requests per key (e.g. an Agent or IP address) in fixed windows of one minute.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    /// Maximum amount of requests per window. None means unlimited.
+    limit: Option<u32>,
+    /// Per key: the start of the current window and the amount of requests in it
+    windows: HashMap<String, (i64, u32)>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: Option<u32>) -> RateLimiter {
+        RateLimiter {
+            limit,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Registers a request for the key.
+    /// Returns an error message if the key has exceeded its limit in the current window.
+    pub fn check(&mut self, key: &str, now: i64) -> Result<(), String> {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let window = self.windows.entry(key.into()).or_insert((now, 0));
+        if now - window.0 >= WINDOW_MS {
+            *window = (now, 0);
+        }
+        if window.1 >= limit {
+            return Err(format!(
+                "Rate limit of {} Commits per minute exceeded for {}. Try again in {} seconds.",
+                limit,
+                key,
+                (window.0 + WINDOW_MS - now) / 1000 + 1
+            ));
+        }
+        window.1 += 1;
+        Ok(())
+    }
+
+    /// Returns the amount of requests per key in the current window.
+    /// Removes the windows that have expired.
+    pub fn current(&mut self, now: i64) -> HashMap<String, u32> {
+        self.windows.retain(|_key, (start, _count)| now - *start < WINDOW_MS);
+        self.windows
+            .iter()
+            .map(|(key, (_start, count))| (key.clone(), *count))
+            .collect()
+    }
+}
+
+/// Keeps track of the rate limits and the Drive quotas.
+/// Is shared between all server workers.
+#[derive(Debug)]
+pub struct Limits {
+    pub agents: RateLimiter,
+    pub ips: RateLimiter,
+    /// Maximum amount of Resources per Drive. None means unlimited.
+    pub quota_resources: Option<i64>,
+    /// Maximum amount of bytes per Drive. None means unlimited.
+    pub quota_bytes: Option<i64>,
+    /// Usage per Drive. Is calculated on startup if there are quotas, and then kept up to date.
+    drives: HashMap<String, Usage>,
+}
+
+impl Limits {
+    /// If quotas are set, this calculates the usage of every Drive, which iterates over the whole store.
+    /// This is done before the server starts, so Commits never have to wait for it.
+    pub fn new(config: &Config, store: &Db) -> AtomicResult<Limits> {
+        let mut limits = Limits {
+            agents: RateLimiter::new(config.rate_limit_agent),
+            ips: RateLimiter::new(config.rate_limit_ip),
+            quota_resources: config.quota_resources,
+            quota_bytes: config.quota_bytes,
+            drives: HashMap::new(),
+        };
+        if limits.has_quotas() {
+            log::info!("Calculating the usage of Drives for quotas...");
+            limits.drives = calculate_usage(store)?;
+        }
+        Ok(limits)
+    }
+
+    fn has_quotas(&self) -> bool {
+        self.quota_resources.is_some() || self.quota_bytes.is_some()
+    }
+
+    /// Checks if the Drive has room for the change in usage.
+    /// Returns a 413 error if it doesn't.
+    pub fn check_quota(&self, drive: &str, delta: &Usage) -> BetterResult<()> {
+        if !self.has_quotas() {
+            return Ok(());
+        }
+        let current = self.drives.get(drive).cloned().unwrap_or_default();
+        if let Some(max) = self.quota_resources {
+            if delta.resources > 0 && current.resources + delta.resources > max {
+                return Err(AppError::payload_too_large(format!(
+                    "Drive {} has reached its quota of {} Resources.",
+                    drive, max
+                )));
+            }
+        }
+        if let Some(max) = self.quota_bytes {
+            if delta.bytes > 0 && current.bytes + delta.bytes > max {
+                return Err(AppError::payload_too_large(format!(
+                    "Drive {} has reached its quota of {} bytes. It currently uses {} bytes, the Commit would add {}.",
+                    drive, max, current.bytes, delta.bytes
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the change in usage of an applied Commit to the Drive.
+    /// Only counted if there are quotas.
+    pub fn add_usage(&mut self, drive: &str, delta: &Usage) {
+        if !self.has_quotas() {
+            return;
+        }
+        let usage = self.drives.entry(drive.into()).or_default();
+        usage.resources += delta.resources;
+        usage.bytes += delta.bytes;
+    }
+
+    /// Returns the usage of all Drives. Is empty if there are no quotas.
+    pub fn drive_usage(&self) -> &HashMap<String, Usage> {
+        &self.drives
+    }
+}
+
+/// Calculates the usage of every Drive in the store.
+fn calculate_usage(store: &Db) -> AtomicResult<HashMap<String, Usage>> {
+    let mut drives: HashMap<String, Usage> = HashMap::new();
+    for resource in store.all_resources(false) {
+        let drive = match find_drive(store, &resource) {
+            Ok(drive) => drive,
+            Err(e) => {
+                log::warn!("Could not find Drive of {}: {}", resource.get_subject(), e);
+                continue;
+            }
+        };
+        let usage = drives.entry(drive).or_default();
+        usage.resources += 1;
+        usage.bytes += resource_size(&resource)?;
+    }
+    Ok(drives)
+}
+
+fn resource_size(resource: &Resource) -> AtomicResult<i64> {
+    Ok(resource.to_json_ad()?.len() as i64)
+}
+
+/// Finds the Drive that a Commit edits, and how much its usage would change when the Commit is applied.
+/// The Commit itself is stored as well, so it counts towards the usage.
+pub fn commit_usage(store: &Db, commit: &Commit) -> AtomicResult<(String, Usage)> {
+    let existing = store.get_resource(commit.get_subject()).ok();
+    let mut resource = existing
+        .clone()
+        .unwrap_or_else(|| Resource::new(commit.get_subject().into()));
+    // Mirrors the default parent that is set when the Commit is applied
+    if resource.get(urls::PARENT).is_err() {
+        if let Some(self_url) = store.get_self_url().filter(|url| url != commit.get_subject()) {
+            resource.set_propval_unsafe(urls::PARENT.into(), Value::AtomicUrl(self_url))?;
+        }
+    }
+    let changed = commit.apply_changes(resource, store)?;
+    let drive = find_drive(store, &changed)?;
+    let commit_bytes = resource_size(&commit.clone().into_resource(store)?)?;
+    let old_bytes = match &existing {
+        Some(resource) => resource_size(resource)?,
+        None => 0,
+    };
+    let old_resources = if existing.is_some() { 1 } else { 0 };
+    let usage = if commit.destroy == Some(true) {
+        Usage {
+            resources: 1 - old_resources,
+            bytes: commit_bytes - old_bytes,
+        }
+    } else {
+        Usage {
+            resources: 2 - old_resources,
+            bytes: commit_bytes + resource_size(&changed)? - old_bytes,
+        }
+    };
+    Ok((drive, usage))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn client_ip_trusted_proxies() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "1.2.3.4".parse().unwrap();
+        // Untrusted peers can't pick their own key
+        assert_eq!(
+            client_ip(Some(client), Some("5.6.7.8"), &[proxy]).unwrap(),
+            "1.2.3.4"
+        );
+        assert_eq!(
+            client_ip(Some(proxy), Some("5.6.7.8:1234"), &[proxy]).unwrap(),
+            "5.6.7.8"
+        );
+        assert_eq!(client_ip(Some(proxy), None, &[proxy]).unwrap(), "10.0.0.1");
+        assert!(client_ip(None, Some("5.6.7.8"), &[proxy]).is_none());
+    }
+
+    #[test]
+    fn rate_limiter_windows() {
+        let mut limiter = RateLimiter::new(Some(2));
+        let agent = "https://localhost/agents/test";
+        limiter.check(agent, 0).unwrap();
+        limiter.check(agent, 10).unwrap();
+        limiter.check(agent, 20).unwrap_err();
+        // Other keys have their own limit
+        limiter.check("https://localhost/agents/other", 20).unwrap();
+        assert_eq!(limiter.current(30).get(agent), Some(&2));
+        // A new window starts after a minute
+        limiter.check(agent, WINDOW_MS).unwrap();
+        assert_eq!(limiter.current(WINDOW_MS).get(agent), Some(&1));
+        // Expired windows are removed
+        assert!(limiter.current(WINDOW_MS * 3).is_empty());
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut limiter = RateLimiter::new(None);
+        for i in 0..1000 {
+            limiter.check("127.0.0.1", i).unwrap();
+        }
+    }
+}
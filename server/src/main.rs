@@ -1,4 +1,6 @@
+mod actor_messages;
 mod appstate;
+mod commit_monitor;
 mod config;
 mod content_types;
 mod errors;
@@ -6,6 +8,8 @@ mod handlers;
 mod helpers;
 mod https;
 mod jsonerrors;
+mod limits;
+mod mirror;
 mod routes;
 #[cfg(feature = "desktop")]
 mod tray_icon;
@@ -103,6 +107,10 @@ async fn main() -> AtomicResult<()> {
         },
     };
 
+    if let Some(drive) = config.mirror.clone() {
+        mirror::spawn_sync(appstate.store.clone(), drive, config.mirror_interval);
+    }
+
     let server = HttpServer::new(move || {
         let data = web::Data::new(Mutex::new(appstate.clone()));
         // Allow requests from other domains
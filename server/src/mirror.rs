@@ -0,0 +1,18 @@
+//! Keeps a read-only copy of a Drive on another server, see `atomic_lib::mirror`.
+
+use atomic_lib::{Db, Storelike};
+
+/// Starts a thread that pulls the new Commits of the mirrored Drive every `interval` seconds.
+/// Requests are signed by the default Agent of the store, which needs read rights on the Drive.
+pub fn spawn_sync(store: Db, drive: String, interval: u64) {
+    log::info!("Mirroring {} every {} seconds...", drive, interval);
+    std::thread::spawn(move || loop {
+        let agent = store.get_default_agent().ok();
+        match atomic_lib::mirror::sync_drive(&store, &drive, agent.as_ref()) {
+            Ok(0) => {}
+            Ok(applied) => log::info!("Mirrored {} Commits from {}", applied, drive),
+            Err(e) => log::error!("Failed to mirror {}: {}", drive, e),
+        }
+        std::thread::sleep(std::time::Duration::from_secs(interval));
+    });
+}
@@ -16,6 +16,21 @@ pub fn config_routes(app: &mut actix_web::web::ServiceConfig) {
         .service(
             web::scope("/tpf").service(web::resource("").route(web::get().to(handlers::tpf::tpf))),
         )
+        .service(
+            web::resource("/ws")
+                .guard(actix_web::guard::Method(Method::GET))
+                .to(handlers::web_sockets::web_socket_handler),
+        )
+        .service(
+            web::resource("/admin/limits")
+                .guard(actix_web::guard::Method(Method::GET))
+                .to(handlers::admin::get_limits),
+        )
+        .service(
+            web::resource("/admin/validate")
+                .guard(actix_web::guard::Method(Method::GET))
+                .to(handlers::admin::get_validate),
+        )
         .service(
             web::resource("/commit")
                 .guard(actix_web::guard::Method(Method::POST))